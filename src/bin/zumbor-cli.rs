@@ -0,0 +1,270 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fs,
+    io::{self, BufReader},
+    path::Path,
+    process::ExitCode,
+};
+
+use dotenv::dotenv;
+use rusty_ziplod::{
+    commands::zumbor::{
+        combat,
+        encounter::{
+            catalogue::{CatalogueEntry, EncounterCatalogue},
+            Encounter,
+        },
+        lint::{self, LintReport},
+        migration,
+        simulation::{self, CHECKPOINTS},
+        terminal::{self, LOCAL_TAG},
+    },
+    errors::Error,
+    storage::StorageClient,
+};
+
+const USAGE: &str = "Usage: zumbor-cli lint [encounter directory]
+       zumbor-cli migrate
+       zumbor-cli simulate [runs] [encounter directory]
+       zumbor-cli play <storage directory> [tag]";
+
+/// Runs played by each strategy and build when no count is given
+const DEFAULT_RUNS: usize = 1000;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    dotenv().ok();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let res = match args.first().map(String::as_str) {
+        Some("lint") => run_lint(args.get(1)).await,
+        Some("migrate") => run_migrate().await,
+        Some("simulate") => run_simulate(args.get(1), args.get(2)).await,
+        Some("play") => match args.get(1) {
+            Some(directory) => run_play(directory, args.get(2)).await,
+            None => {
+                println!("{}", USAGE);
+                return ExitCode::FAILURE;
+            }
+        },
+        _ => {
+            println!("{}", USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match res {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            println!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/**
+ * Lints a local directory of encounters, or the bucket when no directory is given
+ */
+async fn run_lint(directory: Option<&String>) -> Result<bool, Error> {
+    let report = match directory {
+        Some(directory) => {
+            let mut report = LintReport::new();
+            lint_directory(Path::new(directory), &mut report)?;
+            report
+        }
+        None => {
+            let bucket_name = env::var("CLOUD_BUCKET_NAME").expect("Bucket name");
+            lint::lint_storage(&StorageClient::new(bucket_name).await).await?
+        }
+    };
+
+    for (name, issues) in &report.files {
+        println!("{}", name);
+        for issue in issues {
+            println!("    {}", issue);
+        }
+    }
+
+    println!(
+        "Checked {} encounters and found {} errors and {} warnings in {} of them",
+        report.checked,
+        report.error_count(),
+        report.issue_count() - report.error_count(),
+        report.files.len()
+    );
+
+    Ok(report.error_count() == 0)
+}
+
+/**
+ * Upgrades every encounter and save in the bucket to the current version
+ */
+async fn run_migrate() -> Result<bool, Error> {
+    let bucket_name = env::var("CLOUD_BUCKET_NAME").expect("Bucket name");
+    let report = migration::migrate_storage(&StorageClient::new(bucket_name).await).await?;
+
+    for name in &report.migrated {
+        println!("Migrated {}", name);
+    }
+    for (name, err) in &report.failed {
+        println!("Failed to migrate {}", name);
+        println!("    {}", err);
+    }
+
+    println!(
+        "Checked {} documents, migrated {} and failed to migrate {}",
+        report.checked,
+        report.migrated.len(),
+        report.failed.len()
+    );
+
+    Ok(report.failed.is_empty())
+}
+
+/**
+ * Plays simulated runs against a local directory of encounters, or the bucket when no directory is given
+ */
+async fn run_simulate(runs: Option<&String>, directory: Option<&String>) -> Result<bool, Error> {
+    let runs = match runs {
+        Some(runs) => runs
+            .parse()
+            .map_err(|_| Error::Plain("The number of runs should be a number"))?,
+        None => DEFAULT_RUNS,
+    };
+
+    // Enemies are looked up where the bucket keeps them, beside the encounter directory when given one
+    let (catalogue, storage_client) = match directory {
+        Some(directory) => {
+            let mut entries = Vec::new();
+            load_directory(Path::new(directory), &mut entries)?;
+            (
+                EncounterCatalogue::new(entries, BTreeMap::new()),
+                StorageClient::local(Path::new(directory).join("../..")),
+            )
+        }
+        None => {
+            let bucket_name = env::var("CLOUD_BUCKET_NAME").expect("Bucket name");
+            let storage_client = StorageClient::new(bucket_name).await;
+            (
+                EncounterCatalogue::load(&storage_client).await?,
+                storage_client,
+            )
+        }
+    };
+
+    let mut enemies = HashMap::new();
+    for id in simulation::enemy_ids(&catalogue) {
+        match combat::load_from(&storage_client, &id).await {
+            Ok(template) => {
+                enemies.insert(id, template);
+            }
+            Err(err) => println!(
+                "Warning: results fighting {} play out without a fight, it couldn't be loaded. {}",
+                id, err
+            ),
+        }
+    }
+
+    println!(
+        "Simulating {} runs per strategy and build against {} encounters\n",
+        runs,
+        catalogue.entries.len()
+    );
+
+    let checkpoints: Vec<String> = CHECKPOINTS
+        .iter()
+        .map(|checkpoint| format!("{:>7}", checkpoint))
+        .collect();
+    println!(
+        "{:<14} {:<20} {:>9} {:>10} | alive after {}",
+        "Strategy",
+        "Build",
+        "Avg score",
+        "Avg length",
+        checkpoints.join("")
+    );
+
+    for report in simulation::simulate(&catalogue, &enemies, runs, &mut rand::thread_rng()) {
+        let survival: Vec<String> = report
+            .survival()
+            .iter()
+            .map(|percentage| format!("{:>6.1}%", percentage))
+            .collect();
+
+        println!(
+            "{:<14} {:<20} {:>9.1} {:>10.1} |            {}",
+            report.strategy.to_string(),
+            report.build,
+            report.average_score(),
+            report.average_encounters(),
+            survival.join("")
+        );
+    }
+
+    let flags = simulation::check_thresholds(&catalogue);
+    println!(
+        "\n{} options have thresholds out of line with typical builds",
+        flags.len()
+    );
+    for flag in &flags {
+        println!(
+            "{} {} (threshold {}) is {}",
+            flag.path, flag.option, flag.threshold, flag.issue
+        );
+    }
+
+    Ok(true)
+}
+
+/**
+ * Plays in the terminal against a local directory laid out like the bucket, with encounters under
+ * `zumbor/encounters/` and saves written to `zumbor/saves/`
+ */
+async fn run_play(directory: &str, tag: Option<&String>) -> Result<bool, Error> {
+    let storage_client = StorageClient::local(directory);
+    let tag = tag.map(String::as_str).unwrap_or(LOCAL_TAG);
+
+    terminal::play(&storage_client, tag, &mut BufReader::new(io::stdin())).await?;
+
+    Ok(true)
+}
+
+/**
+ * Reads every valid encounter in the directory into catalogue entries, skipping invalid ones
+ */
+fn load_directory(directory: &Path, entries: &mut Vec<CatalogueEntry>) -> Result<(), Error> {
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            load_directory(&path, entries)?;
+        } else if path.extension().is_some_and(|ext| ext == "json") {
+            match Encounter::from_slice(&fs::read(&path)?) {
+                Ok(encounter) => entries.push(CatalogueEntry {
+                    path: path.display().to_string(),
+                    encounter,
+                }),
+                Err(err) => println!("Skipping invalid encounter {}. {}", path.display(), err),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn lint_directory(directory: &Path, report: &mut LintReport) -> Result<(), Error> {
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            lint_directory(&path, report)?;
+        } else if path.extension().is_some_and(|ext| ext == "json") {
+            let bytes = fs::read(&path)?;
+            report.add(path.display().to_string(), &bytes);
+        }
+    }
+
+    Ok(())
+}
@@ -22,6 +22,8 @@ fn get_prompt(i: usize) -> &'static str {
 
 pub struct ChatBot {
     pub client: Chat,
+    /// The model behind the chat, shared with anything that generates text outside of it
+    pub model: Llama,
 }
 
 impl ChatBot {
@@ -31,11 +33,14 @@ impl ChatBot {
             .build()
             .await
             .unwrap();
-        let chat = Chat::builder(model)
+        let chat = Chat::builder(model.clone())
             .with_system_prompt(get_prompt(0))
             .build();
 
-        Ok(ChatBot { client: chat })
+        Ok(ChatBot {
+            client: chat,
+            model,
+        })
     }
 
     pub async fn prompt(&mut self, msg: &str) -> Result<String, Error> {
@@ -55,11 +60,12 @@ impl ChatBot {
             .build()
             .await
             .unwrap();
-        let chat = Chat::builder(model)
+        let chat = Chat::builder(model.clone())
             .with_system_prompt(get_prompt(0))
             .build();
 
         self.client = chat;
+        self.model = model;
     }
 }
 
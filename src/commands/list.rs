@@ -6,12 +6,10 @@ use serenity::{
 
 use crate::{
     errors::Error,
-    storage::StorageClient,
+    storage::{StorageClient, StoredObject},
     utilities::message,
 };
 
-use cloud_storage::Object;
-
 #[command]
 pub async fn list(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     println!("The play command has been triggered");
@@ -43,7 +41,7 @@ pub async fn list(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult
     return Ok(());
 }
 
-pub async fn get_tracks(ctx: &Context, track_type: &str) -> Result<Vec<Object>, Error> {
+pub async fn get_tracks(ctx: &Context, track_type: &str) -> Result<Vec<StoredObject>, Error> {
     let data = ctx.data.read().await;
     let storage_client = data
         .get::<StorageClient>()
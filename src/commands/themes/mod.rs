@@ -1,4 +1,8 @@
-use crate::{errors::Error, storage::StorageClient, utilities::random::random_range};
+use crate::{
+    errors::Error,
+    storage::{StorageClient, StoredObject},
+    utilities::random::random_range,
+};
 use serenity::{
     all::User,
     framework::standard::{macros::command, Args, CommandResult},
@@ -75,6 +79,6 @@ pub async fn get_theme_list(
     tag: &str,
     kind: &str,
     client: &StorageClient,
-) -> Result<Vec<cloud_storage::Object>, Error> {
+) -> Result<Vec<StoredObject>, Error> {
     client.get_objects(&get_theme_prefix(tag, kind)).await
 }
@@ -1,53 +1,117 @@
+use std::collections::{hash_map::Entry, HashMap};
+
 use serenity::{
     all::UserId,
-    framework::standard::{macros::command, CommandResult},
+    framework::standard::{macros::command, Args, CommandResult},
     model::prelude::Message,
     prelude::{Context, TypeMapKey},
 };
 
-mod attributes;
-mod effects;
-mod encounter;
+pub mod achievements;
+pub mod attributes;
+mod authoring;
+pub mod boss;
+pub mod classes;
+pub mod combat;
+mod daily;
+mod duel;
+pub mod effects;
+pub mod encounter;
+pub mod events;
+#[cfg(feature = "chat")]
+mod generation;
 mod initialise;
-mod player;
+pub mod items;
+pub mod lint;
+pub mod migration;
+pub mod party;
+pub mod player;
+pub mod replay;
+pub mod rules;
+pub mod session;
+pub mod sheet;
+pub mod shop;
+pub mod simulation;
+pub mod terminal;
 mod ui;
+pub mod zone;
 use initialise::start;
+use session::Session;
 
 use crate::errors::Error;
 
 #[command]
-pub async fn zumbor(ctx: &Context, msg: &Message) -> CommandResult {
-    let res = start(ctx, msg).await;
-    match res {
-        Ok(some) => println!("{}", some),
-        Err(err) => println!("{}", err),
+pub async fn zumbor(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let res: Result<(), Error> = match args.single::<String>().ok().as_deref() {
+        None => start(ctx, msg).await.map(|some| println!("{}", some)),
+        Some("lint") => lint::lint(ctx, msg).await,
+        Some("migrate") => migration::migrate(ctx, msg).await,
+        Some("party") => party::host(ctx, msg).await,
+        Some("sessions") => session::sessions(ctx, msg, args).await,
+        Some("daily") => match args.single::<String>().ok().as_deref() {
+            None => daily::start(ctx, msg).await,
+            Some("leaderboard") => daily::leaderboard(ctx, msg).await,
+            _ => Err(Error::Plain("No matching daily subcommand")),
+        },
+        Some("duel") => duel::challenge(ctx, msg, args).await,
+        Some("achievements") => achievements::show(ctx, msg).await,
+        Some("replay") => replay::replay(ctx, msg, args).await,
+        Some("rules") => rules::rules(ctx, msg, args).await,
+        Some("export") => sheet::export(ctx, msg).await,
+        Some("import") => sheet::import(ctx, msg).await,
+        Some("boss") => boss::schedule(ctx, msg, args).await,
+        Some("encounter") => match args.single::<String>().ok().as_deref() {
+            Some("new") => authoring::new(ctx, msg).await,
+            #[cfg(feature = "chat")]
+            Some("generate") => generation::generate(ctx, msg, args).await,
+            Some("review") => authoring::review(ctx, msg).await,
+            _ => Err(Error::Plain("No matching encounter subcommand")),
+        },
+        Some(_) => Err(Error::Plain("No matching subcommand")),
+    };
+
+    if let Err(err) = res {
+        println!("{}", err);
     }
+
     Ok(())
 }
 
 #[derive(Default, Debug)]
 pub struct ZumborInstances {
-    instances: Vec<UserId>,
+    instances: HashMap<UserId, Session>,
 }
 
 impl ZumborInstances {
-    pub fn add(&mut self, user_id: UserId) -> Result<(), Error> {
-        if self.instances.contains(&user_id) {
-            Err(Error::Plain(
+    pub fn add(&mut self, user_id: UserId, session: Session) -> Result<(), Error> {
+        match self.instances.entry(user_id) {
+            Entry::Occupied(_) => Err(Error::Plain(
                 "The user currently has an active Zumbor instance",
-            ))
-        } else {
-            self.instances.push(user_id);
-            println!("{:?}", self.instances);
-            Ok(())
+            )),
+            Entry::Vacant(entry) => {
+                entry.insert(session);
+                Ok(())
+            }
+        }
+    }
+
+    /// Only removes the session with the given id, in case the user has since started another
+    pub fn remove(&mut self, user_id: UserId, session_id: u64) {
+        if self
+            .instances
+            .get(&user_id)
+            .is_some_and(|session| session.id == session_id)
+        {
+            self.instances.remove(&user_id);
         }
     }
 
-    pub fn remove(&mut self, user_id: UserId) {
-        println!("{:?}", self.instances);
-        self.instances
-            .retain(|&instance_user_id| instance_user_id != user_id);
-        println!("{:?}", self.instances);
+    pub fn get(&self, user_id: UserId) -> Option<&Session> {
+        self.instances.get(&user_id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&UserId, &Session)> {
+        self.instances.iter()
     }
 }
 
@@ -0,0 +1,351 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    all::{Colour, CreateEmbed, CreateMessage},
+    model::prelude::Message,
+    prelude::Context,
+};
+
+use crate::{errors::Error, storage::StorageClient};
+
+use super::{
+    effects::LingeringEffectName,
+    events::{EventListener, EventSender, GameEvent},
+};
+
+const ACHIEVEMENT_PREFIX: &str = "zumbor/achievements/";
+
+const SURVIVOR_ENCOUNTERS: u32 = 50;
+const HOT_STREAK_CRITICALS: u8 = 3;
+const SEASONED_LEVEL: u8 = 5;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Achievement {
+    Survivor,
+    HotStreak,
+    Poisoned,
+    Honed,
+    Seasoned,
+    Fallen,
+}
+
+impl Achievement {
+    pub const VALUES: [Achievement; 6] = [
+        Achievement::Survivor,
+        Achievement::HotStreak,
+        Achievement::Poisoned,
+        Achievement::Honed,
+        Achievement::Seasoned,
+        Achievement::Fallen,
+    ];
+
+    pub fn description(&self) -> String {
+        match self {
+            Achievement::Survivor => format!("Survive {} encounters", SURVIVOR_ENCOUNTERS),
+            Achievement::HotStreak => {
+                format!("Roll {} critical successes in a row", HOT_STREAK_CRITICALS)
+            }
+            Achievement::Poisoned => "Die to poison".to_string(),
+            Achievement::Honed => "Raise a stat as high as your level allows".to_string(),
+            Achievement::Seasoned => format!("Reach level {}", SEASONED_LEVEL),
+            Achievement::Fallen => "Lose a character".to_string(),
+        }
+    }
+
+    /// The cosmetic title the achievement awards, shown alongside the character's name
+    pub fn title(&self) -> Option<&'static str> {
+        match self {
+            Achievement::Survivor => Some("the Enduring"),
+            Achievement::HotStreak => Some("the Blessed"),
+            Achievement::Poisoned => Some("the Green"),
+            Achievement::Honed => Some("the Honed"),
+            Achievement::Seasoned | Achievement::Fallen => None,
+        }
+    }
+}
+
+impl Display for Achievement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Achievement::Survivor => write!(f, "Survivor"),
+            Achievement::HotStreak => write!(f, "Hot streak"),
+            Achievement::Poisoned => write!(f, "Should have checked the label"),
+            Achievement::Honed => write!(f, "Honed"),
+            Achievement::Seasoned => write!(f, "Seasoned"),
+            Achievement::Fallen => write!(f, "Fallen"),
+        }
+    }
+}
+
+/// A user's unlocks and progress, kept apart from their save so it outlives their characters
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct AchievementRecord {
+    /// Each achievement with when it was unlocked, in seconds since the unix epoch
+    pub unlocked: Vec<(Achievement, u64)>,
+    /// Encounters survived by the user's current character, started over when it falls
+    pub encounters: u32,
+}
+
+impl AchievementRecord {
+    pub fn has(&self, achievement: Achievement) -> bool {
+        self.unlocked
+            .iter()
+            .any(|(unlocked, _)| *unlocked == achievement)
+    }
+
+    /**
+     * Unlocks the achievement, returning whether it was newly unlocked
+     */
+    pub fn unlock(&mut self, achievement: Achievement) -> bool {
+        if self.has(achievement) {
+            return false;
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+        self.unlocked.push((achievement, now));
+        true
+    }
+
+    /// The title from the most recently unlocked achievement that has one
+    pub fn title(&self) -> Option<&'static str> {
+        self.unlocked
+            .iter()
+            .rev()
+            .find_map(|(achievement, _)| achievement.title())
+    }
+}
+
+/**
+ * Loads a user's achievements, starting them afresh only if they have none saved yet
+ */
+pub async fn load(
+    storage_client: &StorageClient,
+    user_tag: &str,
+) -> Result<AchievementRecord, Error> {
+    match storage_client
+        .get_json(&format!("{}{}", ACHIEVEMENT_PREFIX, user_tag))
+        .await
+    {
+        Err(err) if err.is_not_found() => Ok(AchievementRecord::default()),
+        result => result,
+    }
+}
+
+/// The title a user's characters go by, if they have earned one
+pub async fn title_for(ctx: &Context, user_tag: &str) -> Option<String> {
+    let data = ctx.data.read().await;
+    let storage_client = data.get::<StorageClient>()?;
+
+    load(storage_client, user_tag)
+        .await
+        .ok()?
+        .title()
+        .map(str::to_string)
+}
+
+async fn save(
+    storage_client: &StorageClient,
+    user_tag: &str,
+    record: &AchievementRecord,
+) -> Result<(), Error> {
+    storage_client
+        .create_json(
+            &format!("{}{}.json", ACHIEVEMENT_PREFIX, user_tag),
+            serde_json::to_string(record)?,
+        )
+        .await
+}
+
+/**
+ * Watches a run's events for achievements, announcing unlocks back onto the bus
+ */
+pub struct AchievementListener {
+    events: EventSender,
+    critical_streaks: HashMap<String, u8>,
+    /// Tags of players whose health was taken to nothing by a poison tick
+    poisoned: HashSet<String>,
+}
+
+impl AchievementListener {
+    pub fn new(events: EventSender) -> Self {
+        AchievementListener {
+            events,
+            critical_streaks: HashMap::new(),
+            poisoned: HashSet::new(),
+        }
+    }
+
+    async fn update(
+        &self,
+        ctx: &Context,
+        tag: &str,
+        name: &str,
+        change: impl FnOnce(&mut AchievementRecord) -> Vec<Achievement>,
+    ) {
+        let data = ctx.data.read().await;
+        let Some(storage_client) = data.get::<StorageClient>() else {
+            return;
+        };
+
+        // Saving over a record we failed to read would wipe the user's unlocks
+        let mut record = match load(storage_client, tag).await {
+            Ok(record) => record,
+            Err(err) => {
+                println!("Unable to load achievements for {}. {}", tag, err);
+                return;
+            }
+        };
+        let unlocked: Vec<Achievement> = change(&mut record)
+            .into_iter()
+            .filter(|achievement| record.unlock(*achievement))
+            .collect();
+
+        if let Err(err) = save(storage_client, tag, &record).await {
+            println!("Unable to save achievements for {}. {}", tag, err);
+            return;
+        }
+
+        for achievement in unlocked {
+            let _ = self.events.send(GameEvent::AchievementUnlocked {
+                tag: tag.to_string(),
+                name: name.to_string(),
+                achievement,
+            });
+        }
+    }
+}
+
+impl EventListener for AchievementListener {
+    fn handle<'a>(&'a mut self, ctx: &'a Context, event: &'a GameEvent) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            match event {
+                GameEvent::Succumbed {
+                    tag,
+                    effect: LingeringEffectName::Poison,
+                    ..
+                } => {
+                    self.poisoned.insert(tag.clone());
+                }
+                GameEvent::EncounterResolved {
+                    tag,
+                    name,
+                    succeeded,
+                    critical,
+                    survived,
+                    ..
+                } => {
+                    let streak = self.critical_streaks.entry(tag.clone()).or_default();
+                    *streak = if *critical && *succeeded {
+                        *streak + 1
+                    } else {
+                        0
+                    };
+                    let streak = *streak;
+
+                    self.update(ctx, tag, name, |record| {
+                        if *survived {
+                            record.encounters += 1;
+                        }
+
+                        let mut unlocked = Vec::new();
+                        if record.encounters >= SURVIVOR_ENCOUNTERS {
+                            unlocked.push(Achievement::Survivor);
+                        }
+                        if streak >= HOT_STREAK_CRITICALS {
+                            unlocked.push(Achievement::HotStreak);
+                        }
+                        unlocked
+                    })
+                    .await;
+                }
+                GameEvent::StatsAllocated {
+                    tag,
+                    name,
+                    stats,
+                    max_stat,
+                    ..
+                } if stats.get_max() >= *max_stat => {
+                    self.update(ctx, tag, name, |_| vec![Achievement::Honed])
+                        .await;
+                }
+                GameEvent::LevelUp { tag, name, level } if *level >= SEASONED_LEVEL => {
+                    self.update(ctx, tag, name, |_| vec![Achievement::Seasoned])
+                        .await;
+                }
+                GameEvent::Death { tag, name, .. } => {
+                    let poisoned = self.poisoned.remove(tag);
+                    self.update(ctx, tag, name, |record| {
+                        record.encounters = 0;
+
+                        if poisoned {
+                            vec![Achievement::Fallen, Achievement::Poisoned]
+                        } else {
+                            vec![Achievement::Fallen]
+                        }
+                    })
+                    .await;
+                }
+                _ => (),
+            }
+        })
+    }
+}
+
+/**
+ * Shows the achievements of the mentioned user, or the author's own
+ */
+pub async fn show(ctx: &Context, msg: &Message) -> Result<(), Error> {
+    let user = msg.mentions.first().unwrap_or(&msg.author);
+
+    let record = {
+        let data = ctx.data.read().await;
+        let storage_client = data
+            .get::<StorageClient>()
+            .ok_or(Error::Plain("Storage client not accessible!"))?;
+
+        load(storage_client, &user.tag()).await?
+    };
+
+    let embed = Achievement::VALUES.into_iter().fold(
+        CreateEmbed::new()
+            .title(format!("{}'s achievements", user.name))
+            .description(format!(
+                "{} of {} unlocked",
+                record.unlocked.len(),
+                Achievement::VALUES.len()
+            ))
+            .colour(Colour::GOLD),
+        |embed, achievement| {
+            let status = if record.has(achievement) {
+                "✅"
+            } else {
+                "🔒"
+            };
+            let reward = achievement
+                .title()
+                .map(|title| format!("\nTitle: *{}*", title))
+                .unwrap_or_default();
+
+            embed.field(
+                format!("{} {}", status, achievement),
+                format!("{}{}", achievement.description(), reward),
+                false,
+            )
+        },
+    );
+
+    msg.channel_id
+        .send_message(ctx, CreateMessage::new().embed(embed))
+        .await?;
+
+    Ok(())
+}
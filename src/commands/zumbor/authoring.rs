@@ -0,0 +1,702 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{json, Value};
+use serenity::{
+    all::{
+        ActionRow, ActionRowComponent, ButtonStyle, ChannelId, ComponentInteraction,
+        CreateActionRow, CreateButton, CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter,
+        CreateInputText, CreateInteractionResponseMessage, CreateMessage, CreateModal,
+        InputTextStyle, ModalInteraction,
+    },
+    builder::CreateInteractionResponse,
+    model::prelude::Message,
+    prelude::Context,
+};
+
+use crate::{
+    errors::Error,
+    storage::StorageClient,
+    utilities::{await_interactions, permissions},
+};
+
+use super::{
+    attributes::Attribute,
+    encounter::{
+        self,
+        schema::{self, BaseEffectV1, LingeringEffectV1},
+        validation, Difficulty, Encounter, EncounterOption, EncounterResult, EncounterResultKind,
+        Rarity,
+    },
+    migration::Versioned,
+};
+
+const APPROVAL_TIMEOUT: Duration = Duration::from_secs(60 * 60 * 24);
+
+/// Encounters wait here as `zumbor/submitted/{id}.json`, along with who wrote them, until a moderator reviews them
+const QUEUE_PREFIX: &str = "zumbor/submitted/";
+
+/// Where submissions are moved while they are posted for review
+const REVIEW_PREFIX: &str = "zumbor/reviewing/";
+
+const REVIEW_NOTICE: &str = "Moderators can approve it with `zumbor encounter review`";
+
+/// An encounter taken out of the queue for review, with where it is kept meanwhile
+struct Submission {
+    path: String,
+    author: String,
+    encounter: Encounter,
+}
+
+/**
+ * Walks the author through writing an encounter, then queues it up for moderators to approve
+ */
+pub async fn new(ctx: &Context, msg: &Message) -> Result<(), Error> {
+    let user_tag: Arc<str> = msg.author.tag().into();
+
+    let message = msg
+        .channel_id
+        .send_message(
+            ctx,
+            CreateMessage::new()
+                .embed(CreateEmbed::new().title("Write an encounter"))
+                .button(CreateButton::new("encounter_details").label("Start")),
+        )
+        .await?;
+
+    let interaction = await_interactions::component(ctx, &message, user_tag.clone()).await?;
+    respond_with_modal(ctx, &interaction, create_details_modal()).await?;
+    let mut interaction = await_interactions::modal(ctx, &message, user_tag.clone()).await?;
+
+    let mut encounter = loop {
+        let inputs = modal_inputs(&interaction.data.components);
+        match parse_details(&inputs) {
+            Ok(encounter) => break encounter,
+            Err(err) => {
+                update_message(
+                    ctx,
+                    &interaction,
+                    CreateEmbed::new()
+                        .title("That encounter doesn't look right...")
+                        .description(err.to_string()),
+                    vec![CreateButton::new("encounter_details").label("Try Again")],
+                )
+                .await?;
+                let retry = await_interactions::component(ctx, &message, user_tag.clone()).await?;
+                respond_with_modal(ctx, &retry, create_details_modal()).await?;
+                interaction = await_interactions::modal(ctx, &message, user_tag.clone()).await?;
+            }
+        }
+    };
+
+    let mut notice: Option<String> = None;
+
+    loop {
+        update_message(
+            ctx,
+            &interaction,
+            preview(&encounter, notice.take()),
+            vec![
+                CreateButton::new("add_option")
+                    .label("Add an option")
+                    .disabled(encounter.options.len() >= validation::MAX_OPTIONS),
+                CreateButton::new("finish")
+                    .label("Submit for approval")
+                    .style(ButtonStyle::Success)
+                    .disabled(encounter.options.is_empty()),
+                CreateButton::new("cancel")
+                    .label("Scrap it")
+                    .style(ButtonStyle::Danger),
+            ],
+        )
+        .await?;
+
+        let choice = await_interactions::component(ctx, &message, user_tag.clone()).await?;
+
+        match choice.data.custom_id.as_str() {
+            "add_option" => {
+                let (option_interaction, option) =
+                    prompt_for_option(ctx, &message, user_tag.clone(), choice).await?;
+                interaction = option_interaction;
+
+                let (name, option) = match option {
+                    Ok(option) => option,
+                    Err(err) => {
+                        notice = Some(format!("That option was thrown out: {}", err));
+                        continue;
+                    }
+                };
+
+                encounter.options.insert(name.clone(), option);
+
+                let issues = validation::validate(&serde_json::to_value(&encounter)?);
+                if !issues.is_empty() {
+                    encounter.options.remove(&name);
+                    notice = Some(format!(
+                        "The {} option was thrown out:\n{}",
+                        name,
+                        issues
+                            .iter()
+                            .map(|issue| issue.to_string())
+                            .collect::<Vec<String>>()
+                            .join("\n")
+                    ));
+                }
+            }
+            "finish" => {
+                {
+                    let data = ctx.data.read().await;
+                    let storage_client = data
+                        .get::<StorageClient>()
+                        .ok_or(Error::Plain("Storage client not accessible!"))?;
+
+                    submit(storage_client, &encounter, &msg.author.name).await?;
+                }
+
+                choice
+                    .create_response(
+                        ctx,
+                        CreateInteractionResponse::UpdateMessage(
+                            CreateInteractionResponseMessage::new()
+                                .embed(
+                                    preview(&encounter, Some(REVIEW_NOTICE.to_string()))
+                                        .title(format!("{} (awaiting approval)", encounter.title)),
+                                )
+                                .components(Vec::new()),
+                        ),
+                    )
+                    .await?;
+                return Ok(());
+            }
+            _ => {
+                choice
+                    .create_response(
+                        ctx,
+                        CreateInteractionResponse::UpdateMessage(
+                            CreateInteractionResponseMessage::new()
+                                .embed(CreateEmbed::new().title("Encounter scrapped"))
+                                .components(Vec::new()),
+                        ),
+                    )
+                    .await?;
+                return Ok(());
+            }
+        }
+    }
+}
+
+/**
+ * Queues the encounter up for the next review, credited to the author
+ */
+pub async fn submit(
+    storage_client: &StorageClient,
+    encounter: &Encounter,
+    author: &str,
+) -> Result<(), Error> {
+    let submitted = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default();
+
+    storage_client
+        .create_json(
+            &format!(
+                "{}{}-{:08x}.json",
+                QUEUE_PREFIX,
+                submitted,
+                rand::random::<u32>()
+            ),
+            serde_json::to_string(&json!({ "author": author, "encounter": encounter }))?,
+        )
+        .await
+}
+
+/**
+ * Takes every valid submission out of the queue and puts it under review. Only one review can
+ * delete an entry from the queue, so reviews running at once never post the same encounter
+ */
+async fn claim(storage_client: &StorageClient) -> Result<Vec<Submission>, Error> {
+    let mut claimed = Vec::new();
+
+    for object in storage_client.get_objects(QUEUE_PREFIX).await? {
+        let bytes = storage_client.get(&object.name).await?;
+        let submission = serde_json::from_slice::<Value>(&bytes)
+            .map_err(Error::from)
+            .and_then(|mut value| {
+                let author = value["author"].as_str().unwrap_or("someone").to_string();
+                Encounter::from_versioned(value["encounter"].take())
+                    .map(|encounter| (author, encounter))
+            });
+        let (author, encounter) = match submission {
+            Ok(submission) => submission,
+            Err(err) => {
+                println!("Skipping invalid submission {}. {}", object.name, err);
+                continue;
+            }
+        };
+
+        if let Err(err) = storage_client.delete(&object.name).await {
+            println!("{} was claimed by another review. {}", object.name, err);
+            continue;
+        }
+
+        let path = object.name.replacen(QUEUE_PREFIX, REVIEW_PREFIX, 1);
+        storage_client
+            .create(bytes, &path, "application/json")
+            .await?;
+        claimed.push(Submission {
+            path,
+            author,
+            encounter,
+        });
+    }
+
+    Ok(claimed)
+}
+
+/// Puts a submission that wasn't decided on back in the queue for the next review
+async fn requeue(storage_client: &StorageClient, path: &str) -> Result<(), Error> {
+    let bytes = storage_client.get(path).await?;
+    storage_client
+        .create(
+            bytes,
+            &path.replacen(REVIEW_PREFIX, QUEUE_PREFIX, 1),
+            "application/json",
+        )
+        .await?;
+    storage_client.delete(path).await
+}
+
+/**
+ * Posts every queued encounter for moderators to approve or reject. Encounters leave the queue
+ * while they are posted and only go back to it if nobody decides on them
+ */
+pub async fn review(ctx: &Context, msg: &Message) -> Result<(), Error> {
+    if !permissions::is_moderator(ctx, msg).await {
+        msg.reply(ctx, "Only moderators can review submitted encounters")
+            .await?;
+        return Err(Error::Plain("Encounters reviewed by a non moderator"));
+    }
+
+    let queued = {
+        let data = ctx.data.read().await;
+        let storage_client = data
+            .get::<StorageClient>()
+            .ok_or(Error::Plain("Storage client not accessible!"))?;
+
+        claim(storage_client).await?
+    };
+
+    if queued.is_empty() {
+        msg.reply(ctx, "There's nothing waiting for review").await?;
+        return Ok(());
+    }
+
+    for submission in queued {
+        let ctx = ctx.clone();
+        let channel_id = msg.channel_id;
+
+        tokio::spawn(async move {
+            let approval =
+                request_approval(&ctx, channel_id, &submission.encounter, &submission.author).await;
+
+            let data = ctx.data.read().await;
+            let Some(storage_client) = data.get::<StorageClient>() else {
+                return;
+            };
+
+            let path = &submission.path;
+            let res = match approval {
+                Ok(()) => storage_client.delete(path).await,
+                Err(err) => {
+                    println!("{} is going back in the queue. {}", path, err);
+                    requeue(storage_client, path).await
+                }
+            };
+            if let Err(err) = res {
+                println!("Unable to take {} out of review. {}", path, err);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+/**
+ * Posts the encounter for moderators to approve, saving it into the v2 encounters once they do
+ */
+async fn request_approval(
+    ctx: &Context,
+    channel: ChannelId,
+    encounter: &Encounter,
+    author: &str,
+) -> Result<(), Error> {
+    let message = channel
+        .send_message(
+            ctx,
+            CreateMessage::new()
+                .embed(
+                    preview(encounter, None)
+                        .author(CreateEmbedAuthor::new(format!("Written by {}", author))),
+                )
+                .button(
+                    CreateButton::new("approve")
+                        .label("Approve")
+                        .style(ButtonStyle::Success),
+                )
+                .button(
+                    CreateButton::new("reject")
+                        .label("Reject")
+                        .style(ButtonStyle::Danger),
+                ),
+        )
+        .await?;
+
+    let interaction =
+        await_interactions::moderator_component(ctx, &message, APPROVAL_TIMEOUT).await?;
+    let moderator = interaction.user.name.clone();
+
+    let outcome = if interaction.data.custom_id == "approve" {
+        let data = ctx.data.read().await;
+        let storage_client = data
+            .get::<StorageClient>()
+            .ok_or(Error::Plain("Storage client not accessible!"))?;
+
+        let path = encounter::save(storage_client, encounter).await?;
+        format!("Approved by {} and saved to {}", moderator, path)
+    } else {
+        format!("Rejected by {}", moderator)
+    };
+
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .embed(preview(encounter, Some(outcome)))
+                    .components(Vec::new()),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+fn preview(encounter: &Encounter, notice: Option<String>) -> CreateEmbed {
+    let embed: CreateEmbed = CreateEmbed::from(encounter).footer(CreateEmbedFooter::new(format!(
+        "{:?} {:?} encounter",
+        encounter.rarity, encounter.difficulty
+    )));
+
+    let embed = encounter
+        .options
+        .iter()
+        .fold(embed, |embed, (name, option)| {
+            embed.field(
+                name,
+                format!(
+                    "{} {}+\n✅ {}\n❌ {}",
+                    option.stat, option.threshold, option.success.title, option.fail.title
+                ),
+                true,
+            )
+        });
+
+    match notice {
+        Some(notice) => embed.field("Note", notice, false),
+        None => embed,
+    }
+}
+
+async fn prompt_for_option(
+    ctx: &Context,
+    message: &Message,
+    user_tag: Arc<str>,
+    interaction: ComponentInteraction,
+) -> Result<(ModalInteraction, Result<(String, EncounterOption), Error>), Error> {
+    respond_with_modal(ctx, &interaction, create_option_modal()).await?;
+    let option_interaction = await_interactions::modal(ctx, message, user_tag.clone()).await?;
+    let option_inputs = modal_inputs(&option_interaction.data.components);
+
+    update_message(
+        ctx,
+        &option_interaction,
+        CreateEmbed::new().title("What happens when they succeed?"),
+        vec![CreateButton::new("success").label("Success")],
+    )
+    .await?;
+    let interaction = await_interactions::component(ctx, message, user_tag.clone()).await?;
+    respond_with_modal(
+        ctx,
+        &interaction,
+        create_result_modal("success", "On success"),
+    )
+    .await?;
+    let success_interaction = await_interactions::modal(ctx, message, user_tag.clone()).await?;
+    let success_inputs = modal_inputs(&success_interaction.data.components);
+
+    update_message(
+        ctx,
+        &success_interaction,
+        CreateEmbed::new().title("And when they fail?"),
+        vec![CreateButton::new("fail").label("Fail")],
+    )
+    .await?;
+    let interaction = await_interactions::component(ctx, message, user_tag.clone()).await?;
+    respond_with_modal(ctx, &interaction, create_result_modal("fail", "On failure")).await?;
+    let fail_interaction = await_interactions::modal(ctx, message, user_tag).await?;
+    let fail_inputs = modal_inputs(&fail_interaction.data.components);
+
+    let option = parse_option(&option_inputs, &success_inputs, &fail_inputs);
+
+    Ok((fail_interaction, option))
+}
+
+fn parse_option(
+    option_inputs: &HashMap<String, String>,
+    success_inputs: &HashMap<String, String>,
+    fail_inputs: &HashMap<String, String>,
+) -> Result<(String, EncounterOption), Error> {
+    let name = option_inputs.get("name").cloned().unwrap_or_default();
+
+    let option = EncounterOption {
+        threshold: option_inputs
+            .get("threshold")
+            .and_then(|threshold| threshold.trim().parse().ok())
+            .ok_or(Error::Plain("Threshold should be a number"))?,
+        stat: Attribute::try_from(
+            option_inputs
+                .get("stat")
+                .map(|stat| stat.trim())
+                .unwrap_or_default(),
+        )?,
+        success: parse_result(success_inputs, EncounterResultKind::Success)?,
+        fail: parse_result(fail_inputs, EncounterResultKind::Fail)?,
+        requires_flags: Default::default(),
+        requires_items: Default::default(),
+    };
+
+    Ok((name, option))
+}
+
+fn parse_details(inputs: &HashMap<String, String>) -> Result<Encounter, Error> {
+    let color = match inputs.get("color").map(|color| color.trim()) {
+        None | Some("") => None,
+        Some(hex) => Some(
+            schema::hex_to_colour(hex).ok_or(Error::Plain("Colours should look like #ff8800"))?,
+        ),
+    };
+
+    let rarity = match inputs.get("rarity").map(|rarity| rarity.trim()) {
+        None | Some("") => Rarity::default(),
+        Some(rarity) => Rarity::try_from(rarity)?,
+    };
+
+    let difficulty = match inputs.get("difficulty").map(|difficulty| difficulty.trim()) {
+        None | Some("") => Difficulty::default(),
+        Some(difficulty) => Difficulty::try_from(difficulty)?,
+    };
+
+    Ok(Encounter {
+        version: schema::CURRENT_VERSION,
+        title: inputs.get("title").cloned().unwrap_or_default(),
+        text: inputs.get("text").cloned().unwrap_or_default(),
+        color,
+        options: HashMap::new(),
+        rarity,
+        difficulty,
+        min_score: None,
+        max_score: None,
+        chained: false,
+        zone: None,
+    })
+}
+
+/**
+ * Effects are written the same way as in v1 encounters, e.g. "Damage 3" or "Poison 2 3"
+ */
+fn parse_result(
+    inputs: &HashMap<String, String>,
+    kind: impl FnOnce(String) -> EncounterResultKind,
+) -> Result<EncounterResult, Error> {
+    let base_effect = match inputs.get("base_effect").map(|effect| effect.trim()) {
+        None | Some("") => None,
+        Some(effect) => {
+            let mut words = effect.split_whitespace();
+            let effect = BaseEffectV1 {
+                name: words.next().unwrap_or_default().to_string(),
+                potency: words
+                    .next()
+                    .and_then(|potency| potency.parse().ok())
+                    .ok_or(Error::Plain("Effects need a potency, like \"Heal 3\""))?,
+            };
+            Some(effect.try_into()?)
+        }
+    };
+
+    let lingering_effect = match inputs.get("lingering_effect").map(|effect| effect.trim()) {
+        None | Some("") => None,
+        Some(effect) => {
+            let words: Vec<&str> = effect.split_whitespace().collect();
+            let number = |index: usize| {
+                words
+                    .get(index)
+                    .and_then(|num| num.parse().ok())
+                    .ok_or(Error::Plain(
+                        "Lingering effects need a potency and duration, like \"Poison 2 3\"",
+                    ))
+            };
+            let effect = LingeringEffectV1 {
+                name: words.first().copied().unwrap_or_default().to_string(),
+                potency: number(1)?,
+                duration: number(2)?,
+                kind: words.get(3).map(|kind| kind.to_string()),
+            };
+            Some(effect.try_into()?)
+        }
+    };
+
+    Ok(EncounterResult {
+        kind: kind(inputs.get("kind").cloned().unwrap_or_default()),
+        title: inputs.get("title").cloned().unwrap_or_default(),
+        text: inputs.get("text").cloned().unwrap_or_default(),
+        base_effect,
+        lingering_effect,
+        next: None,
+        set_flags: Default::default(),
+        clear_flags: Default::default(),
+        items: Vec::new(),
+        target: Default::default(),
+        gold: 0,
+        shop: None,
+        enemy: None,
+    })
+}
+
+fn modal_inputs(rows: &[ActionRow]) -> HashMap<String, String> {
+    rows.iter()
+        .flat_map(|row| row.components.iter())
+        .filter_map(|component| match component {
+            ActionRowComponent::InputText(input) => input
+                .value
+                .clone()
+                .map(|value| (input.custom_id.clone(), value)),
+            _ => None,
+        })
+        .collect()
+}
+
+async fn respond_with_modal(
+    ctx: &Context,
+    interaction: &ComponentInteraction,
+    modal: CreateModal,
+) -> Result<(), Error> {
+    interaction
+        .create_response(ctx, CreateInteractionResponse::Modal(modal))
+        .await
+        .map_err(|err| {
+            println!("{}", err);
+            Error::Plain("Modal failed")
+        })
+}
+
+async fn update_message(
+    ctx: &Context,
+    interaction: &ModalInteraction,
+    embed: CreateEmbed,
+    buttons: Vec<CreateButton>,
+) -> Result<(), Error> {
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .embed(embed)
+                    .components(vec![CreateActionRow::Buttons(buttons)]),
+            ),
+        )
+        .await
+        .map_err(Error::from)
+}
+
+fn create_details_modal() -> CreateModal {
+    CreateModal::new("encounter_details", "Set the scene").components(vec![
+        CreateActionRow::InputText(
+            CreateInputText::new(InputTextStyle::Short, "Title", "title").max_length(256),
+        ),
+        CreateActionRow::InputText(CreateInputText::new(
+            InputTextStyle::Paragraph,
+            "Text",
+            "text",
+        )),
+        CreateActionRow::InputText(
+            CreateInputText::new(InputTextStyle::Short, "Colour", "color")
+                .placeholder("#ff8800")
+                .required(false),
+        ),
+        CreateActionRow::InputText(
+            CreateInputText::new(InputTextStyle::Short, "Rarity", "rarity")
+                .placeholder("Common, Uncommon, Rare or Legendary")
+                .required(false),
+        ),
+        CreateActionRow::InputText(
+            CreateInputText::new(InputTextStyle::Short, "Difficulty", "difficulty")
+                .placeholder("Easy, Normal, Hard or Deadly")
+                .required(false),
+        ),
+    ])
+}
+
+fn create_option_modal() -> CreateModal {
+    CreateModal::new("encounter_option", "Give them a choice").components(vec![
+        CreateActionRow::InputText(
+            CreateInputText::new(InputTextStyle::Short, "Option", "name").max_length(80),
+        ),
+        CreateActionRow::InputText(
+            CreateInputText::new(InputTextStyle::Short, "Stat", "stat")
+                .placeholder("Charisma, Strength, Wisdom or Agility"),
+        ),
+        CreateActionRow::InputText(
+            CreateInputText::new(InputTextStyle::Short, "Threshold", "threshold").placeholder(
+                format!(
+                    "{} to {}",
+                    validation::THRESHOLD_RANGE.start(),
+                    validation::THRESHOLD_RANGE.end()
+                ),
+            ),
+        ),
+    ])
+}
+
+fn create_result_modal(custom_id: &str, title: &str) -> CreateModal {
+    CreateModal::new(custom_id, title).components(vec![
+        CreateActionRow::InputText(
+            CreateInputText::new(InputTextStyle::Short, "Title", "title").max_length(256),
+        ),
+        CreateActionRow::InputText(CreateInputText::new(
+            InputTextStyle::Paragraph,
+            "Text",
+            "text",
+        )),
+        CreateActionRow::InputText(
+            CreateInputText::new(InputTextStyle::Short, "Type", "kind").placeholder("Combat"),
+        ),
+        CreateActionRow::InputText(
+            CreateInputText::new(InputTextStyle::Short, "Effect", "base_effect")
+                .placeholder("Heal 3, Damage 2 or Strength -1")
+                .required(false),
+        ),
+        CreateActionRow::InputText(
+            CreateInputText::new(
+                InputTextStyle::Short,
+                "Lingering effect",
+                "lingering_effect",
+            )
+            .placeholder("Poison 2 3, or Wisdom 1 3 Buff")
+            .required(false),
+        ),
+    ])
+}
@@ -0,0 +1,590 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use futures_util::StreamExt;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    all::{
+        ButtonStyle, ChannelId, Colour, ComponentInteraction, CreateButton, CreateEmbed,
+        CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage, EditMessage,
+        GuildId, UserId,
+    },
+    framework::standard::Args,
+    model::prelude::Message,
+    prelude::{Context, TypeMapKey},
+};
+
+use tokio_util::sync::CancellationToken;
+
+use crate::{errors::Error, storage::StorageClient, utilities::permissions};
+
+use super::{
+    attributes::Attribute,
+    effects::{
+        BaseEffect, BaseHealthEffect, Effectable, LingeringEffect, LingeringEffectKind,
+        LingeringEffectName,
+    },
+    events::EventSender,
+    items::Item,
+    player::{self, stats::Stats, RollResult},
+    rules,
+    session::{self, SessionKind},
+};
+
+pub const BOSS_PREFIX: &str = "zumbor/bosses/";
+const LEADERBOARD_PREFIX: &str = "zumbor/leaderboards/bosses/";
+
+/// How long each player has to wait between attacks
+const ATTACK_COOLDOWN: Duration = Duration::from_secs(60);
+
+const DEFAULT_DURATION_MINUTES: u64 = 30;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BossTemplate {
+    pub name: String,
+    pub description: String,
+    pub health: i16,
+    /// Taken off the damage of rolls made against the matching attribute
+    pub stats: Stats,
+    pub attacks: Vec<BossAttack>,
+    /// Score given to everyone who took part when the boss falls
+    #[serde(default)]
+    pub reward: u16,
+    /// Given to whoever dealt the most damage
+    #[serde(default)]
+    pub items: Vec<Item>,
+}
+
+/// A check participants roll against each time they attack, failing it lets the boss hit back
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BossAttack {
+    pub name: String,
+    pub text: String,
+    pub stat: Attribute,
+    pub threshold: u8,
+    pub damage: i16,
+    #[serde(default)]
+    pub effect: Option<LingeringEffect>,
+}
+
+pub struct Boss {
+    pub template: BossTemplate,
+    pub health: i16,
+    pub stats: Stats,
+    pub effects: Vec<LingeringEffect>,
+}
+
+impl Boss {
+    pub fn new(template: BossTemplate) -> Self {
+        Boss {
+            health: template.health,
+            stats: template.stats.clone(),
+            effects: Vec::new(),
+            template,
+        }
+    }
+}
+
+impl Effectable for Boss {
+    fn get_name(&self) -> String {
+        self.template.name.clone()
+    }
+    fn get_effects(&self) -> Vec<LingeringEffect> {
+        self.effects.clone()
+    }
+    fn set_effects(&mut self, effects: Vec<LingeringEffect>) {
+        self.effects = effects;
+    }
+    fn get_health(&self) -> i16 {
+        self.health
+    }
+    fn get_stats(&self) -> Stats {
+        self.stats.clone()
+    }
+    fn set_health(&mut self, health: i16) {
+        self.health = health;
+    }
+    fn set_stats(&mut self, stats: Stats) {
+        self.stats = stats;
+    }
+    fn get_events(&self) -> Option<&EventSender> {
+        None
+    }
+}
+
+impl From<&Boss> for CreateEmbed {
+    fn from(boss: &Boss) -> Self {
+        let health = boss.health.max(0);
+        let filled = (health as usize * 20) / boss.template.health.max(1) as usize;
+
+        let embed = CreateEmbed::new()
+            .title(&boss.template.name)
+            .description(&boss.template.description)
+            .colour(Colour::DARK_RED)
+            .field(
+                "Health",
+                format!(
+                    "{}{} {}/{}",
+                    "█".repeat(filled.min(20)),
+                    "░".repeat(20 - filled.min(20)),
+                    health,
+                    boss.template.health
+                ),
+                false,
+            );
+
+        boss.effects.iter().fold(embed, |embed, effect| {
+            embed.field(
+                format!("{} {}", effect.name, effect.kind),
+                format!("{} for {} turns", effect.potency, effect.duration),
+                true,
+            )
+        })
+    }
+}
+
+/// Guilds with a boss scheduled or underway, only one is allowed per guild at a time
+#[derive(Default)]
+pub struct ActiveBosses(HashSet<GuildId>);
+
+impl TypeMapKey for ActiveBosses {
+    type Value = ActiveBosses;
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct BossLeaderboard {
+    pub entries: HashMap<String, LeaderboardEntry>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct LeaderboardEntry {
+    pub name: String,
+    pub damage: u32,
+    /// Bosses felled while being the top damage dealer
+    pub kills: u16,
+}
+
+/// Damage dealt by each participant during a single boss event
+struct Participant {
+    user_id: UserId,
+    tag: String,
+    name: String,
+    damage: u32,
+}
+
+/**
+ * Schedules a random boss for the guild, `zumbor boss [minutes until it appears] [minutes it stays]`
+ */
+pub async fn schedule(ctx: &Context, msg: &Message, mut args: Args) -> Result<(), Error> {
+    if !permissions::is_admin(ctx, msg).await {
+        msg.reply(ctx, "Only admins can summon bosses you pleb")
+            .await?;
+        return Err(Error::Plain("Boss summoned by a non admin"));
+    }
+
+    let guild_id = msg
+        .guild_id
+        .ok_or(Error::Plain("Bosses can only be summoned in a guild"))?;
+
+    let delay = Duration::from_secs(60 * args.single::<u64>().unwrap_or(0));
+    let duration =
+        Duration::from_secs(60 * args.single::<u64>().unwrap_or(DEFAULT_DURATION_MINUTES));
+
+    let templates = {
+        let data = ctx.data.read().await;
+        let storage_client = data
+            .get::<StorageClient>()
+            .ok_or(Error::Plain("Storage client not accessible!"))?;
+
+        load(storage_client).await?
+    };
+
+    let template = templates
+        .choose(&mut rand::thread_rng())
+        .cloned()
+        .ok_or(Error::Plain("There are no bosses to summon"))?;
+
+    {
+        let mut data = ctx.data.write().await;
+        let active = data.entry::<ActiveBosses>().or_default();
+        if !active.0.insert(guild_id) {
+            drop(data);
+            msg.reply(ctx, "A boss is already on its way").await?;
+            return Err(Error::Plain("Guild already has a boss"));
+        }
+    }
+
+    msg.channel_id
+        .say(
+            ctx,
+            format!(
+                "Something stirs... a boss will appear in {} minutes",
+                delay.as_secs() / 60
+            ),
+        )
+        .await?;
+
+    let ctx = ctx.clone();
+    let channel_id = msg.channel_id;
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+
+        if let Err(err) = run(&ctx, channel_id, guild_id, Boss::new(template), duration).await {
+            println!("World boss failed. {}", err);
+        }
+
+        if let Some(active) = ctx.data.write().await.get_mut::<ActiveBosses>() {
+            active.0.remove(&guild_id);
+        }
+    });
+
+    Ok(())
+}
+
+/**
+ * Loads every boss in the bucket, skipping any that can't be read
+ */
+pub async fn load(storage_client: &StorageClient) -> Result<Vec<BossTemplate>, Error> {
+    let objects = storage_client.get_objects(BOSS_PREFIX).await?;
+    let mut templates = Vec::new();
+
+    for object in objects {
+        let bytes = storage_client.get(&object.name).await?;
+
+        match serde_json::from_slice::<BossTemplate>(&bytes) {
+            Ok(template) if !template.attacks.is_empty() => templates.push(template),
+            Ok(_) => println!("Skipping boss {} without any attacks", object.name),
+            Err(err) => println!("Skipping invalid boss {}. {}", object.name, err),
+        }
+    }
+
+    Ok(templates)
+}
+
+async fn run(
+    ctx: &Context,
+    channel_id: ChannelId,
+    guild_id: GuildId,
+    mut boss: Boss,
+    duration: Duration,
+) -> Result<(), Error> {
+    let mut message = channel_id
+        .send_message(
+            ctx,
+            CreateMessage::new()
+                .content("A world boss has appeared!")
+                .embed((&boss).into())
+                .button(
+                    CreateButton::new("attack")
+                        .label("Attack")
+                        .style(ButtonStyle::Danger),
+                ),
+        )
+        .await?;
+
+    let mut participants: Vec<Participant> = Vec::new();
+    let mut cooldowns: HashMap<UserId, Instant> = HashMap::new();
+
+    let mut interactions = message
+        .await_component_interactions(ctx)
+        .timeout(duration)
+        .stream();
+
+    while let Some(interaction) = interactions.next().await {
+        let ready = cooldowns
+            .get(&interaction.user.id)
+            .is_none_or(|last| last.elapsed() >= ATTACK_COOLDOWN);
+
+        let embed = if ready {
+            match attack(ctx, &interaction, &mut boss, &mut participants).await {
+                Ok(embed) => {
+                    cooldowns.insert(interaction.user.id, Instant::now());
+                    embed
+                }
+                Err(err) => CreateEmbed::new()
+                    .title("You can't attack right now")
+                    .description(err.to_string()),
+            }
+        } else {
+            CreateEmbed::new().title("You are still catching your breath")
+        };
+
+        interaction
+            .create_response(
+                ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .embed(embed)
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+
+        message
+            .edit(
+                ctx,
+                EditMessage::new().embeds(vec![(&boss).into(), damage_embed(&participants)]),
+            )
+            .await?;
+
+        if boss.health <= 0 {
+            break;
+        }
+    }
+
+    participants.sort_by_key(|participant| std::cmp::Reverse(participant.damage));
+    let defeated = boss.health <= 0;
+
+    message
+        .edit(ctx, EditMessage::new().components(Vec::new()))
+        .await?;
+
+    let mut summary = CreateEmbed::new().title(if defeated {
+        format!("{} has fallen!", boss.template.name)
+    } else {
+        format!("{} retreats...", boss.template.name)
+    });
+
+    if defeated {
+        summary = summary.description(reward(ctx, channel_id, &boss, &participants).await);
+    }
+
+    record(ctx, guild_id, &participants, defeated).await?;
+
+    channel_id
+        .send_message(
+            ctx,
+            CreateMessage::new().embeds(vec![summary, damage_embed(&participants)]),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/**
+ * Resolves one participant's attack, rolling against one of the boss's attacks and updating their save
+ */
+async fn attack(
+    ctx: &Context,
+    interaction: &ComponentInteraction,
+    boss: &mut Boss,
+    participants: &mut Vec<Participant>,
+) -> Result<CreateEmbed, Error> {
+    // Holding a session stops the save being changed by a normal run at the same time
+    let _session = session::claim(
+        ctx,
+        interaction.user.id,
+        SessionKind::Boss,
+        interaction.channel_id,
+        CancellationToken::new(),
+    )
+    .await
+    .map_err(|_| Error::Plain("Finish your current adventure first"))?;
+
+    let mut player = player::storage::load_save(ctx, &interaction.user.tag())
+        .await
+        .map_err(|_| Error::Plain("You need a Zumbor character to fight"))?;
+    player.rules = rules::for_guild(ctx, interaction.guild_id).await;
+
+    let boss_attack = boss
+        .template
+        .attacks
+        .choose(&mut rand::thread_rng())
+        .cloned()
+        .expect("Bosses have at least one attack");
+
+    let roll = player.roll_stat(&boss_attack.stat, &mut rand::thread_rng());
+    let defence = boss.stats.get(boss_attack.stat.clone());
+
+    let (damage, held_off) = match roll {
+        RollResult::CriticalFail => (0, false),
+        RollResult::CriticalSuccess => {
+            // A perfect strike leaves the boss bleeding
+            boss.add_effect(LingeringEffect {
+                kind: LingeringEffectKind::Debuff,
+                name: LingeringEffectName::Poison,
+                potency: 2,
+                duration: 3,
+            });
+            (
+                player.rules.critical_multiplier * (player.rules.die_sides - defence).max(1),
+                true,
+            )
+        }
+        RollResult::Value(value) => (
+            (value - defence).max(0),
+            value >= boss_attack.threshold.into(),
+        ),
+    };
+
+    boss.affect(&BaseEffect::Health(BaseHealthEffect { potency: -damage }));
+    boss.apply_effects();
+
+    let mut embed = CreateEmbed::new()
+        .title(format!("{} attacks {}", player.name, boss.template.name))
+        .field("Damage", damage.to_string(), true);
+
+    if !held_off {
+        player.affect_health(&BaseHealthEffect {
+            potency: -boss_attack.damage,
+        });
+        // The boss can knock characters out but never kill them
+        player.health = player.health.max(1);
+
+        if let Some(effect) = &boss_attack.effect {
+            player.add_effect(effect.clone());
+        }
+
+        let thorns = player.thorns();
+        if thorns > 0 {
+            boss.affect_health(&BaseHealthEffect { potency: -thorns });
+        }
+
+        embed = embed.field(
+            &boss_attack.name,
+            format!(
+                "{}\nYou take {} damage",
+                boss_attack.text, boss_attack.damage
+            ),
+            false,
+        );
+    }
+
+    player.save(ctx).await?;
+
+    match participants
+        .iter_mut()
+        .find(|participant| participant.user_id == interaction.user.id)
+    {
+        Some(participant) => participant.damage += damage as u32,
+        None => participants.push(Participant {
+            user_id: interaction.user.id,
+            tag: player.tag.clone(),
+            name: player.name.clone(),
+            damage: damage as u32,
+        }),
+    }
+
+    Ok(embed)
+}
+
+/**
+ * Gives every participant the boss's score reward and the top damage dealer its items
+ */
+async fn reward(
+    ctx: &Context,
+    channel_id: ChannelId,
+    boss: &Boss,
+    participants: &[Participant],
+) -> String {
+    let mut lines = Vec::new();
+
+    for (rank, participant) in participants.iter().enumerate() {
+        let Ok(_session) = session::claim(
+            ctx,
+            participant.user_id,
+            SessionKind::Boss,
+            channel_id,
+            CancellationToken::new(),
+        )
+        .await
+        else {
+            lines.push(format!(
+                "{} was busy adventuring and missed out",
+                participant.name
+            ));
+            continue;
+        };
+
+        if let Ok(mut player) = player::storage::load_save(ctx, &participant.tag).await {
+            player.add_score(boss.template.reward);
+            if rank == 0 {
+                for item in &boss.template.items {
+                    player.give_item(item.clone());
+                }
+            }
+
+            match player.save(ctx).await {
+                Ok(_) => lines.push(format!(
+                    "{} gains {} score",
+                    participant.name, boss.template.reward
+                )),
+                Err(err) => println!("Unable to reward {}. {}", participant.tag, err),
+            }
+        }
+    }
+
+    lines.join("\n")
+}
+
+/**
+ * Adds the event's damage to the guild's boss leaderboard
+ */
+async fn record(
+    ctx: &Context,
+    guild_id: GuildId,
+    participants: &[Participant],
+    defeated: bool,
+) -> Result<(), Error> {
+    let data = ctx.data.read().await;
+    let storage_client = data
+        .get::<StorageClient>()
+        .ok_or(Error::Plain("Storage client not accessible!"))?;
+
+    let path = format!("{}{}", LEADERBOARD_PREFIX, guild_id);
+    let mut leaderboard: BossLeaderboard = match storage_client.get_json(&path).await {
+        Err(err) if err.is_not_found() => BossLeaderboard::default(),
+        result => result?,
+    };
+
+    for (rank, participant) in participants.iter().enumerate() {
+        let entry = leaderboard
+            .entries
+            .entry(participant.tag.clone())
+            .or_default();
+
+        entry.name = participant.name.clone();
+        entry.damage += participant.damage;
+        if defeated && rank == 0 {
+            entry.kills += 1;
+        }
+    }
+
+    storage_client
+        .create_json(
+            &format!("{}.json", path),
+            serde_json::to_string(&leaderboard)?,
+        )
+        .await
+}
+
+fn damage_embed(participants: &[Participant]) -> CreateEmbed {
+    let mut ranked: Vec<&Participant> = participants.iter().collect();
+    ranked.sort_by_key(|participant| std::cmp::Reverse(participant.damage));
+
+    let ranking = ranked
+        .iter()
+        .take(10)
+        .enumerate()
+        .map(|(rank, participant)| {
+            format!(
+                "{}. {} - {}",
+                rank + 1,
+                participant.name,
+                participant.damage
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    CreateEmbed::new()
+        .title("Damage dealt")
+        .description(if ranking.is_empty() {
+            "Nobody has landed a hit yet".to_string()
+        } else {
+            ranking
+        })
+}
@@ -0,0 +1,136 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serenity::all::{Colour, CreateEmbed};
+
+use crate::{errors::Error, storage::StorageClient};
+
+use super::{
+    attributes::Attribute,
+    effects::{Effectable, LingeringEffect},
+    encounter::{validation, Encounter, EncounterOption},
+    player::{stats::Stats, Player},
+};
+
+pub const CLASS_PREFIX: &str = "zumbor/classes/";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Class {
+    pub name: String,
+    pub description: String,
+    /// Stats the class starts with before the player allocates their own points
+    pub stats: Stats,
+    pub health: i16,
+    /// Applied at creation and never expires
+    #[serde(default)]
+    pub passive: Option<LingeringEffect>,
+    #[serde(default)]
+    pub option: Option<ClassOption>,
+}
+
+/// An option only players of the class can pick, offered alongside an encounter's own options
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ClassOption {
+    pub name: String,
+    /// Chance from 0 to 1 of the option being offered in an encounter
+    pub chance: f64,
+    #[serde(flatten)]
+    pub option: EncounterOption,
+}
+
+impl From<&Class> for CreateEmbed {
+    fn from(class: &Class) -> Self {
+        let embed = CreateEmbed::new()
+            .title(&class.name)
+            .description(&class.description)
+            .colour(Colour::DARK_TEAL)
+            .field("Health", class.health.to_string(), true);
+
+        let embed = Attribute::VALUES.into_iter().fold(embed, |embed, attr| {
+            embed.field(attr.clone(), class.stats.get(attr).to_string(), true)
+        });
+
+        let embed = match &class.passive {
+            Some(effect) => embed.field(
+                "Passive",
+                format!("{} {} {}", effect.potency, effect.name, effect.kind),
+                true,
+            ),
+            None => embed,
+        };
+
+        match &class.option {
+            Some(option) => embed.field("Special option", &option.name, true),
+            None => embed,
+        }
+    }
+}
+
+/**
+ * Loads every class in the bucket, skipping any that can't be read
+ */
+pub async fn load(storage_client: &StorageClient) -> Result<Vec<Class>, Error> {
+    let objects = storage_client.get_objects(CLASS_PREFIX).await?;
+    let mut classes = Vec::new();
+
+    for object in objects {
+        let bytes = storage_client.get(&object.name).await?;
+
+        match serde_json::from_slice::<Class>(&bytes) {
+            Ok(class) => classes.push(class),
+            Err(err) => println!("Skipping invalid class {}. {}", object.name, err),
+        }
+    }
+
+    Ok(classes)
+}
+
+impl Player {
+    /**
+     * Gives the player the class's starting stats, health and passive effect
+     */
+    pub fn assign_class(&mut self, class: Class) {
+        self.health = class.health;
+        self.stats = self.stats.clone() + class.stats.clone();
+
+        if let Some(mut passive) = class.passive.clone() {
+            passive.duration = 0;
+            self.add_effect(passive);
+        }
+
+        self.class = Some(class);
+    }
+
+    /// The stats the player started with before allocating any points
+    pub fn base_stats(&self) -> Stats {
+        self.class
+            .as_ref()
+            .map(|class| class.stats.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl Encounter {
+    /**
+     * Occasionally adds the player's class option to the encounter
+     */
+    pub fn offer_class_option(&mut self, player: &Player, rng: &mut impl Rng) {
+        let Some(class_option) = player
+            .class
+            .as_ref()
+            .and_then(|class| class.option.as_ref())
+        else {
+            return;
+        };
+
+        // Older encounters can already fill the button row the option would go in
+        if self.options.contains_key(&class_option.name)
+            || self.options.len() > validation::MAX_OPTIONS
+            || !rng.gen_bool(class_option.chance.clamp(0.0, 1.0))
+        {
+            return;
+        }
+
+        self.options
+            .insert(class_option.name.clone(), class_option.option.clone());
+    }
+}
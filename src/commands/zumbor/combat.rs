@@ -0,0 +1,509 @@
+use std::fmt::{self, Display};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    all::{Colour, CreateEmbed},
+    prelude::Context,
+};
+
+use crate::{errors::Error, storage::StorageClient};
+
+use super::{
+    attributes::Attribute,
+    effects::{
+        BaseHealthEffect, Effectable, LingeringEffect, LingeringEffectKind, LingeringEffectName,
+    },
+    events::EventSender,
+    player::{stats::Stats, Player, RollResult},
+};
+
+/// Enemies are stored as `zumbor/enemies/{id}.json` and referred to by id from encounter results
+pub const ENEMY_PREFIX: &str = "zumbor/enemies/";
+
+/// Rounds fought before the enemy loses interest, so a fight can't go on forever
+const MAX_ROUNDS: u16 = 20;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EnemyTemplate {
+    pub name: String,
+    pub description: String,
+    pub health: i16,
+    /// Taken off the damage of attacks made against the matching attribute
+    pub stats: Stats,
+    /// The attribute players attack and defend with
+    pub stat: Attribute,
+    /// What players need to roll to defend or flee
+    pub threshold: u8,
+    /// Dealt to the player every round the enemy gets to strike
+    pub damage: i16,
+    /// Given to the player with every strike that lands
+    #[serde(default)]
+    pub effect: Option<LingeringEffect>,
+    /// Score given for defeating the enemy
+    #[serde(default)]
+    pub reward: u16,
+    #[serde(default)]
+    pub gold: u32,
+}
+
+pub struct Enemy {
+    pub template: EnemyTemplate,
+    pub health: i16,
+    pub stats: Stats,
+    pub effects: Vec<LingeringEffect>,
+}
+
+impl Enemy {
+    pub fn new(template: EnemyTemplate) -> Self {
+        Enemy {
+            health: template.health,
+            stats: template.stats.clone(),
+            effects: Vec::new(),
+            template,
+        }
+    }
+}
+
+impl Effectable for Enemy {
+    fn get_name(&self) -> String {
+        self.template.name.clone()
+    }
+    fn get_effects(&self) -> Vec<LingeringEffect> {
+        self.effects.clone()
+    }
+    fn set_effects(&mut self, effects: Vec<LingeringEffect>) {
+        self.effects = effects;
+    }
+    fn get_health(&self) -> i16 {
+        self.health
+    }
+    fn get_stats(&self) -> Stats {
+        self.stats.clone()
+    }
+    fn set_health(&mut self, health: i16) {
+        self.health = health;
+    }
+    fn set_stats(&mut self, stats: Stats) {
+        self.stats = stats;
+    }
+    fn get_events(&self) -> Option<&EventSender> {
+        // Everything that happens to the enemy is told through the round
+        None
+    }
+}
+
+impl From<&Enemy> for CreateEmbed {
+    fn from(enemy: &Enemy) -> Self {
+        let health = enemy.health.max(0);
+        let filled = (health as usize * 20) / enemy.template.health.max(1) as usize;
+
+        let embed = CreateEmbed::new()
+            .title(&enemy.template.name)
+            .description(&enemy.template.description)
+            .colour(Colour::DARK_RED)
+            .field(
+                "Health",
+                format!(
+                    "{}{} {}/{}",
+                    "█".repeat(filled.min(20)),
+                    "░".repeat(20 - filled.min(20)),
+                    health,
+                    enemy.template.health
+                ),
+                false,
+            )
+            .field(
+                "Fought with",
+                format!("{} {}+", enemy.template.stat, enemy.template.threshold),
+                true,
+            );
+
+        enemy.effects.iter().fold(embed, |embed, effect| {
+            embed.field(
+                format!("{} {}", effect.name, effect.kind),
+                format!("{} for {} turns", effect.potency, effect.duration),
+                true,
+            )
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CombatAction {
+    /// Deals damage, taking a hit in return
+    Attack,
+    /// Blocks the enemy's strike and sets up a better attack, if the roll is good enough
+    Defend,
+    /// Ends the fight with nothing gained, if the roll is good enough
+    Flee,
+}
+
+impl CombatAction {
+    pub const VALUES: [CombatAction; 3] = [
+        CombatAction::Attack,
+        CombatAction::Defend,
+        CombatAction::Flee,
+    ];
+}
+
+impl Display for CombatAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CombatAction::Attack => write!(f, "Attack"),
+            CombatAction::Defend => write!(f, "Defend"),
+            CombatAction::Flee => write!(f, "Flee"),
+        }
+    }
+}
+
+impl TryFrom<&str> for CombatAction {
+    type Error = Error;
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        CombatAction::VALUES
+            .into_iter()
+            .find(|action| action.to_string().eq_ignore_ascii_case(value))
+            .ok_or(Error::Plain("Combat actions are Attack, Defend or Flee"))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum CombatOutcome {
+    Victory,
+    Defeat,
+    Fled,
+    /// The enemy lost interest before either side fell
+    Withdrew,
+}
+
+impl Display for CombatOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CombatOutcome::Victory => write!(f, "won"),
+            CombatOutcome::Defeat => write!(f, "lost"),
+            CombatOutcome::Fled => write!(f, "fled"),
+            CombatOutcome::Withdrew => write!(f, "was left alone"),
+        }
+    }
+}
+
+/**
+ * A fight between a player and an enemy, played a round at a time
+ */
+pub struct Combat {
+    pub enemy: Enemy,
+    pub round: u16,
+    pub outcome: Option<CombatOutcome>,
+}
+
+impl Combat {
+    pub fn new(enemy: Enemy) -> Self {
+        Combat {
+            enemy,
+            round: 0,
+            outcome: None,
+        }
+    }
+
+    /**
+     * Plays out the player's action and the enemy's answer to it, returning what happened
+     */
+    pub fn round(
+        &mut self,
+        player: &mut Player,
+        action: CombatAction,
+        rng: &mut impl Rng,
+    ) -> Vec<String> {
+        self.round += 1;
+        let enemy = &mut self.enemy;
+        let mut lines = Vec::new();
+
+        let stat = match action {
+            CombatAction::Flee => Attribute::Agility,
+            _ => enemy.template.stat.clone(),
+        };
+        let roll = player.roll_stat(&stat, rng);
+        lines.push(format!(
+            "{} chose to {} and rolled {}",
+            player.name,
+            action.to_string().to_lowercase(),
+            roll
+        ));
+
+        let met = match roll {
+            RollResult::CriticalFail => false,
+            RollResult::CriticalSuccess => true,
+            RollResult::Value(value) => value >= enemy.template.threshold.into(),
+        };
+
+        match action {
+            CombatAction::Attack => {
+                let defence = enemy.stats.get(stat);
+                let damage = match roll {
+                    RollResult::CriticalFail => 0,
+                    RollResult::CriticalSuccess => {
+                        player.rules.critical_multiplier * (player.rules.die_sides - defence).max(1)
+                    }
+                    RollResult::Value(value) => (value - defence).max(0),
+                };
+
+                enemy.affect_health(&BaseHealthEffect { potency: -damage });
+                lines.push(format!(
+                    "{} hits {} for {} damage",
+                    player.name, enemy.template.name, damage
+                ));
+            }
+            CombatAction::Defend if met => {
+                // The shield takes the coming strike and the opening is used next round
+                player.add_effect(LingeringEffect {
+                    kind: LingeringEffectKind::Buff,
+                    name: LingeringEffectName::Shield,
+                    potency: enemy.template.damage.max(1),
+                    duration: 1,
+                });
+                player.add_effect(LingeringEffect {
+                    kind: LingeringEffectKind::Buff,
+                    name: LingeringEffectName::Advantage,
+                    potency: 1,
+                    duration: 2,
+                });
+                lines.push(format!("{} braces for the blow", player.name));
+            }
+            CombatAction::Defend => {
+                lines.push(format!("{} is caught off guard", player.name));
+            }
+            CombatAction::Flee if met => {
+                lines.push(format!("{} gets away", player.name));
+                self.outcome = Some(CombatOutcome::Fled);
+                return lines;
+            }
+            CombatAction::Flee => {
+                lines.push(format!("{} can't get away", player.name));
+            }
+        }
+
+        if enemy.health > 0 {
+            if enemy.has_effect(&LingeringEffectName::Stun) {
+                enemy.consume_effect(&LingeringEffectName::Stun);
+                lines.push(format!("{} is stunned", enemy.template.name));
+            } else {
+                let before = player.health;
+                player.affect_health(&BaseHealthEffect {
+                    potency: -enemy.template.damage,
+                });
+                lines.push(format!(
+                    "{} strikes {} for {} damage",
+                    enemy.template.name,
+                    player.name,
+                    before - player.health
+                ));
+
+                if let Some(effect) = &enemy.template.effect {
+                    player.add_effect(effect.clone());
+                }
+
+                let thorns = player.thorns();
+                if thorns > 0 {
+                    enemy.affect_health(&BaseHealthEffect { potency: -thorns });
+                    lines.push(format!("Thorns deal {} damage back", thorns));
+                }
+            }
+        }
+
+        player.apply_effects();
+        enemy.apply_effects();
+
+        self.outcome = if player.health <= 0 {
+            Some(CombatOutcome::Defeat)
+        } else if enemy.health <= 0 {
+            lines.push(format!("{} falls", enemy.template.name));
+            Some(CombatOutcome::Victory)
+        } else if self.round >= MAX_ROUNDS {
+            lines.push(format!("{} loses interest", enemy.template.name));
+            Some(CombatOutcome::Withdrew)
+        } else {
+            None
+        };
+
+        lines
+    }
+
+    /**
+     * Gives the player the enemy's rewards if they won
+     */
+    pub fn reward(&self, player: &mut Player) -> Option<String> {
+        if self.outcome != Some(CombatOutcome::Victory) {
+            return None;
+        }
+
+        let template = &self.enemy.template;
+        player.add_score(template.reward);
+        player.gold = player.gold.saturating_add(template.gold);
+
+        Some(format!(
+            "{} gains {} score and {} gold",
+            player.name, template.reward, template.gold
+        ))
+    }
+}
+
+pub async fn load(ctx: &Context, id: &str) -> Result<EnemyTemplate, Error> {
+    let data = ctx.data.read().await;
+    let storage_client = data
+        .get::<StorageClient>()
+        .ok_or(Error::Plain("Storage client not accessible!"))?;
+
+    load_from(storage_client, id).await
+}
+
+pub async fn load_from(storage_client: &StorageClient, id: &str) -> Result<EnemyTemplate, Error> {
+    storage_client
+        .get_json(&format!("{}{}", ENEMY_PREFIX, id))
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::super::player::PlayerDetails;
+    use super::*;
+
+    fn player() -> Player {
+        let mut player = Player::new(
+            "tester".to_string(),
+            PlayerDetails {
+                name: "Tester".to_string(),
+                description: "Fighting".to_string(),
+            },
+            Stats::default(),
+        );
+        // A one sided die only ever rolls a critical fail
+        player.rules.die_sides = 1;
+        player
+    }
+
+    fn combat(health: i16, damage: i16) -> Combat {
+        Combat::new(Enemy::new(EnemyTemplate {
+            name: "Goblin".to_string(),
+            description: "Small and angry".to_string(),
+            health,
+            stats: Stats::default(),
+            stat: Attribute::Strength,
+            threshold: 10,
+            damage,
+            effect: None,
+            reward: 5,
+            gold: 3,
+        }))
+    }
+
+    #[test]
+    fn failed_attacks_miss_and_take_the_strike() {
+        let mut player = player();
+        let health = player.health;
+        let mut combat = combat(10, 3);
+
+        combat.round(
+            &mut player,
+            CombatAction::Attack,
+            &mut StdRng::seed_from_u64(1),
+        );
+
+        assert_eq!(combat.round, 1);
+        assert_eq!(combat.enemy.health, 10);
+        assert_eq!(player.health, health - 3);
+        assert_eq!(combat.outcome, None);
+    }
+
+    #[test]
+    fn failed_escapes_keep_the_fight_going() {
+        let mut player = player();
+        let mut combat = combat(10, 1);
+
+        combat.round(
+            &mut player,
+            CombatAction::Flee,
+            &mut StdRng::seed_from_u64(1),
+        );
+
+        assert_eq!(combat.outcome, None);
+    }
+
+    #[test]
+    fn stunned_enemies_skip_their_strike() {
+        let mut player = player();
+        let health = player.health;
+        let mut combat = combat(10, 3);
+        combat.enemy.add_effect(LingeringEffect {
+            kind: LingeringEffectKind::Debuff,
+            name: LingeringEffectName::Stun,
+            potency: 1,
+            duration: 2,
+        });
+
+        combat.round(
+            &mut player,
+            CombatAction::Defend,
+            &mut StdRng::seed_from_u64(1),
+        );
+
+        assert_eq!(player.health, health);
+        assert!(combat.enemy.effects.is_empty());
+    }
+
+    #[test]
+    fn thorns_can_win_the_fight() {
+        let mut player = player();
+        player.add_effect(LingeringEffect {
+            kind: LingeringEffectKind::Buff,
+            name: LingeringEffectName::Thorns,
+            potency: 5,
+            duration: 0,
+        });
+        let mut combat = combat(4, 1);
+
+        combat.round(
+            &mut player,
+            CombatAction::Attack,
+            &mut StdRng::seed_from_u64(1),
+        );
+        assert_eq!(combat.outcome, Some(CombatOutcome::Victory));
+
+        let score = player.score;
+        assert!(combat.reward(&mut player).is_some());
+        assert_eq!(player.score, score + 5);
+        assert_eq!(player.gold, 3);
+    }
+
+    #[test]
+    fn players_brought_to_nothing_are_defeated() {
+        let mut player = player();
+        player.health = 2;
+        let mut combat = combat(10, 5);
+
+        combat.round(
+            &mut player,
+            CombatAction::Attack,
+            &mut StdRng::seed_from_u64(1),
+        );
+
+        assert_eq!(combat.outcome, Some(CombatOutcome::Defeat));
+        assert!(combat.reward(&mut player).is_none());
+    }
+
+    #[test]
+    fn enemies_lose_interest_eventually() {
+        let mut player = player();
+        let mut combat = combat(10, 0);
+        let mut rng = StdRng::seed_from_u64(1);
+
+        for _ in 1..MAX_ROUNDS {
+            combat.round(&mut player, CombatAction::Attack, &mut rng);
+            assert_eq!(combat.outcome, None);
+        }
+        combat.round(&mut player, CombatAction::Attack, &mut rng);
+
+        assert_eq!(combat.outcome, Some(CombatOutcome::Withdrew));
+    }
+}
@@ -0,0 +1,326 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use serde::{Deserialize, Serialize};
+use serenity::{
+    all::{Colour, CreateEmbed, CreateMessage, GuildId},
+    model::prelude::Message,
+    prelude::Context,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{errors::Error, storage::StorageClient};
+
+use super::{
+    attributes::Attribute,
+    initialise::{run, RunMode},
+    party::Party,
+    player::{stats::Stats, Player, PlayerDetails},
+    rules::{self, Ruleset},
+    session::{self, SessionKind},
+};
+
+const DAILY_PREFIX: &str = "zumbor/daily/";
+
+const SECONDS_PER_DAY: u64 = 60 * 60 * 24;
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct DailyBoard {
+    pub entries: Vec<DailyEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DailyEntry {
+    pub tag: String,
+    pub name: String,
+    pub score: u16,
+    /// Attempts are recorded as soon as they start so they can't be retried, this marks the ones that ended
+    #[serde(default)]
+    pub finished: bool,
+}
+
+/// Days since the unix epoch, the challenge changes over at midnight UTC
+pub fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs() / SECONDS_PER_DAY)
+        .unwrap_or_default()
+}
+
+/**
+ * The random number generator everyone in the guild plays the day's challenge with
+ */
+pub fn rng(guild_id: GuildId, day: u64) -> StdRng {
+    StdRng::seed_from_u64(guild_id.get().rotate_left(32) ^ day)
+}
+
+/**
+ * The random number generator the challenge's encounter at the given point is picked with.
+ * It is seeded apart from the dice so every challenger meets the same encounters however their rolls go
+ */
+pub fn selection_rng(guild_id: GuildId, day: u64, index: u64) -> StdRng {
+    StdRng::seed_from_u64(
+        (guild_id.get().rotate_left(32) ^ day).rotate_left(16)
+            ^ index.wrapping_mul(0x9E37_79B9_7F4A_7C15),
+    )
+}
+
+fn board_path(guild_id: GuildId, day: u64) -> String {
+    format!("{}{}/{}", DAILY_PREFIX, guild_id, day)
+}
+
+/**
+ * Loads the day's board, only starting it empty if nobody has played yet
+ */
+async fn load_board(
+    storage_client: &StorageClient,
+    guild_id: GuildId,
+    day: u64,
+) -> Result<DailyBoard, Error> {
+    match storage_client.get_json(&board_path(guild_id, day)).await {
+        Err(err) if err.is_not_found() => Ok(DailyBoard::default()),
+        result => result,
+    }
+}
+
+async fn save_board(
+    storage_client: &StorageClient,
+    guild_id: GuildId,
+    day: u64,
+    board: &DailyBoard,
+) -> Result<(), Error> {
+    storage_client
+        .create_json(
+            &format!("{}.json", board_path(guild_id, day)),
+            serde_json::to_string(board)?,
+        )
+        .await
+}
+
+/**
+ * Starts the author's one attempt at today's challenge for the guild
+ */
+pub async fn start(ctx: &Context, msg: &Message) -> Result<(), Error> {
+    let Some(guild_id) = msg.guild_id else {
+        msg.reply(ctx, "The daily challenge can only be played in a server")
+            .await?;
+        return Err(Error::Plain("Daily challenge started outside a guild"));
+    };
+    let day = today();
+
+    let session = match session::claim(
+        ctx,
+        msg.author.id,
+        SessionKind::Daily,
+        msg.channel_id,
+        CancellationToken::new(),
+    )
+    .await
+    {
+        Ok(session) => session,
+        Err(err) => {
+            msg.reply(
+                ctx,
+                "You already have a running instance of Zumbor you fool!",
+            )
+            .await?;
+            return Err(err);
+        }
+    };
+
+    claim_attempt(ctx, msg, guild_id, day).await?;
+
+    let rules = rules::for_guild(ctx, Some(guild_id)).await;
+    let player = challenger(
+        msg.author.tag(),
+        msg.author.name.clone(),
+        guild_id,
+        day,
+        &rules,
+    );
+
+    run(
+        ctx,
+        msg.channel_id,
+        Party::solo(session, player),
+        RunMode::Daily { guild_id, day },
+        rules,
+    )
+    .await
+    .map(|_| ())
+}
+
+/**
+ * Records that the author has started today's challenge, failing if they already have
+ */
+async fn claim_attempt(
+    ctx: &Context,
+    msg: &Message,
+    guild_id: GuildId,
+    day: u64,
+) -> Result<(), Error> {
+    let data = ctx.data.read().await;
+    let storage_client = data
+        .get::<StorageClient>()
+        .ok_or(Error::Plain("Storage client not accessible!"))?;
+
+    let tag = msg.author.tag();
+    let mut board = match load_board(storage_client, guild_id, day).await {
+        Ok(board) => board,
+        Err(err) => {
+            msg.reply(
+                ctx,
+                "Today's challenge can't be reached right now, try again in a bit",
+            )
+            .await?;
+            return Err(err);
+        }
+    };
+
+    if board.entries.iter().any(|entry| entry.tag == tag) {
+        msg.reply(
+            ctx,
+            "You've already had your go at today's challenge, come back tomorrow",
+        )
+        .await?;
+        return Err(Error::Plain("Daily challenge already attempted"));
+    }
+
+    board.entries.push(DailyEntry {
+        tag,
+        name: msg.author.name.clone(),
+        score: 0,
+        finished: false,
+    });
+
+    save_board(storage_client, guild_id, day, &board).await
+}
+
+/**
+ * The character everyone plays today's challenge with, only the name differs between players
+ */
+fn challenger(tag: String, name: String, guild_id: GuildId, day: u64, rules: &Ruleset) -> Player {
+    let limits = rules.stat_limits(1);
+    // Seeded apart from the run so the character doesn't shift the day's encounters
+    let mut rng = rng(guild_id, !day);
+
+    let mut stats = Stats::default();
+    let mut remaining = limits.budget;
+    while remaining > 0 {
+        let available: Vec<&Attribute> = Attribute::VALUES
+            .iter()
+            .filter(|attribute| stats.get((*attribute).clone()) < limits.max)
+            .collect();
+
+        let Some(attribute) = available.choose(&mut rng) else {
+            break;
+        };
+
+        *stats.get_mut((*attribute).clone()) += 1;
+        remaining -= 1;
+    }
+
+    let mut player = Player::new(
+        tag,
+        PlayerDetails {
+            name,
+            description: "Taking on today's challenge".to_string(),
+        },
+        stats,
+    );
+    player.health = rules.starting_health;
+    player
+}
+
+/**
+ * Updates the player's entry on the day's leaderboard with their score so far, or the one they finished on
+ */
+pub async fn record(
+    ctx: &Context,
+    guild_id: GuildId,
+    day: u64,
+    player: &Player,
+    finished: bool,
+) -> Result<(), Error> {
+    let data = ctx.data.read().await;
+    let storage_client = data
+        .get::<StorageClient>()
+        .ok_or(Error::Plain("Storage client not accessible!"))?;
+
+    let mut board = load_board(storage_client, guild_id, day).await?;
+
+    match board
+        .entries
+        .iter_mut()
+        .find(|entry| entry.tag == player.tag)
+    {
+        Some(entry) => {
+            entry.score = player.score;
+            entry.finished = finished;
+        }
+        None => board.entries.push(DailyEntry {
+            tag: player.tag.clone(),
+            name: player.name.clone(),
+            score: player.score,
+            finished,
+        }),
+    }
+
+    board
+        .entries
+        .sort_by_key(|entry| std::cmp::Reverse(entry.score));
+
+    save_board(storage_client, guild_id, day, &board).await
+}
+
+/**
+ * Shows how everyone in the guild has done at today's challenge so far
+ */
+pub async fn leaderboard(ctx: &Context, msg: &Message) -> Result<(), Error> {
+    let guild_id = msg
+        .guild_id
+        .ok_or(Error::Plain("Daily leaderboard requested outside a guild"))?;
+
+    let board = {
+        let data = ctx.data.read().await;
+        let storage_client = data
+            .get::<StorageClient>()
+            .ok_or(Error::Plain("Storage client not accessible!"))?;
+
+        load_board(storage_client, guild_id, today()).await?
+    };
+
+    let description = if board.entries.is_empty() {
+        "Nobody has taken on today's challenge yet".to_string()
+    } else {
+        board
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(place, entry)| {
+                format!(
+                    "{}. {} - {}{}",
+                    place + 1,
+                    entry.name,
+                    entry.score,
+                    if entry.finished { "" } else { " (playing)" }
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    };
+
+    msg.channel_id
+        .send_message(
+            ctx,
+            CreateMessage::new().embed(
+                CreateEmbed::new()
+                    .title("Today's challenge")
+                    .description(description)
+                    .colour(Colour::BLUE),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
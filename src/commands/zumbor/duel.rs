@@ -0,0 +1,395 @@
+use std::sync::Arc;
+
+use rand::seq::SliceRandom;
+use serenity::{
+    all::{
+        ButtonStyle, ComponentInteraction, CreateActionRow, CreateButton, CreateEmbed,
+        CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage, EditMessage,
+        User,
+    },
+    framework::standard::Args,
+    model::prelude::Message,
+    prelude::Context,
+};
+
+use tokio_util::sync::CancellationToken;
+
+use crate::{errors::Error, utilities::await_interactions};
+
+use super::{
+    attributes::Attribute,
+    effects::{BaseHealthEffect, Effectable},
+    player::{self, Player, RollResult},
+    rules,
+    session::{self, SessionKind},
+};
+
+/// Round wins needed to take the duel
+const ROUNDS_TO_WIN: u8 = 3;
+
+/// Health lost by the loser of each round
+const ROUND_DAMAGE: i16 = 2;
+
+struct Duellist {
+    user: User,
+    player: Player,
+    wins: u8,
+}
+
+/**
+ * Challenges the mentioned user to a duel between their saved characters, optionally staking score
+ */
+pub async fn challenge(ctx: &Context, msg: &Message, mut args: Args) -> Result<(), Error> {
+    let Some(opponent) = msg.mentions.first().cloned() else {
+        msg.reply(ctx, "Mention who you want to duel").await?;
+        return Err(Error::Plain("No opponent mentioned"));
+    };
+
+    if opponent.id == msg.author.id || opponent.bot {
+        msg.reply(ctx, "Pick a real opponent you coward").await?;
+        return Err(Error::Plain("Invalid opponent"));
+    }
+
+    // The mention is the first argument, the stake follows it
+    args.advance();
+    let stake: u16 = args.single().unwrap_or(0);
+
+    let token = CancellationToken::new();
+
+    let session = match session::claim(
+        ctx,
+        msg.author.id,
+        SessionKind::Duel,
+        msg.channel_id,
+        token.clone(),
+    )
+    .await
+    {
+        Ok(session) => session,
+        Err(err) => {
+            msg.reply(
+                ctx,
+                "You already have a running instance of Zumbor you fool!",
+            )
+            .await?;
+            return Err(err);
+        }
+    };
+
+    // Both saves are free to be played again once the sessions drop, however the duel ended
+    let _opponent_session =
+        match session::claim(ctx, opponent.id, SessionKind::Duel, msg.channel_id, token).await {
+            Ok(session) => session,
+            Err(err) => {
+                msg.reply(ctx, format!("{} is busy adventuring", opponent.name))
+                    .await?;
+                return Err(err);
+            }
+        };
+
+    session
+        .until_killed(duel(ctx, msg, opponent.clone(), stake))
+        .await
+}
+
+async fn duel(ctx: &Context, msg: &Message, opponent: User, stake: u16) -> Result<(), Error> {
+    let rules = rules::for_guild(ctx, msg.guild_id).await;
+
+    let mut duellists = Vec::new();
+    for user in [msg.author.clone(), opponent] {
+        let Ok(mut player) = player::storage::load_save(ctx, &user.tag()).await else {
+            msg.reply(ctx, format!("{} has no saved character", user.name))
+                .await?;
+            return Err(Error::Plain("Duellist has no save"));
+        };
+        player.rules = rules.clone();
+
+        if player.score < stake {
+            msg.reply(
+                ctx,
+                format!("{} can't afford to stake {} score", player.name, stake),
+            )
+            .await?;
+            return Err(Error::Plain("Duellist can't afford the stake"));
+        }
+
+        duellists.push(Duellist {
+            user,
+            player,
+            wins: 0,
+        });
+    }
+
+    let mut message = msg
+        .channel_id
+        .send_message(
+            ctx,
+            CreateMessage::new()
+                .content(format!("<@{}>", duellists[1].user.id))
+                .embed(challenge_embed(&duellists, stake))
+                .components(vec![CreateActionRow::Buttons(vec![
+                    CreateButton::new("accept")
+                        .label("Accept")
+                        .style(ButtonStyle::Success),
+                    CreateButton::new("decline")
+                        .label("Decline")
+                        .style(ButtonStyle::Danger),
+                ])]),
+        )
+        .await?;
+
+    let interaction =
+        await_interactions::component(ctx, &message, duellists[1].user.tag().into()).await?;
+
+    if interaction.data.custom_id == "decline" {
+        update(
+            ctx,
+            &interaction,
+            CreateEmbed::new().title(format!("{} declined the duel", duellists[1].player.name)),
+            "",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let mut round = 1;
+    let mut last_round = String::new();
+    let mut interaction = interaction;
+    let (winner, loser, fled) = loop {
+        let attribute = Attribute::VALUES
+            .choose(&mut rand::thread_rng())
+            .expect("There are attributes")
+            .clone();
+
+        let mut rolls = Vec::new();
+        for turn in 0..duellists.len() {
+            update(
+                ctx,
+                &interaction,
+                round_embed(&duellists, round, &attribute, &rolls, &last_round),
+                &format!("Your turn {}", duellists[turn].player.name),
+            )
+            .await?;
+
+            let user_tag: Arc<str> = duellists[turn].user.tag().into();
+            interaction = match await_interactions::component(ctx, &message, user_tag).await {
+                Ok(interaction) => interaction,
+                Err(_) => {
+                    // Letting the timer run out forfeits the duel
+                    message
+                        .channel_id
+                        .say(
+                            ctx,
+                            format!("{} fled the duel", duellists[turn].player.name),
+                        )
+                        .await?;
+                    break;
+                }
+            };
+
+            rolls.push(
+                duellists[turn]
+                    .player
+                    .roll_stat(&attribute, &mut rand::thread_rng()),
+            );
+        }
+
+        if rolls.len() < duellists.len() {
+            let fled = rolls.len();
+            break (1 - fled, fled, true);
+        }
+
+        let round_loser = match score(&rolls[0]).cmp(&score(&rolls[1])) {
+            std::cmp::Ordering::Greater => Some(1),
+            std::cmp::Ordering::Less => Some(0),
+            std::cmp::Ordering::Equal => None,
+        };
+
+        last_round = format!(
+            "Round {} went to {} ({} vs {})",
+            round,
+            round_loser.map_or("nobody", |round_loser| {
+                duellists[1 - round_loser].player.name.as_str()
+            }),
+            describe(&rolls[0]),
+            describe(&rolls[1])
+        );
+
+        if let Some(round_loser) = round_loser {
+            duellists[1 - round_loser].wins += 1;
+            duellists[round_loser]
+                .player
+                .affect_health(&BaseHealthEffect {
+                    potency: -ROUND_DAMAGE,
+                });
+
+            let thorns = duellists[round_loser].player.thorns();
+            if thorns > 0 {
+                duellists[1 - round_loser]
+                    .player
+                    .affect_health(&BaseHealthEffect { potency: -thorns });
+            }
+        }
+
+        // Lingering effects carry on ticking between rounds, only on the duel's copies of the players
+        for duellist in duellists.iter_mut() {
+            duellist.player.apply_effects();
+        }
+
+        if let Some(index) = duellists
+            .iter()
+            .position(|duellist| duellist.wins >= ROUNDS_TO_WIN || duellist.player.health <= 0)
+        {
+            if duellists[index].player.health <= 0 {
+                break (1 - index, index, false);
+            }
+            break (index, 1 - index, false);
+        }
+
+        round += 1;
+    };
+
+    // Stakes come out of the real saves, not the copies the duel was fought with
+    if stake > 0 {
+        let mut winner_save =
+            player::storage::load_save(ctx, &duellists[winner].user.tag()).await?;
+        let mut loser_save = player::storage::load_save(ctx, &duellists[loser].user.tag()).await?;
+
+        let stake = stake.min(loser_save.score);
+        loser_save.score -= stake;
+        winner_save.add_score(stake);
+
+        loser_save.save(ctx).await?;
+        winner_save.save(ctx).await?;
+    }
+
+    let result = CreateEmbed::new()
+        .title(format!("{} wins the duel!", duellists[winner].player.name))
+        .description(format!(
+            "{} is bested after {} rounds",
+            duellists[loser].player.name, round
+        ))
+        .field(
+            &duellists[winner].player.name,
+            format!("{} rounds won", duellists[winner].wins),
+            true,
+        )
+        .field(
+            &duellists[loser].player.name,
+            format!("{} rounds won", duellists[loser].wins),
+            true,
+        )
+        .field("Stake", format!("{} score", stake), true);
+
+    // A fleeing duellist leaves the last interaction already answered
+    if !fled {
+        interaction
+            .create_response(ctx, CreateInteractionResponse::Acknowledge)
+            .await?;
+    }
+
+    message
+        .edit(
+            ctx,
+            EditMessage::new()
+                .content("")
+                .embed(result)
+                .components(Vec::new()),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Criticals beat any roll of the other kind
+fn score(roll: &RollResult) -> i16 {
+    match roll {
+        RollResult::CriticalFail => i16::MIN,
+        RollResult::CriticalSuccess => i16::MAX,
+        RollResult::Value(value) => *value,
+    }
+}
+
+fn describe(roll: &RollResult) -> String {
+    match roll {
+        RollResult::CriticalFail => "Critical fail!".to_string(),
+        RollResult::CriticalSuccess => "Critical success!".to_string(),
+        RollResult::Value(value) => value.to_string(),
+    }
+}
+
+fn challenge_embed(duellists: &[Duellist], stake: u16) -> CreateEmbed {
+    let embed = CreateEmbed::new()
+        .title(format!(
+            "{} challenges {} to a duel",
+            duellists[0].player.name, duellists[1].player.name
+        ))
+        .description(format!("First to {} rounds wins", ROUNDS_TO_WIN));
+
+    if stake > 0 {
+        embed.field("Stake", format!("{} score", stake), true)
+    } else {
+        embed
+    }
+}
+
+fn round_embed(
+    duellists: &[Duellist],
+    round: u8,
+    attribute: &Attribute,
+    rolls: &[RollResult],
+    last_round: &str,
+) -> CreateEmbed {
+    duellists.iter().enumerate().fold(
+        CreateEmbed::new()
+            .title(format!("Round {}: {}", round, attribute))
+            .description(last_round),
+        |embed, (index, duellist)| {
+            embed.field(
+                &duellist.player.name,
+                format!(
+                    "Health {} | Wins {}\n{}",
+                    duellist.player.health,
+                    duellist.wins,
+                    rolls
+                        .get(index)
+                        .map(describe)
+                        .unwrap_or_else(|| "Waiting to roll...".to_string())
+                ),
+                true,
+            )
+        },
+    )
+}
+
+/**
+ * Updates the duel message, showing the roll button when given whose turn it is
+ */
+async fn update(
+    ctx: &Context,
+    interaction: &ComponentInteraction,
+    embed: CreateEmbed,
+    turn: &str,
+) -> Result<(), Error> {
+    let components = if turn.is_empty() {
+        Vec::new()
+    } else {
+        vec![CreateActionRow::Buttons(vec![
+            CreateButton::new("roll").label("Roll")
+        ])]
+    };
+
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content(turn)
+                    .embed(embed)
+                    .components(components),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
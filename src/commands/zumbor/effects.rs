@@ -4,7 +4,10 @@ use serde::{Deserialize, Serialize};
 use serenity::all::Colour;
 use serenity::builder::CreateEmbed;
 
-use super::attributes::Attribute;
+use super::{
+    attributes::Attribute,
+    events::{EventSender, GameEvent},
+};
 
 use super::player::stats::Stats;
 
@@ -45,9 +48,20 @@ pub struct LingeringEffect {
     pub kind: LingeringEffectKind,
     pub name: LingeringEffectName,
     pub potency: i16,
+    /// Turns left before the effect expires, effects with no duration never do
     pub duration: i16,
 }
 
+impl LingeringEffect {
+    /// Buffs raise stats and debuffs lower them
+    pub fn signed_potency(&self) -> i16 {
+        match self.kind {
+            LingeringEffectKind::Buff => self.potency,
+            LingeringEffectKind::Debuff => -self.potency,
+        }
+    }
+}
+
 impl From<&LingeringEffect> for CreateEmbed {
     fn from(effect: &LingeringEffect) -> Self {
         CreateEmbed::new()
@@ -63,6 +77,24 @@ pub enum LingeringEffectName {
     Stat(Attribute),
     Poison,
     Regenerate,
+    /// Absorbs up to its potency in damage before breaking
+    Shield,
+    /// Takes the next choice out of the holder's hands
+    Stun,
+    /// Deals its potency back to whoever damages the holder in duels and boss fights
+    Thorns,
+    /// Rolls twice and keeps the better roll
+    Advantage,
+}
+
+impl LingeringEffectName {
+    pub fn stacking(&self) -> Stacking {
+        match self {
+            Self::Stat(_) | Self::Poison => Stacking::Stack,
+            Self::Regenerate | Self::Stun | Self::Advantage => Stacking::Refresh,
+            Self::Shield | Self::Thorns => Stacking::Strongest,
+        }
+    }
 }
 
 impl Display for LingeringEffectName {
@@ -71,6 +103,10 @@ impl Display for LingeringEffectName {
             Self::Stat(attr) => write!(f, "{}", attr),
             Self::Poison => write!(f, "Poison"),
             Self::Regenerate => write!(f, "Regnerate"),
+            Self::Shield => write!(f, "Shield"),
+            Self::Stun => write!(f, "Stun"),
+            Self::Thorns => write!(f, "Thorns"),
+            Self::Advantage => write!(f, "Advantage"),
         }
     }
 }
@@ -80,6 +116,10 @@ impl From<&LingeringEffectName> for Colour {
         match effect_name {
             LingeringEffectName::Poison => Colour::PURPLE,
             LingeringEffectName::Regenerate => Colour::FABLED_PINK,
+            LingeringEffectName::Shield => Colour::LIGHT_GREY,
+            LingeringEffectName::Stun => Colour::GOLD,
+            LingeringEffectName::Thorns => Colour::DARK_GREEN,
+            LingeringEffectName::Advantage => Colour::TEAL,
             LingeringEffectName::Stat(attr) => match attr {
                 Attribute::Strength => Colour::DARK_RED,
                 Attribute::Wisdom => Colour::DARK_GREEN,
@@ -90,6 +130,17 @@ impl From<&LingeringEffectName> for Colour {
     }
 }
 
+/// What happens when an effect is added while one of the same name and kind is already active
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Stacking {
+    /// Both effects are kept
+    Stack,
+    /// The existing effect's duration is reset
+    Refresh,
+    /// Only the more potent effect is kept
+    Strongest,
+}
+
 #[derive(PartialEq, Serialize, Deserialize, Clone, Debug)]
 pub enum LingeringEffectKind {
     Buff,
@@ -116,13 +167,99 @@ impl TryFrom<&str> for LingeringEffectKind {
     }
 }
 
+/// Reported by effectables as their lingering effects change
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum EffectEvent {
+    Started {
+        target: String,
+        effect: LingeringEffect,
+    },
+    Refreshed {
+        target: String,
+        effect: LingeringEffect,
+    },
+    Ticked {
+        target: String,
+        effect: LingeringEffect,
+    },
+    Expired {
+        target: String,
+        effect: LingeringEffect,
+    },
+    Absorbed {
+        target: String,
+        amount: i16,
+    },
+}
+
+impl Display for EffectEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EffectEvent::Started { target, effect } => {
+                write!(f, "{} received a {} {}", target, effect.name, effect.kind)
+            }
+            EffectEvent::Refreshed { target, effect } => {
+                write!(
+                    f,
+                    "{}'s {} {} was refreshed",
+                    target, effect.name, effect.kind
+                )
+            }
+            EffectEvent::Ticked { target, effect } => write!(
+                f,
+                "{} {} {} health from {}",
+                target,
+                match effect.name {
+                    LingeringEffectName::Regenerate => "gained",
+                    _ => "lost",
+                },
+                effect.potency,
+                effect.name
+            ),
+            EffectEvent::Expired { target, effect } => write!(
+                f,
+                "{}'s potency {} {} {} has expired",
+                target, effect.potency, effect.name, effect.kind
+            ),
+            EffectEvent::Absorbed { target, amount } => {
+                write!(f, "{}'s shield absorbed {} damage", target, amount)
+            }
+        }
+    }
+}
+
+impl From<&EffectEvent> for CreateEmbed {
+    fn from(event: &EffectEvent) -> Self {
+        match event {
+            EffectEvent::Started { effect, .. } | EffectEvent::Refreshed { effect, .. } => {
+                CreateEmbed::from(effect).title(event.to_string())
+            }
+            _ => CreateEmbed::new().title(event.to_string()),
+        }
+    }
+}
+
 pub trait Effectable {
+    fn get_name(&self) -> String;
     fn get_effects(&self) -> Vec<LingeringEffect>;
     fn set_effects(&mut self, effects: Vec<LingeringEffect>);
     fn get_health(&self) -> i16;
     fn set_health(&mut self, health: i16);
     fn get_stats(&self) -> Stats;
     fn set_stats(&mut self, stats: Stats);
+    fn get_events(&self) -> Option<&EventSender>;
+
+    /// The user tag behind the effectable, only players have one
+    fn get_tag(&self) -> Option<String> {
+        None
+    }
+
+    fn emit(&self, event: impl Into<GameEvent>) {
+        if let Some(events) = self.get_events() {
+            // Nobody listening is fine, the event has still happened
+            let _ = events.send(event.into());
+        }
+    }
 
     fn affect(&mut self, effect: &BaseEffect) {
         println!("Player affected by {:?}", effect);
@@ -139,9 +276,20 @@ pub trait Effectable {
             effect.potency
         );
 
-        self.set_health(self.get_health() + effect.potency);
+        let change = if effect.potency < 0 {
+            -self.absorb(-effect.potency)
+        } else {
+            effect.potency
+        };
+
+        let from = self.get_health();
+        self.set_health(from + change);
 
-        println!("After Health: {}", self.get_health());
+        self.emit(GameEvent::HealthChanged {
+            target: self.get_name(),
+            from,
+            to: self.get_health(),
+        });
     }
 
     fn affect_stat(&mut self, effect: &BaseAttributeEffect) {
@@ -151,38 +299,114 @@ pub trait Effectable {
         self.set_stats(stats);
     }
 
-    fn add_effect(&mut self, effect: LingeringEffect) {
+    /**
+     * Lets any shields soak up the damage, returning how much gets through
+     */
+    fn absorb(&mut self, damage: i16) -> i16 {
+        let mut remaining = damage;
+        let mut broken = Vec::new();
         let mut effects = self.get_effects();
 
-        if let LingeringEffectName::Stat(attr) = &effect.name {
-            self.affect_stat(&BaseAttributeEffect {
-                name: attr.clone(),
-                potency: effect.potency,
+        for shield in effects
+            .iter_mut()
+            .filter(|effect| effect.name == LingeringEffectName::Shield)
+        {
+            let absorbed = remaining.min(shield.potency);
+            shield.potency -= absorbed;
+            remaining -= absorbed;
+
+            if shield.potency <= 0 {
+                broken.push(shield.clone());
+            }
+        }
+
+        effects.retain(|effect| effect.name != LingeringEffectName::Shield || effect.potency > 0);
+        self.set_effects(effects);
+
+        if remaining < damage {
+            self.emit(EffectEvent::Absorbed {
+                target: self.get_name(),
+                amount: damage - remaining,
             });
         }
+        for shield in broken {
+            self.on_effect_expire(&shield);
+        }
 
-        effects.push(effect);
+        remaining
+    }
 
-        self.set_effects(effects);
+    fn has_effect(&self, name: &LingeringEffectName) -> bool {
+        self.get_effects().iter().any(|effect| effect.name == *name)
     }
 
-    fn remove_effect(&mut self, effect: &LingeringEffect) {
-        let mut effects = self.get_effects();
+    /// Damage dealt back to anything that hurts the holder
+    fn thorns(&self) -> i16 {
+        self.get_effects()
+            .iter()
+            .filter(|effect| effect.name == LingeringEffectName::Thorns)
+            .map(|effect| effect.potency)
+            .sum()
+    }
 
-        if let LingeringEffectName::Stat(name) = &effect.name {
-            let potency = match &effect.kind {
-                LingeringEffectKind::Buff => -effect.potency,
-                LingeringEffectKind::Debuff => effect.potency,
-            };
-            self.affect_stat(&BaseAttributeEffect {
-                name: name.clone(),
-                potency,
-            })
+    /**
+     * Adds the effect following its stacking rule
+     */
+    fn add_effect(&mut self, effect: LingeringEffect) {
+        let mut effects = self.get_effects();
+        let existing = effects
+            .iter()
+            .position(|eff| eff.name == effect.name && eff.kind == effect.kind);
+
+        match (effect.name.stacking(), existing) {
+            (Stacking::Refresh, Some(index)) => {
+                effects[index].duration = effect.duration;
+                let refreshed = effects[index].clone();
+                self.set_effects(effects);
+                self.emit(EffectEvent::Refreshed {
+                    target: self.get_name(),
+                    effect: refreshed,
+                });
+            }
+            (Stacking::Strongest, Some(index)) => {
+                if effect.potency > effects[index].potency {
+                    let weaker = effects[index].clone();
+                    self.remove_effect(&weaker);
+                    self.start_effect(effect);
+                }
+            }
+            (Stacking::Stack, _) | (_, None) => self.start_effect(effect),
         }
+    }
 
-        effects.retain(|eff| eff != effect);
+    fn start_effect(&mut self, effect: LingeringEffect) {
+        let mut effects = self.get_effects();
+        effects.push(effect.clone());
+        self.set_effects(effects);
+
+        self.on_effect_start(&effect);
+    }
 
+    fn remove_effect(&mut self, effect: &LingeringEffect) {
+        let mut effects = self.get_effects();
+        let Some(index) = effects.iter().position(|eff| eff == effect) else {
+            return;
+        };
+        effects.remove(index);
         self.set_effects(effects);
+
+        self.on_effect_expire(effect);
+    }
+
+    /**
+     * Removes every effect with the given name, for effects that are used up rather than worn off
+     */
+    fn consume_effect(&mut self, name: &LingeringEffectName) {
+        for effect in self.get_effects() {
+            if effect.name == *name {
+                self.remove_effect(&effect);
+            }
+        }
     }
 
     fn clear_effects(&mut self) {
@@ -192,26 +416,266 @@ pub trait Effectable {
         self.set_effects(Vec::new())
     }
 
+    /**
+     * Ticks every effect, counting down their durations and expiring any that run out
+     */
     fn apply_effects(&mut self) {
-        let effects = self.get_effects();
-
-        effects.iter().for_each(|effect| {
-            match effect.name {
-                LingeringEffectName::Poison => self.set_health(self.get_health() - effect.potency),
-                LingeringEffectName::Regenerate => {
-                    self.set_health(self.get_health() + effect.potency)
+        let mut remaining = Vec::new();
+        let mut expired = Vec::new();
+
+        for mut effect in self.get_effects() {
+            self.on_effect_tick(&effect);
+
+            match effect.duration {
+                // Effects without a duration are permanent
+                duration if duration <= 0 => remaining.push(effect),
+                1 => expired.push(effect),
+                _ => {
+                    effect.duration -= 1;
+                    remaining.push(effect);
                 }
-                _ => (),
             }
+        }
 
-            let mut new_effect = effect.clone();
+        self.set_effects(remaining);
 
-            self.remove_effect(effect);
+        for effect in expired {
+            self.on_effect_expire(&effect);
+        }
+    }
 
-            if effect.duration != 1 {
-                new_effect.duration -= 1;
-                self.add_effect(new_effect);
-            }
-        })
+    fn on_effect_start(&mut self, effect: &LingeringEffect) {
+        if let LingeringEffectName::Stat(name) = &effect.name {
+            self.affect_stat(&BaseAttributeEffect {
+                name: name.clone(),
+                potency: effect.signed_potency(),
+            });
+        }
+
+        self.emit(EffectEvent::Started {
+            target: self.get_name(),
+            effect: effect.clone(),
+        });
+    }
+
+    fn on_effect_tick(&mut self, effect: &LingeringEffect) {
+        let change = match effect.name {
+            LingeringEffectName::Poison => -effect.potency,
+            LingeringEffectName::Regenerate => effect.potency,
+            _ => return,
+        };
+
+        let before = self.get_health();
+        self.set_health(before + change);
+        self.emit(EffectEvent::Ticked {
+            target: self.get_name(),
+            effect: effect.clone(),
+        });
+
+        if let Some(tag) = self
+            .get_tag()
+            .filter(|_| before > 0 && self.get_health() <= 0)
+        {
+            self.emit(GameEvent::Succumbed {
+                tag,
+                name: self.get_name(),
+                effect: effect.name.clone(),
+            });
+        }
+    }
+
+    fn on_effect_expire(&mut self, effect: &LingeringEffect) {
+        if let LingeringEffectName::Stat(name) = &effect.name {
+            self.affect_stat(&BaseAttributeEffect {
+                name: name.clone(),
+                potency: -effect.signed_potency(),
+            });
+        }
+
+        self.emit(EffectEvent::Expired {
+            target: self.get_name(),
+            effect: effect.clone(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::player::{Player, PlayerDetails};
+    use super::*;
+
+    fn player() -> Player {
+        Player::new(
+            "tester".to_string(),
+            PlayerDetails {
+                name: "Tester".to_string(),
+                description: "Here to be hit".to_string(),
+            },
+            Stats {
+                charisma: 1,
+                strength: 2,
+                wisdom: 1,
+                agility: 1,
+            },
+        )
+    }
+
+    fn effect(
+        kind: LingeringEffectKind,
+        name: LingeringEffectName,
+        potency: i16,
+        duration: i16,
+    ) -> LingeringEffect {
+        LingeringEffect {
+            kind,
+            name,
+            potency,
+            duration,
+        }
+    }
+
+    #[test]
+    fn stacking_effects_are_all_kept() {
+        let mut player = player();
+        player.add_effect(effect(
+            LingeringEffectKind::Debuff,
+            LingeringEffectName::Poison,
+            1,
+            3,
+        ));
+        player.add_effect(effect(
+            LingeringEffectKind::Debuff,
+            LingeringEffectName::Poison,
+            2,
+            3,
+        ));
+
+        assert_eq!(player.effects.len(), 2);
+    }
+
+    #[test]
+    fn refreshing_effects_reset_their_duration() {
+        let mut player = player();
+        player.add_effect(effect(
+            LingeringEffectKind::Buff,
+            LingeringEffectName::Regenerate,
+            1,
+            3,
+        ));
+        player.apply_effects();
+        player.add_effect(effect(
+            LingeringEffectKind::Buff,
+            LingeringEffectName::Regenerate,
+            4,
+            3,
+        ));
+
+        assert_eq!(player.effects.len(), 1);
+        assert_eq!(player.effects[0].duration, 3);
+        assert_eq!(player.effects[0].potency, 1);
+    }
+
+    #[test]
+    fn only_the_strongest_effect_is_kept() {
+        let mut player = player();
+        player.add_effect(effect(
+            LingeringEffectKind::Buff,
+            LingeringEffectName::Shield,
+            3,
+            2,
+        ));
+        player.add_effect(effect(
+            LingeringEffectKind::Buff,
+            LingeringEffectName::Shield,
+            2,
+            2,
+        ));
+        assert_eq!(player.effects.len(), 1);
+        assert_eq!(player.effects[0].potency, 3);
+
+        player.add_effect(effect(
+            LingeringEffectKind::Buff,
+            LingeringEffectName::Shield,
+            5,
+            2,
+        ));
+        assert_eq!(player.effects.len(), 1);
+        assert_eq!(player.effects[0].potency, 5);
+    }
+
+    #[test]
+    fn buffs_and_debuffs_are_kept_apart() {
+        let mut player = player();
+        player.add_effect(effect(
+            LingeringEffectKind::Buff,
+            LingeringEffectName::Thorns,
+            2,
+            2,
+        ));
+        player.add_effect(effect(
+            LingeringEffectKind::Debuff,
+            LingeringEffectName::Thorns,
+            1,
+            2,
+        ));
+
+        assert_eq!(player.effects.len(), 2);
+    }
+
+    #[test]
+    fn stat_debuffs_are_reversed_when_they_expire() {
+        let mut player = player();
+        player.add_effect(effect(
+            LingeringEffectKind::Debuff,
+            LingeringEffectName::Stat(Attribute::Strength),
+            2,
+            2,
+        ));
+        assert_eq!(player.stats.strength, 0);
+
+        player.apply_effects();
+        assert_eq!(player.stats.strength, 0);
+
+        player.apply_effects();
+        assert!(player.effects.is_empty());
+        assert_eq!(player.stats.strength, 2);
+    }
+
+    #[test]
+    fn shields_absorb_damage_until_they_break() {
+        let mut player = player();
+        let health = player.health;
+        player.add_effect(effect(
+            LingeringEffectKind::Buff,
+            LingeringEffectName::Shield,
+            3,
+            0,
+        ));
+
+        player.affect_health(&BaseHealthEffect { potency: -2 });
+        assert_eq!(player.health, health);
+        assert_eq!(player.effects[0].potency, 1);
+
+        player.affect_health(&BaseHealthEffect { potency: -4 });
+        assert_eq!(player.health, health - 3);
+        assert!(player.effects.is_empty());
+    }
+
+    #[test]
+    fn effects_without_a_duration_never_expire() {
+        let mut player = player();
+        let health = player.health;
+        player.add_effect(effect(
+            LingeringEffectKind::Debuff,
+            LingeringEffectName::Poison,
+            1,
+            0,
+        ));
+
+        for _ in 0..5 {
+            player.apply_effects();
+        }
+        assert_eq!(player.effects.len(), 1);
+        assert_eq!(player.health, health - 5);
     }
 }
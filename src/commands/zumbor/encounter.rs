@@ -1,37 +1,68 @@
 use crate::errors::Error;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
-use rand::seq::SliceRandom;
+use rand::{seq::SliceRandom, Rng};
 use serde::{Deserialize, Serialize};
-use serde_json::{Map, Value};
 use serenity::{
     all::{Colour, CreateActionRow, CreateButton, CreateEmbed},
     prelude::Context,
 };
 
-use crate::StorageClient;
+use crate::storage::StorageClient;
+
+pub mod catalogue;
+pub mod schema;
+pub mod validation;
 
 use super::{
     attributes::Attribute,
-    effects::{
-        BaseAttributeEffect, BaseEffect, BaseHealthEffect, LingeringEffect, LingeringEffectKind,
-        LingeringEffectName,
-    },
-    player::RollResult,
+    effects::{BaseEffect, Effectable, LingeringEffect},
+    items::Item,
+    migration::Versioned,
+    player::{experience, Player, RollResult},
 };
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Encounter {
+    #[serde(default = "schema::current_version")]
+    pub version: u8,
     pub title: String,
     pub text: String,
+    #[serde(default, deserialize_with = "schema::deserialize_colour")]
     pub color: Option<Colour>,
     pub options: HashMap<String, EncounterOption>,
+    #[serde(default)]
+    pub rarity: Rarity,
+    #[serde(default)]
+    pub difficulty: Difficulty,
+    #[serde(default)]
+    pub min_score: Option<u16>,
+    #[serde(default)]
+    pub max_score: Option<u16>,
+    /// Chained encounters are only reached through the result of another encounter
+    #[serde(default)]
+    pub chained: bool,
+    /// The id of the zone the encounter is found in, encounters without one are found in the wilds
+    #[serde(default)]
+    pub zone: Option<String>,
 }
 
 impl Encounter {
     pub fn get_option(&mut self, name: &String) -> Option<&mut EncounterOption> {
         self.options.get_mut(name)
     }
+
+    /**
+     * Validates the raw encounter json before converting it from whichever version it was written in
+     */
+    pub fn from_slice(bytes: &[u8]) -> Result<Encounter, Error> {
+        Encounter::from_versioned(serde_json::from_slice(bytes)?)
+    }
+
+    pub fn is_available_at(&self, score: u16) -> bool {
+        self.min_score.is_none_or(|min| score >= min)
+            && self.max_score.is_none_or(|max| score <= max)
+    }
 }
 
 impl From<&Encounter> for CreateEmbed {
@@ -43,87 +74,125 @@ impl From<&Encounter> for CreateEmbed {
     }
 }
 
-impl From<&Encounter> for CreateActionRow {
-    fn from(enc: &Encounter) -> CreateActionRow {
+impl Encounter {
+    /**
+     * The options at least one of the players has the flags and key items to choose from
+     */
+    pub fn options_for<'a>(
+        &'a self,
+        players: &'a [&'a Player],
+    ) -> impl Iterator<Item = (&'a String, &'a EncounterOption)> {
+        self.options
+            .iter()
+            .filter(|(_, option)| players.iter().any(|player| option.is_available_to(player)))
+    }
+
+    pub fn action_row(&self, players: &[&Player]) -> CreateActionRow {
         CreateActionRow::Buttons(
-            enc.options
-                .keys()
-                .map(|key| CreateButton::new(key).label(key))
+            self.options_for(players)
+                .map(|(key, _)| CreateButton::new(key).label(key))
                 .collect(),
         )
     }
 }
 
-impl TryFrom<Value> for Encounter {
-    type Error = Error;
-
-    fn try_from(enc: Value) -> Result<Self, Self::Error> {
-        let mut options: HashMap<String, EncounterOption> = HashMap::new();
-
-        if let Value::Object(options_map) = enc["options"].clone() {
-            for (key, value) in options_map {
-                let success_result = if let Value::Object(res) = value["Success"].clone() {
-                    EncounterResult {
-                        kind: EncounterResultKind::Success(
-                            res["type"].as_str().unwrap().to_string(),
-                        ),
-                        title: res["title"].as_str().unwrap().into(),
-                        text: res["text"].as_str().unwrap().into(),
-                        base_effect: res.clone().try_into().ok(),
-                        lingering_effect: res.try_into().ok(),
-                    }
-                } else {
-                    panic!("Success result should be an object");
-                };
-
-                let fail_result = if let Value::Object(res) = value["Fail"].clone() {
-                    EncounterResult {
-                        kind: EncounterResultKind::Fail(res["type"].as_str().unwrap().to_string()),
-                        title: res["title"].as_str().unwrap().into(),
-                        text: res["text"].as_str().unwrap().into(),
-                        base_effect: res.clone().try_into().ok(),
-                        lingering_effect: res.try_into().ok(),
-                    }
-                } else {
-                    panic!("Success result should be an object");
-                };
-
-                let option = EncounterOption {
-                    threshold: value["threshold"].as_u64().unwrap().try_into().unwrap(),
-                    stat: value["stat"].as_str().unwrap().try_into().unwrap(),
-                    success: success_result,
-                    fail: fail_result,
-                };
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+pub enum Rarity {
+    #[default]
+    Common,
+    Uncommon,
+    Rare,
+    Legendary,
+}
 
-                options.insert(key, option);
-            }
+impl Rarity {
+    pub const VALUES: [Rarity; 4] = [
+        Rarity::Common,
+        Rarity::Uncommon,
+        Rarity::Rare,
+        Rarity::Legendary,
+    ];
+
+    pub fn weight(&self) -> u32 {
+        match self {
+            Rarity::Common => 100,
+            Rarity::Uncommon => 50,
+            Rarity::Rare => 20,
+            Rarity::Legendary => 5,
         }
+    }
+}
 
-        let color = match &enc["color"] {
-            Value::String(val) => hex_to_colour(val.as_ref()),
-            _ => hex_to_colour("#000000"),
-        };
+impl TryFrom<&str> for Rarity {
+    type Error = Error;
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Rarity::VALUES
+            .into_iter()
+            .find(|rarity| format!("{:?}", rarity).eq_ignore_ascii_case(value))
+            .ok_or(Error::Plain(
+                "Rarity should be Common, Uncommon, Rare or Legendary",
+            ))
+    }
+}
 
-        let encounter = Encounter {
-            title: enc["title"].to_string(),
-            text: enc["text"].to_string(),
-            color: Some(color),
-            options,
-        };
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+    Deadly,
+}
 
-        Ok(encounter)
+impl Difficulty {
+    pub const VALUES: [Difficulty; 4] = [
+        Difficulty::Easy,
+        Difficulty::Normal,
+        Difficulty::Hard,
+        Difficulty::Deadly,
+    ];
+
+    pub fn tier(&self) -> u16 {
+        match self {
+            Difficulty::Easy => 0,
+            Difficulty::Normal => 1,
+            Difficulty::Hard => 2,
+            Difficulty::Deadly => 3,
+        }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl TryFrom<&str> for Difficulty {
+    type Error = Error;
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Difficulty::VALUES
+            .into_iter()
+            .find(|difficulty| format!("{:?}", difficulty).eq_ignore_ascii_case(value))
+            .ok_or(Error::Plain(
+                "Difficulty should be Easy, Normal, Hard or Deadly",
+            ))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct EncounterOption {
     pub threshold: u8,
     pub stat: Attribute,
     pub success: EncounterResult,
     pub fail: EncounterResult,
+    #[serde(default)]
+    pub requires_flags: BTreeSet<String>,
+    /// Ids of the key items needed to pick this option
+    #[serde(default)]
+    pub requires_items: BTreeSet<String>,
 }
 
 impl EncounterOption {
+    pub fn is_available_to(&self, player: &Player) -> bool {
+        self.requires_flags.is_subset(&player.flags)
+            && self.requires_items.iter().all(|id| player.has_item(id))
+    }
+
     pub fn test(&mut self, roll: &RollResult) -> &mut EncounterResult {
         match roll {
             RollResult::CriticalFail => &mut self.fail,
@@ -147,6 +216,115 @@ pub struct EncounterResult {
     pub text: String,
     pub base_effect: Option<BaseEffect>,
     pub lingering_effect: Option<LingeringEffect>,
+    #[serde(default)]
+    pub next: Option<NextEncounter>,
+    #[serde(default)]
+    pub set_flags: BTreeSet<String>,
+    #[serde(default)]
+    pub clear_flags: BTreeSet<String>,
+    #[serde(default)]
+    pub items: Vec<Item>,
+    #[serde(default)]
+    pub target: ResultTarget,
+    /// Gold given to the targets, or taken from them when negative
+    #[serde(default)]
+    pub gold: i32,
+    /// The shop the attempter gets to browse once the result has played out
+    #[serde(default)]
+    pub shop: Option<String>,
+    /// The enemy the attempter has to fight once the result has played out
+    #[serde(default)]
+    pub enemy: Option<String>,
+}
+
+impl EncounterResult {
+    /**
+     * Sets and clears the result's flags on the player, hands out its items and gold and queues
+     * up any follow up encounter
+     */
+    pub fn progress(&self, player: &mut Player, rng: &mut impl Rng) {
+        for item in &self.items {
+            player.give_item(item.clone());
+        }
+        player.gold = player.gold.saturating_add_signed(self.gold);
+
+        for flag in &self.clear_flags {
+            player.flags.remove(flag);
+        }
+        player.flags.extend(self.set_flags.iter().cloned());
+
+        player.next_encounter = self.next.as_ref().and_then(|next| match next {
+            NextEncounter::Encounter(reference) => Some(reference.clone()),
+            NextEncounter::Pool(pool) => pool.choose(rng).cloned(),
+        });
+    }
+
+    /// Lands the result's progress and effects on one of the players it targets
+    pub fn land_on(&self, player: &mut Player, rng: &mut impl Rng) {
+        self.progress(player, rng);
+
+        if let Some(effect) = &self.base_effect {
+            player.affect(effect);
+        }
+        if let Some(effect) = &self.lingering_effect {
+            player.add_effect(effect.clone());
+        }
+    }
+}
+
+/// The result an attempt at an option came to, once it has played out on the attempter
+pub struct ResolvedResult {
+    pub result: EncounterResult,
+    pub succeeded: bool,
+    /// What the attempt is worth to each player the result targets
+    pub experience: u32,
+}
+
+/**
+ * Plays out the option for the roll the way every frontend does. The base effect is scaled by the
+ * ruleset's critical multiplier on a critical, then the result lands on the player and their turn ends
+ */
+pub fn resolve_result(
+    player: &mut Player,
+    option: &mut EncounterOption,
+    roll: &RollResult,
+    rng: &mut impl Rng,
+) -> ResolvedResult {
+    let threshold = option.threshold;
+    let mut result = option.test(roll).clone();
+    let succeeded = matches!(result.kind, EncounterResultKind::Success(_));
+
+    if let Some(effect) = &mut result.base_effect {
+        if !matches!(roll, RollResult::Value(_)) {
+            effect.set_potency(effect.get_potency() * player.rules.critical_multiplier);
+        }
+    }
+
+    result.land_on(player, rng);
+    player.end_turn();
+
+    ResolvedResult {
+        experience: experience::experience_for(roll, threshold, succeeded),
+        result,
+        succeeded,
+    }
+}
+
+/// Who in a party a result's effects, items and flags land on
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+pub enum ResultTarget {
+    /// Only the member who attempted the option
+    #[default]
+    Individual,
+    Party,
+}
+
+/// Refers to encounters by their path in the bucket, with or without the folder and extension
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum NextEncounter {
+    Encounter(String),
+    Pool(Vec<String>),
 }
 
 impl From<&EncounterResult> for CreateEmbed {
@@ -159,7 +337,7 @@ impl From<&EncounterResult> for CreateEmbed {
                 EncounterResultKind::Fail(_) => Colour::from((240, 40, 20)),
             });
 
-        if let Some(effect) = &result.base_effect {
+        let embed = if let Some(effect) = &result.base_effect {
             match effect {
                 BaseEffect::Attribute(effect) => {
                     embed.field(effect.name.clone(), effect.potency.to_string(), true)
@@ -170,6 +348,26 @@ impl From<&EncounterResult> for CreateEmbed {
             }
         } else {
             embed
+        };
+
+        let embed = match result.gold {
+            0 => embed,
+            gold => embed.field("Gold", format!("{:+}", gold), true),
+        };
+
+        if result.items.is_empty() {
+            embed
+        } else {
+            embed.field(
+                "Found",
+                result
+                    .items
+                    .iter()
+                    .map(|item| item.name.clone())
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                true,
+            )
         }
     }
 }
@@ -180,176 +378,83 @@ pub enum EncounterResultKind {
     Fail(String),
 }
 
-// Return a random encounter from the storage bucket
-pub async fn get_random(ctx: &Context) -> Result<Encounter, Error> {
-    let data = ctx.data.read().await;
-
-    let storage_client = data
-        .get::<StorageClient>()
-        .expect("Storage client is available in context");
+pub const ENCOUNTER_PREFIX: &str = "zumbor/encounters";
+pub const V2_PREFIX: &str = "zumbor/encounters/v2/";
 
-    let objects = storage_client.get_objects("zumbor/encounters").await?;
-
-    // println!("{:?}", objects);
-
-    let object = objects
-        .choose(&mut rand::thread_rng())
-        .expect("Random number is limited to the number of objects");
-
-    let byte_array = storage_client.get(&object.name).await?;
-
-    let encounter: Result<Encounter, _> = serde_json::from_slice(&byte_array);
-
-    // V2 encounters should be serializable straight to a struct
-    if let Ok(encounter) = encounter {
-        println!("Serialize success for {}", encounter.title);
-        return Ok(encounter);
-    } else {
-        println!("Failed to deserialize {:?}", encounter);
-    }
-
-    // Handles previous versions of the Encounter object
-    let enc: Value = serde_json::from_slice(&byte_array).unwrap();
-
-    let encounter = Encounter::try_from(enc)?;
-
-    let mut encounter_name = object
-        .name
-        .as_str()
-        .replace(" ", "-")
+/**
+ * The path the original encounters were copied to when they were first converted to v2
+ */
+pub fn converted_path(path: &str) -> String {
+    path.replace(' ', "-")
         .replace("%20", "-")
-        .replace("/encounters/", "/encounters/v2/");
-
-    encounter_name.make_ascii_lowercase();
-
-    let encounter_json: String = serde_json::to_string(&encounter).map_err(Error::from)?;
+        .replace("/encounters/", "/encounters/v2/")
+        .to_ascii_lowercase()
+}
 
-    storage_client
-        .create_json(&encounter_name, encounter_json)
+/**
+ * Saves a new encounter under a path derived from its title, returning the path it was saved to
+ */
+pub async fn save(storage_client: &StorageClient, encounter: &Encounter) -> Result<String, Error> {
+    // Titles without any letters or numbers in them still need a name to be saved under
+    let slug = match slugify(&encounter.title) {
+        slug if slug.is_empty() => format!("{:016x}", rand::random::<u64>()),
+        slug => slug,
+    };
+    let existing = storage_client
+        .get_count(&format!("{}{}", V2_PREFIX, slug))
         .await?;
 
-    Ok(encounter)
-}
+    let path = match existing {
+        0 => format!("{}{}.json", V2_PREFIX, slug),
+        count => format!("{}{}-{}.json", V2_PREFIX, slug, count),
+    };
 
-impl TryFrom<Map<String, Value>> for BaseEffect {
-    type Error = Error;
+    let encounter_json = serde_json::to_string(encounter)?;
+    storage_client.create_json(&path, encounter_json).await?;
 
-    fn try_from(value: Map<String, Value>) -> Result<Self, Self::Error> {
-        let effect = value
-            .get("baseEffect")
-            .map(|val| val.as_object().expect("baseEffect to be an object"));
-
-        effect
-            .and_then(
-                |effect| match effect["name"].as_str().expect("name is a string") {
-                    "Heal" => Some(BaseEffect::Health(BaseHealthEffect {
-                        potency: effect["potency"].as_u64().unwrap().try_into().unwrap(),
-                    })),
-                    "Damage" => Some(BaseEffect::Health(BaseHealthEffect {
-                        potency: -<i64 as TryInto<i16>>::try_into(
-                            effect["potency"].as_i64().unwrap(),
-                        )
-                        .unwrap(),
-                    })),
-                    _ => effect["name"]
-                        .as_str()
-                        .expect("Name to be a string")
-                        .try_into()
-                        .map(|name| {
-                            BaseEffect::Attribute(BaseAttributeEffect {
-                                name,
-                                potency: effect["potency"].as_i64().unwrap().try_into().unwrap(),
-                            })
-                        })
-                        .map_err(|err| println!("{:?}", err))
-                        .ok(),
-                },
-            )
-            .ok_or(Error::Plain("Hmmm"))
-    }
+    Ok(path)
 }
 
-fn hex_to_colour(hex: &str) -> Colour {
-    let hex_str = &hex[1..];
-    Colour::from(u64::from_str_radix(hex_str, 16).expect("Incorrect format"))
+fn slugify(title: &str) -> String {
+    title
+        .to_ascii_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>()
+        .join("-")
 }
 
-impl TryFrom<Map<String, Value>> for LingeringEffect {
-    type Error = Error;
-
-    fn try_from(value: Map<String, Value>) -> Result<Self, Self::Error> {
-        let effect = value
-            .get("additionalEffect")
-            .map(|val| val.as_object().expect("additionalEffect to be an object"));
-
-        println!("{:?}", effect);
-
-        effect
-            .and_then(
-                |effect| match effect["name"].as_str().expect("name is a string") {
-                    "Poison" => Some(LingeringEffect {
-                        kind: LingeringEffectKind::Debuff,
-                        name: LingeringEffectName::Poison,
-                        duration: effect["duration"]
-                            .as_u64()
-                            .expect("duration to be a number")
-                            .try_into()
-                            .expect("Should be small enough to convert to a 16 bit signed integer"),
-                        potency: effect["potency"]
-                            .as_u64()
-                            .expect("potency to be a number")
-                            .try_into()
-                            .expect("Should be small enough to convert to a 16 bit signed integer"),
-                    }),
-                    "Regenerate" => Some(LingeringEffect {
-                        kind: LingeringEffectKind::Debuff,
-                        name: LingeringEffectName::Poison,
-                        duration: effect["duration"]
-                            .as_u64()
-                            .expect("duration to be a number")
-                            .try_into()
-                            .expect("Should be small enough to convert to a 16 bit signed integer"),
-                        potency: effect["potency"]
-                            .as_u64()
-                            .expect("potency to be a number")
-                            .try_into()
-                            .expect("Should be small enough to convert to a 16 bit signed integer"),
-                    }),
-                    _ => effect["name"]
-                        .as_str()
-                        .expect("Name to be a string")
-                        .try_into()
-                        .map(|name| LingeringEffect {
-                            kind: LingeringEffectKind::try_from(
-                                effect["type"].as_str().expect("type to be a string"),
-                            )
-                            .unwrap_or_else(|err| {
-                                println!("Using default buff type. {}", err);
-                                LingeringEffectKind::Buff
-                            }),
-                            name: LingeringEffectName::Stat(name),
-                            duration: effect["duration"]
-                                .as_u64()
-                                .expect("duration to be a number")
-                                .try_into()
-                                .expect(
-                                    "Should be small enough to convert to a 16 bit signed integer",
-                                ),
-                            potency: effect["potency"]
-                                .as_u64()
-                                .expect("potency to be a number")
-                                .try_into()
-                                .expect(
-                                    "Should be small enough to convert to a 16 bit signed integer",
-                                ),
-                        })
-                        .map_err(|err| {
-                            println!("{:?}", err);
-                            err
-                        })
-                        .ok(),
-                },
-            )
-            .ok_or(Error::Plain("O no"))
+/**
+ * Returns the encounter the player's chain leads to, or a random one suited to the given score,
+ * along with the path it was loaded from
+ */
+pub async fn get_next(
+    ctx: &Context,
+    player: &mut Player,
+    score: u16,
+    rng: &mut impl Rng,
+) -> Result<(String, Encounter), Error> {
+    let catalogue = catalogue::get(ctx).await?;
+
+    let chained = player.next_encounter.take().and_then(|reference| {
+        let entry = catalogue.find(&reference);
+        if entry.is_none() {
+            println!("Chained encounter {} could not be found", reference);
+        }
+        entry
+    });
+
+    let entry = match chained {
+        Some(entry) => entry,
+        None => catalogue
+            .choose_at(player, score, rng)
+            .ok_or(Error::Plain("No valid encounters could be found"))?,
+    };
+
+    let mut encounter = entry.encounter.clone();
+    if encounter.color.is_none() {
+        encounter.color = catalogue.zone_colour(player, rng);
     }
+
+    Ok((entry.path.clone(), encounter))
 }
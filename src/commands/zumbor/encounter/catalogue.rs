@@ -0,0 +1,201 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use rand::{seq::SliceRandom, Rng};
+use serenity::{
+    all::Colour,
+    prelude::{Context, TypeMapKey},
+};
+
+use crate::{
+    commands::zumbor::{
+        player::Player,
+        zone::{Zone, ZONE_PREFIX},
+    },
+    errors::Error,
+    storage::StorageClient,
+};
+
+use super::{converted_path, Difficulty, Encounter, ENCOUNTER_PREFIX, V2_PREFIX};
+
+/// How long a loaded catalogue is used before the bucket is checked for new encounters
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 10);
+
+/// Score needed before each further difficulty tier becomes the most likely pick
+const SCORE_PER_TIER: u16 = 25;
+
+pub struct CatalogueEntry {
+    pub path: String,
+    pub encounter: Encounter,
+}
+
+/// Every valid encounter and zone in the bucket, kept in memory so they can be weighed against each other
+pub struct EncounterCatalogue {
+    pub entries: Vec<CatalogueEntry>,
+    /// Zones by their id
+    pub zones: BTreeMap<String, Zone>,
+    loaded_at: Instant,
+}
+
+impl EncounterCatalogue {
+    pub fn new(entries: Vec<CatalogueEntry>, zones: BTreeMap<String, Zone>) -> Self {
+        EncounterCatalogue {
+            entries,
+            zones,
+            loaded_at: Instant::now(),
+        }
+    }
+
+    /**
+     * Loads every encounter and zone in the bucket, skipping any that fail validation
+     */
+    pub async fn load(storage_client: &StorageClient) -> Result<Self, Error> {
+        let objects = storage_client.get_objects(ENCOUNTER_PREFIX).await?;
+        let paths: HashSet<&str> = objects.iter().map(|object| object.name.as_str()).collect();
+        let mut entries = Vec::new();
+
+        for object in &objects {
+            // Originals that were converted to v2 would otherwise be picked twice as often
+            if !object.name.starts_with(V2_PREFIX)
+                && paths.contains(converted_path(&object.name).as_str())
+            {
+                continue;
+            }
+
+            let byte_array = storage_client.get(&object.name).await?;
+
+            let encounter = match Encounter::from_slice(&byte_array) {
+                Ok(encounter) => encounter,
+                Err(err) => {
+                    println!("Skipping invalid encounter {}. {}", object.name, err);
+                    continue;
+                }
+            };
+
+            entries.push(CatalogueEntry {
+                path: object.name.clone(),
+                encounter,
+            });
+        }
+
+        let mut zones = BTreeMap::new();
+        for object in storage_client.get_objects(ZONE_PREFIX).await? {
+            let byte_array = storage_client.get(&object.name).await?;
+
+            match serde_json::from_slice::<Zone>(&byte_array) {
+                Ok(zone) => {
+                    zones.insert(Zone::id_from_path(&object.name), zone);
+                }
+                Err(err) => println!("Skipping invalid zone {}. {}", object.name, err),
+            }
+        }
+
+        Ok(EncounterCatalogue::new(entries, zones))
+    }
+
+    pub fn is_stale(&self) -> bool {
+        self.loaded_at.elapsed() > REFRESH_INTERVAL
+    }
+
+    /**
+     * Finds an encounter by its full path, or by its file name with or without the extension
+     */
+    pub fn find(&self, reference: &str) -> Option<&CatalogueEntry> {
+        let file_name = format!("/{}", reference.trim_end_matches(".json"));
+
+        self.entries.iter().find(|entry| {
+            entry.path == reference || entry.path.trim_end_matches(".json").ends_with(&file_name)
+        })
+    }
+
+    /**
+     * Picks an encounter from the player's zone weighted by its rarity and how close its difficulty
+     * is to the player's, avoiding the encounters the player has most recently seen where possible.
+     * Zones without any encounters for the player fall back on every zone's
+     */
+    pub fn choose(&self, player: &Player, rng: &mut impl Rng) -> Option<&CatalogueEntry> {
+        self.choose_at(player, player.score, rng)
+    }
+
+    /// Picks as `choose` does, but for the given score rather than the player's own
+    pub fn choose_at(
+        &self,
+        player: &Player,
+        score: u16,
+        rng: &mut impl Rng,
+    ) -> Option<&CatalogueEntry> {
+        let all: Vec<&CatalogueEntry> = self
+            .entries
+            .iter()
+            .filter(|entry| !entry.encounter.chained && entry.encounter.is_available_at(score))
+            .collect();
+
+        let zoned: Vec<&CatalogueEntry> = all
+            .iter()
+            .copied()
+            .filter(|entry| entry.encounter.zone == player.zone)
+            .collect();
+
+        let available = if zoned.is_empty() { all } else { zoned };
+
+        let unseen: Vec<&CatalogueEntry> = available
+            .iter()
+            .copied()
+            .filter(|entry| !player.recent_encounters.contains(&entry.path))
+            .collect();
+
+        let candidates = if unseen.is_empty() { available } else { unseen };
+
+        candidates
+            .choose_weighted(rng, |entry| weight(&entry.encounter, score))
+            .ok()
+            .copied()
+    }
+
+    /// A colour from the palette of the player's zone, if it has one
+    pub fn zone_colour(&self, player: &Player, rng: &mut impl Rng) -> Option<Colour> {
+        let zone = self.zones.get(player.zone.as_ref()?)?;
+        zone.palette.choose(rng).copied()
+    }
+}
+
+fn weight(encounter: &Encounter, score: u16) -> u32 {
+    let preferred_tier = (score / SCORE_PER_TIER).min(Difficulty::Deadly.tier());
+    let distance = encounter.difficulty.tier().abs_diff(preferred_tier);
+
+    encounter.rarity.weight() << (Difficulty::Deadly.tier() - distance)
+}
+
+impl TypeMapKey for EncounterCatalogue {
+    type Value = Arc<EncounterCatalogue>;
+}
+
+/**
+ * Returns the cached catalogue, reloading it from the bucket when it has gone stale
+ */
+pub async fn get(ctx: &Context) -> Result<Arc<EncounterCatalogue>, Error> {
+    if let Some(catalogue) = ctx.data.read().await.get::<EncounterCatalogue>() {
+        if !catalogue.is_stale() {
+            return Ok(catalogue.clone());
+        }
+    }
+
+    let catalogue = {
+        let data = ctx.data.read().await;
+        let storage_client = data
+            .get::<StorageClient>()
+            .ok_or(Error::Plain("Storage client not accessible!"))?;
+
+        Arc::new(EncounterCatalogue::load(storage_client).await?)
+    };
+
+    ctx.data
+        .write()
+        .await
+        .insert::<EncounterCatalogue>(catalogue.clone());
+
+    Ok(catalogue)
+}
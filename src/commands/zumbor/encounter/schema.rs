@@ -0,0 +1,399 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use serenity::all::Colour;
+
+use crate::{
+    commands::zumbor::migration::{Migration, Versioned},
+    commands::zumbor::{
+        attributes::Attribute,
+        effects::{
+            BaseAttributeEffect, BaseEffect, BaseHealthEffect, LingeringEffect,
+            LingeringEffectKind, LingeringEffectName,
+        },
+    },
+    errors::Error,
+};
+
+use super::{validation, Encounter, EncounterOption, EncounterResult, EncounterResultKind};
+
+pub const CURRENT_VERSION: u8 = 2;
+
+pub enum SchemaVersion {
+    /// The original hand written format, with camel case keys and hex colours
+    V1,
+    /// A direct serialization of the `Encounter` struct
+    V2,
+}
+
+pub fn detect_version(value: &Value) -> Result<SchemaVersion, String> {
+    match value.get("version") {
+        Some(Value::Number(num)) => match num.as_u64() {
+            Some(1) => Ok(SchemaVersion::V1),
+            Some(2) => Ok(SchemaVersion::V2),
+            _ => Err(format!("Unsupported encounter version {}", num)),
+        },
+        Some(other) => Err(format!("Expected a version number but found {}", other)),
+        // Unversioned files are told apart by the casing of their result keys
+        None => {
+            let is_v1 = value["options"].as_object().is_some_and(|options| {
+                options
+                    .values()
+                    .any(|option| option.get("Success").is_some() || option.get("Fail").is_some())
+            });
+
+            Ok(if is_v1 {
+                SchemaVersion::V1
+            } else {
+                SchemaVersion::V2
+            })
+        }
+    }
+}
+
+pub fn current_version() -> u8 {
+    CURRENT_VERSION
+}
+
+impl Versioned for Encounter {
+    const MIGRATIONS: &'static [Migration] = &[migrate_v1];
+
+    fn detect_version(value: &Value) -> Result<u8, Error> {
+        match detect_version(value) {
+            Ok(SchemaVersion::V1) => Ok(1),
+            Ok(SchemaVersion::V2) => Ok(2),
+            Err(_) => Err(Error::Plain("Unsupported encounter version")),
+        }
+    }
+
+    /// Only errors keep an encounter out of play, balance warnings are left for the linter
+    fn validate(value: &Value) -> Result<(), Error> {
+        let errors: Vec<String> = validation::validate(value)
+            .iter()
+            .filter(|issue| issue.is_error())
+            .map(|issue| issue.to_string())
+            .collect();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Validation(errors))
+        }
+    }
+}
+
+fn migrate_v1(value: Value) -> Result<Value, Error> {
+    let encounter: Encounter = serde_json::from_value::<EncounterV1>(value)?.try_into()?;
+
+    Ok(serde_json::to_value(encounter)?)
+}
+
+pub fn hex_to_colour(hex: &str) -> Option<Colour> {
+    let hex_str = hex.strip_prefix('#')?;
+
+    if hex_str.len() != 6 {
+        return None;
+    }
+
+    u32::from_str_radix(hex_str, 16).ok().map(Colour::from)
+}
+
+fn colour_from_value(value: Value) -> Result<Colour, String> {
+    match value {
+        Value::String(hex) => {
+            hex_to_colour(&hex).ok_or_else(|| format!("{} is not a valid colour", hex))
+        }
+        value => Colour::deserialize(value).map_err(|err| err.to_string()),
+    }
+}
+
+/**
+ * Accepts either the serialized colour number or a "#rrggbb" string
+ */
+pub fn deserialize_colour<'de, D>(deserializer: D) -> Result<Option<Colour>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<Value>::deserialize(deserializer)? {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => colour_from_value(value)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+/**
+ * A list of colours, each written in either form `deserialize_colour` accepts
+ */
+pub fn deserialize_palette<'de, D>(deserializer: D) -> Result<Vec<Colour>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Vec::<Value>::deserialize(deserializer)?
+        .into_iter()
+        .map(colour_from_value)
+        .collect::<Result<Vec<Colour>, String>>()
+        .map_err(serde::de::Error::custom)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EncounterV1 {
+    pub title: String,
+    pub text: String,
+    pub color: Option<String>,
+    pub options: HashMap<String, EncounterOptionV1>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EncounterOptionV1 {
+    pub threshold: u8,
+    pub stat: String,
+    #[serde(rename = "Success")]
+    pub success: EncounterResultV1,
+    #[serde(rename = "Fail")]
+    pub fail: EncounterResultV1,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EncounterResultV1 {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub title: String,
+    pub text: String,
+    pub base_effect: Option<BaseEffectV1>,
+    pub additional_effect: Option<LingeringEffectV1>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct BaseEffectV1 {
+    pub name: String,
+    pub potency: i16,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LingeringEffectV1 {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    pub potency: i16,
+    pub duration: i16,
+}
+
+impl TryFrom<EncounterV1> for Encounter {
+    type Error = Error;
+
+    fn try_from(enc: EncounterV1) -> Result<Self, Self::Error> {
+        let mut options = HashMap::new();
+
+        for (key, option) in enc.options {
+            let option = EncounterOption {
+                threshold: option.threshold,
+                stat: option.stat.as_str().try_into()?,
+                success: option.success.into_result(EncounterResultKind::Success)?,
+                fail: option.fail.into_result(EncounterResultKind::Fail)?,
+                requires_flags: Default::default(),
+                requires_items: Default::default(),
+            };
+            options.insert(key, option);
+        }
+
+        let color = match enc.color {
+            Some(hex) => Some(hex_to_colour(&hex).ok_or(Error::Plain("Invalid colour hex"))?),
+            None => None,
+        };
+
+        Ok(Encounter {
+            version: CURRENT_VERSION,
+            title: enc.title,
+            text: enc.text,
+            color,
+            options,
+            rarity: Default::default(),
+            difficulty: Default::default(),
+            min_score: None,
+            max_score: None,
+            chained: false,
+            zone: None,
+        })
+    }
+}
+
+impl EncounterResultV1 {
+    fn into_result(
+        self,
+        kind: impl FnOnce(String) -> EncounterResultKind,
+    ) -> Result<EncounterResult, Error> {
+        Ok(EncounterResult {
+            kind: kind(self.kind),
+            title: self.title,
+            text: self.text,
+            base_effect: self.base_effect.map(BaseEffect::try_from).transpose()?,
+            lingering_effect: self
+                .additional_effect
+                .map(LingeringEffect::try_from)
+                .transpose()?,
+            next: None,
+            set_flags: Default::default(),
+            clear_flags: Default::default(),
+            items: Vec::new(),
+            target: Default::default(),
+            gold: 0,
+            shop: None,
+            enemy: None,
+        })
+    }
+}
+
+impl TryFrom<BaseEffectV1> for BaseEffect {
+    type Error = Error;
+
+    fn try_from(effect: BaseEffectV1) -> Result<Self, Self::Error> {
+        Ok(match effect.name.as_str() {
+            "Heal" => BaseEffect::Health(BaseHealthEffect {
+                potency: effect.potency,
+            }),
+            "Damage" => BaseEffect::Health(BaseHealthEffect {
+                potency: -effect.potency,
+            }),
+            name => BaseEffect::Attribute(BaseAttributeEffect {
+                name: Attribute::try_from(name)?,
+                potency: effect.potency,
+            }),
+        })
+    }
+}
+
+impl TryFrom<LingeringEffectV1> for LingeringEffect {
+    type Error = Error;
+
+    fn try_from(effect: LingeringEffectV1) -> Result<Self, Self::Error> {
+        let (name, kind) = match effect.name.as_str() {
+            "Poison" => (LingeringEffectName::Poison, LingeringEffectKind::Debuff),
+            "Regenerate" => (LingeringEffectName::Regenerate, LingeringEffectKind::Buff),
+            "Shield" => (LingeringEffectName::Shield, LingeringEffectKind::Buff),
+            "Stun" => (LingeringEffectName::Stun, LingeringEffectKind::Debuff),
+            "Thorns" => (LingeringEffectName::Thorns, LingeringEffectKind::Buff),
+            "Advantage" => (LingeringEffectName::Advantage, LingeringEffectKind::Buff),
+            name => (
+                LingeringEffectName::Stat(Attribute::try_from(name)?),
+                effect
+                    .kind
+                    .as_deref()
+                    .and_then(|kind| LingeringEffectKind::try_from(kind).ok())
+                    .unwrap_or(LingeringEffectKind::Buff),
+            ),
+        };
+
+        Ok(LingeringEffect {
+            kind,
+            name,
+            potency: effect.potency,
+            duration: effect.duration,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn v1_encounter() -> Value {
+        json!({
+            "title": "A troll",
+            "text": "It blocks the bridge",
+            "color": "#ff0000",
+            "options": {
+                "Fight": {
+                    "threshold": 12,
+                    "stat": "Strength",
+                    "Success": {
+                        "type": "You won",
+                        "title": "Won",
+                        "text": "It flees",
+                        "baseEffect": { "name": "Heal", "potency": 2 },
+                    },
+                    "Fail": {
+                        "type": "You lost",
+                        "title": "Lost",
+                        "text": "It hits back",
+                        "baseEffect": { "name": "Damage", "potency": 3 },
+                        "additionalEffect": {
+                            "name": "Poison",
+                            "type": "Debuff",
+                            "potency": 1,
+                            "duration": 2,
+                        },
+                    },
+                },
+            },
+        })
+    }
+
+    #[test]
+    fn detects_v1_by_its_result_keys() {
+        assert!(matches!(
+            detect_version(&v1_encounter()),
+            Ok(SchemaVersion::V1)
+        ));
+        assert!(matches!(
+            detect_version(&json!({ "options": {} })),
+            Ok(SchemaVersion::V2)
+        ));
+        assert!(detect_version(&json!({ "version": 9 })).is_err());
+    }
+
+    #[test]
+    fn migrates_v1_encounters() {
+        let encounter = Encounter::from_versioned(v1_encounter()).unwrap();
+
+        assert_eq!(encounter.version, CURRENT_VERSION);
+        assert_eq!(encounter.color, Some(Colour::from(0xff0000)));
+
+        let option = &encounter.options["Fight"];
+        assert_eq!(option.stat, Attribute::Strength);
+        assert!(matches!(
+            option.success.base_effect,
+            Some(BaseEffect::Health(BaseHealthEffect { potency: 2 }))
+        ));
+        assert!(matches!(
+            option.fail.base_effect,
+            Some(BaseEffect::Health(BaseHealthEffect { potency: -3 }))
+        ));
+
+        let poison = option.fail.lingering_effect.as_ref().unwrap();
+        assert_eq!(poison.name, LingeringEffectName::Poison);
+        assert_eq!(poison.kind, LingeringEffectKind::Debuff);
+    }
+
+    #[test]
+    fn migrated_encounters_round_trip() {
+        let encounter = Encounter::from_versioned(v1_encounter()).unwrap();
+        let value = serde_json::to_value(&encounter).unwrap();
+
+        let (upgraded, changed) = Encounter::upgrade(value).unwrap();
+        assert!(!changed);
+        assert_eq!(upgraded["options"]["Fight"]["threshold"], 12);
+    }
+
+    #[test]
+    fn structural_errors_are_rejected_on_load() {
+        let mut value = v1_encounter();
+        value["options"]["Fight"]["stat"] = json!("Luck");
+
+        assert!(matches!(
+            Encounter::from_versioned(value),
+            Err(Error::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn hex_colours_need_six_digits() {
+        assert_eq!(hex_to_colour("#00ff00"), Some(Colour::from(0x00ff00)));
+        assert_eq!(hex_to_colour("00ff00"), None);
+        assert_eq!(hex_to_colour("#0f0"), None);
+    }
+}
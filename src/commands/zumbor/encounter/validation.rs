@@ -0,0 +1,685 @@
+use std::{
+    fmt::{self, Display},
+    ops::RangeInclusive,
+};
+
+use serde_json::{Map, Value};
+
+use crate::commands::zumbor::{
+    attributes::Attribute,
+    effects::LingeringEffectKind,
+    encounter::{Difficulty, Rarity, ResultTarget},
+    items::Item,
+};
+
+use super::schema::{self, SchemaVersion};
+
+/// Below 2 an option can only be failed by a critical, above 25 it can't be passed without one
+pub const THRESHOLD_RANGE: RangeInclusive<i64> = 2..=25;
+pub const POTENCY_RANGE: RangeInclusive<i64> = -20..=20;
+pub const DURATION_RANGE: RangeInclusive<i64> = 1..=20;
+pub const GOLD_RANGE: RangeInclusive<i64> = -100..=100;
+/// Discord fits 5 buttons in a row and one is kept free for the player's class option
+pub const MAX_OPTIONS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The encounter can't be played as written and is rejected when loaded
+    Error,
+    /// The encounter plays, but is likely to be unbalanced or unreachable
+    Warning,
+}
+
+/// A single problem with an encounter file, located by its path within the json
+#[derive(Debug, Clone)]
+pub struct Issue {
+    pub path: String,
+    pub message: String,
+    pub severity: Severity,
+}
+
+impl Issue {
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "{}: {}", self.path, self.message),
+            Severity::Warning => write!(f, "{}: {} (warning)", self.path, self.message),
+        }
+    }
+}
+
+/**
+ * Parses and validates the raw bytes of an encounter file, collecting every issue found
+ */
+pub fn lint(bytes: &[u8]) -> Vec<Issue> {
+    match serde_json::from_slice::<Value>(bytes) {
+        Ok(value) => validate(&value),
+        Err(err) => vec![Issue {
+            path: "$".to_string(),
+            message: format!("Invalid json: {}", err),
+            severity: Severity::Error,
+        }],
+    }
+}
+
+pub fn validate(value: &Value) -> Vec<Issue> {
+    let mut validator = Validator::default();
+
+    let Some(encounter) = validator.object(value, "$") else {
+        return validator.issues;
+    };
+
+    match schema::detect_version(value) {
+        Ok(version) => validator.encounter(encounter, &version),
+        Err(err) => validator.report("$.version", err),
+    }
+
+    validator.issues
+}
+
+#[derive(Default)]
+struct Validator {
+    issues: Vec<Issue>,
+}
+
+impl Validator {
+    fn report(&mut self, path: &str, message: impl Into<String>) {
+        self.issues.push(Issue {
+            path: path.to_string(),
+            message: message.into(),
+            severity: Severity::Error,
+        });
+    }
+
+    fn warn(&mut self, path: &str, message: impl Into<String>) {
+        self.issues.push(Issue {
+            path: path.to_string(),
+            message: message.into(),
+            severity: Severity::Warning,
+        });
+    }
+
+    fn object<'v>(&mut self, value: &'v Value, path: &str) -> Option<&'v Map<String, Value>> {
+        let object = value.as_object();
+        if object.is_none() {
+            self.report(path, "Expected an object");
+        }
+        object
+    }
+
+    fn field<'v>(
+        &mut self,
+        map: &'v Map<String, Value>,
+        path: &str,
+        key: &str,
+    ) -> Option<&'v Value> {
+        match map.get(key) {
+            Some(Value::Null) | None => {
+                self.report(&format!("{}.{}", path, key), "Missing required field");
+                None
+            }
+            some => some,
+        }
+    }
+
+    fn string<'v>(
+        &mut self,
+        map: &'v Map<String, Value>,
+        path: &str,
+        key: &str,
+    ) -> Option<&'v str> {
+        let value = self.field(map, path, key)?;
+        let string = value.as_str();
+        match string {
+            Some("") => self.report(&format!("{}.{}", path, key), "Should not be empty"),
+            None => self.report(&format!("{}.{}", path, key), "Expected a string"),
+            _ => (),
+        }
+        string
+    }
+
+    /**
+     * Reads an integer, warning when it falls outside of the range the game is balanced around
+     */
+    fn integer(
+        &mut self,
+        map: &Map<String, Value>,
+        path: &str,
+        key: &str,
+        range: RangeInclusive<i64>,
+    ) -> Option<i64> {
+        let value = self.field(map, path, key)?;
+        let path = format!("{}.{}", path, key);
+        match value.as_i64() {
+            Some(num) => {
+                if !range.contains(&num) {
+                    self.warn(
+                        &path,
+                        format!(
+                            "{} is outside of the range {} to {}",
+                            num,
+                            range.start(),
+                            range.end()
+                        ),
+                    );
+                }
+                Some(num)
+            }
+            None => {
+                self.report(&path, "Expected an integer");
+                None
+            }
+        }
+    }
+
+    fn attribute(&mut self, value: &Value, path: &str) -> Option<Attribute> {
+        match value.as_str().map(Attribute::try_from) {
+            Some(Ok(attr)) => Some(attr),
+            Some(Err(_)) => {
+                self.report(path, format!("Unknown stat {}", value));
+                None
+            }
+            None => {
+                self.report(path, "Expected a stat name");
+                None
+            }
+        }
+    }
+
+    fn colour(&mut self, value: &Value, path: &str) {
+        match value {
+            Value::Null => (),
+            Value::Number(num) if num.as_u64().is_some_and(|num| num <= 0xFFFFFF) => (),
+            Value::String(hex) => {
+                if schema::hex_to_colour(hex).is_none() {
+                    self.report(
+                        path,
+                        format!("{} is not a colour in the format #rrggbb", hex),
+                    );
+                }
+            }
+            _ => self.report(path, "Expected a hex string or a number up to 0xFFFFFF"),
+        }
+    }
+
+    fn encounter(&mut self, encounter: &Map<String, Value>, version: &SchemaVersion) {
+        self.string(encounter, "$", "title");
+        self.string(encounter, "$", "text");
+
+        if let Some(color) = encounter.get("color") {
+            self.colour(color, "$.color");
+        }
+
+        if let Some(rarity) = encounter.get("rarity") {
+            if serde_json::from_value::<Rarity>(rarity.clone()).is_err() {
+                self.report("$.rarity", format!("Unknown rarity {}", rarity));
+            }
+        }
+
+        if let Some(difficulty) = encounter.get("difficulty") {
+            if serde_json::from_value::<Difficulty>(difficulty.clone()).is_err() {
+                self.report("$.difficulty", format!("Unknown difficulty {}", difficulty));
+            }
+        }
+
+        if encounter.get("zone").is_some_and(|zone| !zone.is_null()) {
+            self.string(encounter, "$", "zone");
+        }
+
+        let score_range = 0..=i64::from(u16::MAX);
+        let min_score = encounter
+            .get("min_score")
+            .filter(|score| !score.is_null())
+            .and_then(|_| self.integer(encounter, "$", "min_score", score_range.clone()));
+        let max_score = encounter
+            .get("max_score")
+            .filter(|score| !score.is_null())
+            .and_then(|_| self.integer(encounter, "$", "max_score", score_range));
+
+        if let (Some(min), Some(max)) = (min_score, max_score) {
+            if min > max {
+                self.warn(
+                    "$.min_score",
+                    "The minimum score is above the maximum score",
+                );
+            }
+        }
+
+        let Some(options) = self
+            .field(encounter, "$", "options")
+            .and_then(|options| self.object(options, "$.options"))
+        else {
+            return;
+        };
+
+        if options.is_empty() {
+            self.report("$.options", "An encounter needs at least one option");
+        } else if options.len() > MAX_OPTIONS {
+            // Older encounters can have a fifth option, they only lose the class option
+            self.warn(
+                "$.options",
+                format!("An encounter can have at most {} options", MAX_OPTIONS),
+            );
+        }
+
+        if let Some(chained) = encounter.get("chained") {
+            if !chained.is_boolean() {
+                self.report("$.chained", "Expected true or false");
+            }
+        }
+
+        let all_gated = options.values().all(|option| {
+            ["requires_flags", "requires_items"].into_iter().any(|key| {
+                option
+                    .get(key)
+                    .and_then(Value::as_array)
+                    .is_some_and(|names| !names.is_empty())
+            })
+        });
+        if !options.is_empty() && all_gated {
+            self.warn(
+                "$.options",
+                "At least one option should be available without any flags or items",
+            );
+        }
+
+        for (key, option) in options {
+            let path = format!("$.options.{}", key);
+            if key.is_empty() || key.len() > 80 {
+                self.report(&path, "Option names should be between 1 and 80 characters");
+            }
+            if let Some(option) = self.object(option, &path) {
+                self.option(option, &path, version);
+            }
+        }
+    }
+
+    fn option(&mut self, option: &Map<String, Value>, path: &str, version: &SchemaVersion) {
+        self.integer(option, path, "threshold", THRESHOLD_RANGE);
+
+        if let Some(stat) = self.field(option, path, "stat") {
+            self.attribute(stat, &format!("{}.stat", path));
+        }
+
+        for key in ["requires_flags", "requires_items"] {
+            if let Some(names) = option.get(key) {
+                self.names(names, &format!("{}.{}", path, key));
+            }
+        }
+
+        let (success_key, fail_key) = match version {
+            SchemaVersion::V1 => ("Success", "Fail"),
+            SchemaVersion::V2 => ("success", "fail"),
+        };
+
+        for (key, expected_kind) in [(success_key, "Success"), (fail_key, "Fail")] {
+            let result_path = format!("{}.{}", path, key);
+            let Some(result) = self
+                .field(option, path, key)
+                .and_then(|result| self.object(result, &result_path))
+            else {
+                continue;
+            };
+
+            match version {
+                SchemaVersion::V1 => self.result_v1(result, &result_path),
+                SchemaVersion::V2 => self.result_v2(result, &result_path, expected_kind),
+            }
+        }
+    }
+
+    fn result_v1(&mut self, result: &Map<String, Value>, path: &str) {
+        self.string(result, path, "type");
+        self.string(result, path, "title");
+        self.string(result, path, "text");
+
+        if let Some(effect) = result.get("baseEffect").filter(|eff| !eff.is_null()) {
+            let path = format!("{}.baseEffect", path);
+            if let Some(effect) = self.object(effect, &path) {
+                if let Some(name) = self.string(effect, &path, "name") {
+                    if !matches!(name, "Heal" | "Damage") && Attribute::try_from(name).is_err() {
+                        self.report(
+                            &format!("{}.name", path),
+                            format!("Unknown base effect {}", name),
+                        );
+                    }
+                }
+                self.integer(effect, &path, "potency", POTENCY_RANGE);
+            }
+        }
+
+        if let Some(effect) = result.get("additionalEffect").filter(|eff| !eff.is_null()) {
+            let path = format!("{}.additionalEffect", path);
+            if let Some(effect) = self.object(effect, &path) {
+                if let Some(name) = self.string(effect, &path, "name") {
+                    if !matches!(name, "Poison" | "Regenerate")
+                        && Attribute::try_from(name).is_err()
+                    {
+                        self.report(
+                            &format!("{}.name", path),
+                            format!("Unknown lingering effect {}", name),
+                        );
+                    }
+                }
+                if let Some(kind) = effect.get("type").filter(|kind| !kind.is_null()) {
+                    self.lingering_kind(kind, &format!("{}.type", path));
+                }
+                self.integer(effect, &path, "potency", 0..=*POTENCY_RANGE.end());
+                self.integer(effect, &path, "duration", DURATION_RANGE);
+            }
+        }
+    }
+
+    fn result_v2(&mut self, result: &Map<String, Value>, path: &str, expected_kind: &str) {
+        self.string(result, path, "title");
+        self.string(result, path, "text");
+
+        // Older v2 files were written with the kind under the "type" key
+        let kind_key = if result.contains_key("kind") {
+            "kind"
+        } else {
+            "type"
+        };
+
+        if let Some(kind) = self.field(result, path, kind_key) {
+            let kind_path = format!("{}.{}", path, kind_key);
+            match kind.as_object().filter(|kind| kind.len() == 1) {
+                Some(kind) => {
+                    for (name, description) in kind {
+                        if name != expected_kind {
+                            self.report(
+                                &kind_path,
+                                format!("Expected a {} result but found {}", expected_kind, name),
+                            );
+                        }
+                        if !description.is_string() {
+                            self.report(&format!("{}.{}", kind_path, name), "Expected a string");
+                        }
+                    }
+                }
+                None => self.report(
+                    &kind_path,
+                    format!("Expected an object like {{\"{}\": \"...\"}}", expected_kind),
+                ),
+            }
+        }
+
+        if let Some(effect) = result.get("base_effect").filter(|eff| !eff.is_null()) {
+            let path = format!("{}.base_effect", path);
+            self.base_effect_v2(effect, &path);
+        }
+
+        if let Some(effect) = result.get("lingering_effect").filter(|eff| !eff.is_null()) {
+            let path = format!("{}.lingering_effect", path);
+            self.lingering_effect_v2(effect, &path);
+        }
+
+        for key in ["set_flags", "clear_flags"] {
+            if let Some(flags) = result.get(key) {
+                self.names(flags, &format!("{}.{}", path, key));
+            }
+        }
+
+        if let Some(items) = result.get("items") {
+            let items_path = format!("{}.items", path);
+            match items.as_array() {
+                Some(items) => {
+                    for (index, item) in items.iter().enumerate() {
+                        if let Err(err) = serde_json::from_value::<Item>(item.clone()) {
+                            self.report(&format!("{}[{}]", items_path, index), err.to_string());
+                        }
+                    }
+                }
+                None => self.report(&items_path, "Expected a list of items"),
+            }
+        }
+
+        if let Some(target) = result.get("target") {
+            if serde_json::from_value::<ResultTarget>(target.clone()).is_err() {
+                self.report(
+                    &format!("{}.target", path),
+                    format!("Unknown target {}, expected Individual or Party", target),
+                );
+            }
+        }
+
+        if result.get("gold").is_some_and(|gold| !gold.is_null()) {
+            self.integer(result, path, "gold", GOLD_RANGE);
+        }
+
+        if result.get("shop").is_some_and(|shop| !shop.is_null()) {
+            self.string(result, path, "shop");
+        }
+
+        if result.get("enemy").is_some_and(|enemy| !enemy.is_null()) {
+            self.string(result, path, "enemy");
+        }
+
+        if let Some(next) = result.get("next").filter(|next| !next.is_null()) {
+            let next_path = format!("{}.next", path);
+            match next {
+                Value::String(reference) if !reference.is_empty() => (),
+                Value::Array(pool)
+                    if !pool.is_empty()
+                        && pool
+                            .iter()
+                            .all(|reference| reference.as_str().is_some_and(|r| !r.is_empty())) => {
+                }
+                _ => self.report(
+                    &next_path,
+                    "Expected an encounter path or a non empty list of encounter paths",
+                ),
+            }
+        }
+    }
+
+    fn names(&mut self, names: &Value, path: &str) {
+        let valid = names.as_array().is_some_and(|names| {
+            names
+                .iter()
+                .all(|name| name.as_str().is_some_and(|name| !name.is_empty()))
+        });
+        if !valid {
+            self.report(path, "Expected a list of names");
+        }
+    }
+
+    fn base_effect_v2(&mut self, effect: &Value, path: &str) {
+        let Some(effect) = self.object(effect, path) else {
+            return;
+        };
+
+        match (effect.get("Health"), effect.get("Attribute")) {
+            (Some(health), None) => {
+                let path = format!("{}.Health", path);
+                if let Some(health) = self.object(health, &path) {
+                    self.integer(health, &path, "potency", POTENCY_RANGE);
+                }
+            }
+            (None, Some(attribute)) => {
+                let path = format!("{}.Attribute", path);
+                if let Some(attribute) = self.object(attribute, &path) {
+                    if let Some(name) = self.field(attribute, &path, "name") {
+                        self.attribute(name, &format!("{}.name", path));
+                    }
+                    self.integer(attribute, &path, "potency", POTENCY_RANGE);
+                }
+            }
+            _ => self.report(
+                path,
+                "Expected exactly one of \"Health\" or \"Attribute\" effects",
+            ),
+        }
+    }
+
+    fn lingering_effect_v2(&mut self, effect: &Value, path: &str) {
+        let Some(effect) = self.object(effect, path) else {
+            return;
+        };
+
+        if let Some(kind) = self.field(effect, path, "kind") {
+            if !matches!(kind.as_str(), Some("Buff" | "Debuff")) {
+                self.report(
+                    &format!("{}.kind", path),
+                    format!("Expected Buff or Debuff but found {}", kind),
+                );
+            }
+        }
+
+        if let Some(name) = self.field(effect, path, "name") {
+            let name_path = format!("{}.name", path);
+            match name {
+                Value::String(name)
+                    if matches!(
+                        name.as_str(),
+                        "Poison" | "Regenerate" | "Shield" | "Stun" | "Thorns" | "Advantage"
+                    ) => {}
+                Value::Object(stat) if stat.len() == 1 && stat.contains_key("Stat") => {
+                    self.attribute(&stat["Stat"], &format!("{}.Stat", name_path));
+                }
+                _ => self.report(&name_path, format!("Unknown lingering effect {}", name)),
+            }
+        }
+
+        self.integer(effect, path, "potency", 0..=*POTENCY_RANGE.end());
+        self.integer(effect, path, "duration", DURATION_RANGE);
+    }
+
+    fn lingering_kind(&mut self, kind: &Value, path: &str) {
+        if kind
+            .as_str()
+            .map(LingeringEffectKind::try_from)
+            .and_then(Result::ok)
+            .is_none()
+        {
+            self.report(path, format!("Expected Buff or Debuff but found {}", kind));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn result(kind: &str) -> Value {
+        json!({
+            "kind": { kind: "" },
+            "title": format!("{} title", kind),
+            "text": format!("{} text", kind),
+            "base_effect": { "Health": { "potency": -2 } },
+        })
+    }
+
+    fn option(threshold: i64) -> Value {
+        json!({
+            "threshold": threshold,
+            "stat": "Strength",
+            "success": result("Success"),
+            "fail": result("Fail"),
+        })
+    }
+
+    fn encounter(options: usize) -> Value {
+        let options: Map<String, Value> = (0..options)
+            .map(|index| (format!("Option {}", index), option(10)))
+            .collect();
+
+        json!({
+            "version": 2,
+            "title": "A troll",
+            "text": "It blocks the bridge",
+            "color": "#20a040",
+            "options": options,
+        })
+    }
+
+    fn errors(value: &Value) -> Vec<Issue> {
+        validate(value)
+            .into_iter()
+            .filter(Issue::is_error)
+            .collect()
+    }
+
+    #[test]
+    fn valid_encounter_has_no_issues() {
+        let issues = validate(&encounter(3));
+        assert!(issues.is_empty(), "{:?}", issues);
+    }
+
+    #[test]
+    fn missing_result_is_an_error() {
+        let mut value = encounter(1);
+        value["options"]["Option 0"]
+            .as_object_mut()
+            .unwrap()
+            .remove("fail");
+
+        let errors = errors(&value);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path, "$.options.Option 0.fail");
+    }
+
+    #[test]
+    fn unknown_stat_is_an_error() {
+        let mut value = encounter(1);
+        value["options"]["Option 0"]["stat"] = json!("Luck");
+
+        assert_eq!(errors(&value).len(), 1);
+    }
+
+    #[test]
+    fn bad_colour_is_an_error() {
+        let mut value = encounter(1);
+        value["color"] = json!("green");
+
+        assert_eq!(errors(&value)[0].path, "$.color");
+    }
+
+    #[test]
+    fn balance_findings_are_only_warnings() {
+        let mut value = encounter(MAX_OPTIONS + 1);
+        value["options"]["Option 0"]["threshold"] = json!(40);
+
+        let issues = validate(&value);
+        assert_eq!(issues.len(), 2);
+        assert!(issues.iter().all(|issue| !issue.is_error()));
+    }
+
+    #[test]
+    fn invalid_json_is_an_error() {
+        let issues = lint(b"{ \"title\": ");
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].is_error());
+    }
+
+    #[test]
+    fn v1_encounter_is_validated_with_its_own_keys() {
+        let value = json!({
+            "title": "A troll",
+            "text": "It blocks the bridge",
+            "options": {
+                "Fight": {
+                    "threshold": 10,
+                    "stat": "Strength",
+                    "Success": { "type": "You won", "title": "Won", "text": "It flees" },
+                    "Fail": {
+                        "type": "You lost",
+                        "title": "Lost",
+                        "text": "It hits back",
+                        "baseEffect": { "name": "Damage", "potency": 3 },
+                    },
+                },
+            },
+        });
+
+        let issues = validate(&value);
+        assert!(issues.is_empty(), "{:?}", issues);
+    }
+}
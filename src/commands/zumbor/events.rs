@@ -0,0 +1,338 @@
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serenity::{builder::CreateEmbed, prelude::Context};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::storage::StorageClient;
+
+use super::{
+    achievements::Achievement,
+    combat::CombatOutcome,
+    effects::{EffectEvent, LingeringEffectName},
+    player::{stats::Stats, RollResult},
+};
+
+pub type EventSender = UnboundedSender<GameEvent>;
+
+const SCOREBOARD_PATH: &str = "zumbor/leaderboards/scores";
+
+/// How many of the best runs the scoreboard keeps
+const SCOREBOARD_SIZE: usize = 10;
+
+/// Everything that happens to players during a run that listeners might care about
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum GameEvent {
+    /// An option was chosen for the player who will attempt it, before it is rolled for
+    ChoiceMade {
+        tag: String,
+        name: String,
+        encounter: String,
+        choice: String,
+        stunned: bool,
+    },
+    /// Spectators reacted to the encounter and lifted the attempter's morale
+    Cheered {
+        name: String,
+        spectators: Vec<String>,
+    },
+    Rolled {
+        name: String,
+        roll: RollResult,
+    },
+    Effect(EffectEvent),
+    HealthChanged {
+        target: String,
+        from: i16,
+        to: i16,
+    },
+    EncounterResolved {
+        tag: String,
+        name: String,
+        encounter: String,
+        succeeded: bool,
+        critical: bool,
+        /// Whether the attempter was still standing once the result and their effects played out
+        #[serde(default)]
+        survived: bool,
+    },
+    /// A fight with an enemy ended, however it ended
+    Fought {
+        tag: String,
+        name: String,
+        enemy: String,
+        outcome: CombatOutcome,
+        rounds: u16,
+    },
+    LevelUp {
+        tag: String,
+        name: String,
+        level: u8,
+    },
+    /// A player's stats after spending their points, not counting their class
+    StatsAllocated {
+        tag: String,
+        name: String,
+        level: u8,
+        stats: Stats,
+        /// The highest any stat can go at the player's level under their guild's rules
+        #[serde(default)]
+        max_stat: i16,
+    },
+    /// A lingering effect's tick took the last of a player's health
+    Succumbed {
+        tag: String,
+        name: String,
+        effect: LingeringEffectName,
+    },
+    Death {
+        tag: String,
+        name: String,
+        score: u16,
+    },
+    AchievementUnlocked {
+        tag: String,
+        name: String,
+        achievement: Achievement,
+    },
+}
+
+impl From<EffectEvent> for GameEvent {
+    fn from(event: EffectEvent) -> Self {
+        GameEvent::Effect(event)
+    }
+}
+
+/**
+ * Receives every event published on the bus, listeners are called in the order they subscribed
+ */
+pub trait EventListener: Send {
+    fn handle<'a>(&'a mut self, ctx: &'a Context, event: &'a GameEvent) -> BoxFuture<'a, ()>;
+}
+
+pub struct EventBus {
+    sender: EventSender,
+    receiver: UnboundedReceiver<GameEvent>,
+    listeners: Vec<Box<dyn EventListener>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        EventBus {
+            sender,
+            receiver,
+            listeners: Vec::new(),
+        }
+    }
+
+    /// Gives the standard listeners every run has
+    pub fn with_defaults() -> Self {
+        let mut bus = EventBus::new();
+        bus.subscribe(Box::new(LogListener));
+        bus.subscribe(Box::new(ScoreboardListener));
+        bus
+    }
+
+    pub fn sender(&self) -> EventSender {
+        self.sender.clone()
+    }
+
+    pub fn publish(&self, event: GameEvent) {
+        // The bus holds a receiver for as long as it lives so this can't fail
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&mut self, listener: Box<dyn EventListener>) {
+        self.listeners.push(listener);
+    }
+
+    /**
+     * Hands every event published so far to the subscribed listeners and then to the ui,
+     * which lives outside the bus since the game loop needs it too
+     */
+    pub async fn dispatch(&mut self, ctx: &Context, ui: &mut dyn EventListener) {
+        while let Ok(event) = self.receiver.try_recv() {
+            for listener in self.listeners.iter_mut() {
+                listener.handle(ctx, &event).await;
+            }
+            ui.handle(ctx, &event).await;
+        }
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GameEvent {
+    /// How the event is shown to players, if at all
+    pub fn embed(&self) -> Option<CreateEmbed> {
+        match self {
+            GameEvent::Effect(event) => Some(event.into()),
+            GameEvent::Death { name, score, .. } => Some(
+                CreateEmbed::new()
+                    .title(format!("Uh oh {} died", name))
+                    .description(format!("They finished with a score of {}", score)),
+            ),
+            GameEvent::AchievementUnlocked {
+                name, achievement, ..
+            } => Some(
+                CreateEmbed::new()
+                    .title(format!("{} unlocked {}", name, achievement))
+                    .description(match achievement.title() {
+                        Some(title) => format!(
+                            "{}\nThey can now go by {} {}",
+                            achievement.description(),
+                            name,
+                            title
+                        ),
+                        None => achievement.description(),
+                    }),
+            ),
+            GameEvent::Cheered { name, spectators } => Some(
+                CreateEmbed::new()
+                    .title(format!("The crowd cheers {} on", name))
+                    .description(format!("Thanks to {}", spectators.join(", "))),
+            ),
+            // Level ups are shown by the prompt to allocate the new stat points
+            GameEvent::LevelUp { .. }
+            | GameEvent::ChoiceMade { .. }
+            | GameEvent::Rolled { .. }
+            | GameEvent::Fought { .. }
+            | GameEvent::StatsAllocated { .. }
+            | GameEvent::HealthChanged { .. }
+            | GameEvent::Succumbed { .. }
+            | GameEvent::EncounterResolved { .. } => None,
+        }
+    }
+
+    /// A line for the event in a replay's timeline, if it is worth one
+    pub fn describe(&self) -> Option<String> {
+        match self {
+            GameEvent::ChoiceMade {
+                name,
+                choice,
+                stunned: true,
+                ..
+            } => Some(format!("{} is stunned and stumbles into {}", name, choice)),
+            GameEvent::ChoiceMade { name, choice, .. } => {
+                Some(format!("{} chose to {}", name, choice))
+            }
+            GameEvent::Cheered { name, spectators } => {
+                Some(format!("{} cheered {} on", spectators.join(", "), name))
+            }
+            GameEvent::Rolled { name, roll } => Some(format!("{} rolled {}", name, roll)),
+            GameEvent::Effect(event) => Some(event.to_string()),
+            GameEvent::HealthChanged { target, from, to } if from != to => {
+                Some(format!("{}'s health went from {} to {}", target, from, to))
+            }
+            GameEvent::EncounterResolved {
+                succeeded,
+                critical,
+                ..
+            } => Some(
+                match (succeeded, critical) {
+                    (true, true) => "A critical success!",
+                    (true, false) => "Success",
+                    (false, true) => "A critical failure!",
+                    (false, false) => "Failure",
+                }
+                .to_string(),
+            ),
+            GameEvent::Fought {
+                name,
+                enemy,
+                outcome,
+                rounds,
+                ..
+            } => Some(format!(
+                "{} fought {} and {} after {} rounds",
+                name, enemy, outcome, rounds
+            )),
+            GameEvent::LevelUp { name, level, .. } => {
+                Some(format!("{} reached level {}", name, level))
+            }
+            GameEvent::Death { name, score, .. } => {
+                Some(format!("{} died with a score of {}", name, score))
+            }
+            GameEvent::AchievementUnlocked {
+                name, achievement, ..
+            } => Some(format!("{} unlocked {}", name, achievement)),
+            // The tick that led to it has already been told
+            GameEvent::Succumbed { .. } => None,
+            GameEvent::HealthChanged { .. } | GameEvent::StatsAllocated { .. } => None,
+        }
+    }
+}
+
+pub struct LogListener;
+
+impl EventListener for LogListener {
+    fn handle<'a>(&'a mut self, _ctx: &'a Context, event: &'a GameEvent) -> BoxFuture<'a, ()> {
+        Box::pin(async move { println!("{:?}", event) })
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct Scoreboard {
+    pub entries: Vec<ScoreboardEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ScoreboardEntry {
+    pub tag: String,
+    pub name: String,
+    pub score: u16,
+}
+
+/// Records the score of every character that dies if it makes the top of the scoreboard
+pub struct ScoreboardListener;
+
+impl EventListener for ScoreboardListener {
+    fn handle<'a>(&'a mut self, ctx: &'a Context, event: &'a GameEvent) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            let GameEvent::Death { tag, name, score } = event else {
+                return;
+            };
+
+            let data = ctx.data.read().await;
+            let Some(storage_client) = data.get::<StorageClient>() else {
+                return;
+            };
+
+            let mut scoreboard: Scoreboard = match storage_client.get_json(SCOREBOARD_PATH).await {
+                Ok(scoreboard) => scoreboard,
+                Err(err) if err.is_not_found() => Scoreboard::default(),
+                Err(err) => {
+                    println!("Unable to load the scoreboard. {}", err);
+                    return;
+                }
+            };
+
+            scoreboard.entries.push(ScoreboardEntry {
+                tag: tag.clone(),
+                name: name.clone(),
+                score: *score,
+            });
+            scoreboard
+                .entries
+                .sort_by_key(|entry| std::cmp::Reverse(entry.score));
+            scoreboard.entries.truncate(SCOREBOARD_SIZE);
+
+            let res = match serde_json::to_string(&scoreboard) {
+                Ok(json) => {
+                    storage_client
+                        .create_json(&format!("{}.json", SCOREBOARD_PATH), json)
+                        .await
+                }
+                Err(err) => Err(err.into()),
+            };
+            if let Err(err) = res {
+                println!("Unable to update the scoreboard. {}", err);
+            }
+        })
+    }
+}
@@ -0,0 +1,255 @@
+use std::{collections::HashMap, ops::RangeInclusive};
+
+use kalosm::language::{Llama, ModelExt, Parse};
+use serenity::{framework::standard::Args, model::prelude::Message, prelude::Context};
+
+use crate::{
+    commands::chat::ChatBot, errors::Error, storage::StorageClient, utilities::permissions,
+};
+
+use super::{
+    attributes::Attribute,
+    authoring,
+    effects::{BaseEffect, BaseHealthEffect},
+    encounter::{
+        schema, validation, Difficulty, Encounter, EncounterOption, EncounterResult,
+        EncounterResultKind, Rarity,
+    },
+};
+
+/// The most encounters generated by a single command, generation is slow
+const MAX_GENERATED: usize = 5;
+
+/// Attempts at each encounter before giving up on it
+const ATTEMPTS: usize = 3;
+
+/// Thresholds a typical character has a fair chance at, tighter than validation allows
+const THRESHOLD_BOUNDS: RangeInclusive<i64> = 6..=18;
+const SUCCESS_HEALTH_BOUNDS: RangeInclusive<i64> = 0..=4;
+const FAIL_HEALTH_BOUNDS: RangeInclusive<i64> = -6..=0;
+
+const PROMPT: &str = "You write encounters for Zumbor, a text adventure played on discord. \
+An encounter is a short scene the player stumbles into, with three options for dealing with it. \
+Each option is tested against one of the player's stats, Charisma, Strength, Wisdom or Agility, \
+and needs a roll of at least its threshold, between 6 and 18, to succeed. \
+Results say what happens in a sentence or two and change the player's health, \
+from 0 to 4 for a success and from -6 to 0 for a failure. \
+Write one new encounter, in a dark and slightly silly tone.";
+
+/**
+ * The shape the model is held to while it writes, simple enough for it to fill in reliably.
+ * It only becomes an encounter once the numbers have been brought within balance
+ */
+#[derive(Parse, Clone, Debug)]
+struct GeneratedEncounter {
+    title: String,
+    text: String,
+    options: [GeneratedOption; 3],
+}
+
+#[derive(Parse, Clone, Debug)]
+struct GeneratedOption {
+    name: String,
+    stat: GeneratedStat,
+    threshold: i64,
+    success: GeneratedResult,
+    fail: GeneratedResult,
+}
+
+#[derive(Parse, Clone, Debug)]
+enum GeneratedStat {
+    Charisma,
+    Strength,
+    Wisdom,
+    Agility,
+}
+
+#[derive(Parse, Clone, Debug)]
+struct GeneratedResult {
+    title: String,
+    text: String,
+    health: i64,
+}
+
+impl From<GeneratedStat> for Attribute {
+    fn from(stat: GeneratedStat) -> Self {
+        match stat {
+            GeneratedStat::Charisma => Attribute::Charisma,
+            GeneratedStat::Strength => Attribute::Strength,
+            GeneratedStat::Wisdom => Attribute::Wisdom,
+            GeneratedStat::Agility => Attribute::Agility,
+        }
+    }
+}
+
+impl GeneratedResult {
+    fn into_result(
+        self,
+        kind: EncounterResultKind,
+        bounds: RangeInclusive<i64>,
+    ) -> EncounterResult {
+        let health = self.health.clamp(*bounds.start(), *bounds.end()) as i16;
+
+        EncounterResult {
+            kind,
+            title: self.title,
+            text: self.text,
+            base_effect: (health != 0)
+                .then_some(BaseEffect::Health(BaseHealthEffect { potency: health })),
+            lingering_effect: None,
+            next: None,
+            set_flags: Default::default(),
+            clear_flags: Default::default(),
+            items: Vec::new(),
+            target: Default::default(),
+            gold: 0,
+            shop: None,
+            enemy: None,
+        }
+    }
+}
+
+impl From<GeneratedEncounter> for Encounter {
+    fn from(generated: GeneratedEncounter) -> Self {
+        let options: HashMap<String, EncounterOption> = generated
+            .options
+            .into_iter()
+            .map(|option| {
+                let threshold = option
+                    .threshold
+                    .clamp(*THRESHOLD_BOUNDS.start(), *THRESHOLD_BOUNDS.end())
+                    as u8;
+
+                (
+                    option.name,
+                    EncounterOption {
+                        threshold,
+                        stat: option.stat.into(),
+                        success: option.success.into_result(
+                            EncounterResultKind::Success("Success".to_string()),
+                            SUCCESS_HEALTH_BOUNDS,
+                        ),
+                        fail: option.fail.into_result(
+                            EncounterResultKind::Fail("Fail".to_string()),
+                            FAIL_HEALTH_BOUNDS,
+                        ),
+                        requires_flags: Default::default(),
+                        requires_items: Default::default(),
+                    },
+                )
+            })
+            .collect();
+
+        Encounter {
+            version: schema::CURRENT_VERSION,
+            title: generated.title,
+            text: generated.text,
+            color: None,
+            options,
+            rarity: Rarity::Common,
+            difficulty: Difficulty::Normal,
+            min_score: None,
+            max_score: None,
+            chained: false,
+            zone: None,
+        }
+    }
+}
+
+/**
+ * Has the model write an encounter, trying again if what it wrote doesn't pass validation.
+ * Options sharing a name collapse into one, so those are thrown out too
+ */
+async fn write_encounter(model: &Llama) -> Result<Encounter, Error> {
+    for _ in 0..ATTEMPTS {
+        let stream = model
+            .stream_structured_text(PROMPT, GeneratedEncounter::new_parser())
+            .await
+            .map_err(|err| {
+                println!("{}", err);
+                Error::Plain("Failed to start generating an encounter")
+            })?;
+
+        let generated = match stream.result().await {
+            Ok(generated) => generated,
+            Err(err) => {
+                println!("Generation failed. {}", err);
+                continue;
+            }
+        };
+
+        let encounter = Encounter::from(generated);
+        let issues = validation::validate(&serde_json::to_value(&encounter)?);
+
+        if encounter.options.len() < 3 {
+            println!("Generated {} with repeated options", encounter.title);
+        } else if !issues.is_empty() {
+            println!("Generated {} with {} issues", encounter.title, issues.len());
+        } else {
+            return Ok(encounter);
+        }
+    }
+
+    Err(Error::Plain("The model couldn't write a valid encounter"))
+}
+
+/**
+ * Generates encounters into the review queue, `encounter generate [count]`
+ */
+pub async fn generate(ctx: &Context, msg: &Message, mut args: Args) -> Result<(), Error> {
+    if !permissions::is_admin(ctx, msg).await {
+        msg.reply(ctx, "Only admins can make the machine write encounters")
+            .await?;
+        return Err(Error::Plain("Encounters generated by a non admin"));
+    }
+
+    let count = args.single::<usize>().unwrap_or(1).clamp(1, MAX_GENERATED);
+
+    // The model is cloned out so the data lock isn't held for the whole of the generation
+    let model = {
+        let data = ctx.data.read().await;
+        data.get::<ChatBot>()
+            .map(|chatbot| chatbot.model.clone())
+            .ok_or(Error::Plain("Chat model not accessible!"))?
+    };
+
+    msg.reply(
+        ctx,
+        format!("Writing {} encounters, this takes a while", count),
+    )
+    .await?;
+
+    let mut queued = Vec::new();
+    for _ in 0..count {
+        let encounter = match write_encounter(&model).await {
+            Ok(encounter) => encounter,
+            Err(err) => {
+                println!("{}", err);
+                continue;
+            }
+        };
+
+        let data = ctx.data.read().await;
+        let storage_client = data
+            .get::<StorageClient>()
+            .ok_or(Error::Plain("Storage client not accessible!"))?;
+
+        authoring::submit(storage_client, &encounter, "the chat model").await?;
+        queued.push(encounter.title);
+    }
+
+    msg.reply(
+        ctx,
+        if queued.is_empty() {
+            "Nothing worth keeping was written".to_string()
+        } else {
+            format!(
+                "Queued {} for review with `zumbor encounter review`",
+                queued.join(", ")
+            )
+        },
+    )
+    .await?;
+
+    Ok(())
+}
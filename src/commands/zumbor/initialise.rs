@@ -1,182 +1,566 @@
+use rand::{rngs::StdRng, SeedableRng};
 use serenity::{
-    all::CreateMessage,
+    all::{CreateMessage, GuildId},
     builder::CreateEmbed,
     model::{
-        prelude::{ChannelId, Message, UserId},
+        prelude::{ChannelId, Message},
         user::User,
     },
     prelude::Context,
 };
+use tokio_util::sync::CancellationToken;
 
-use crate::{commands::zumbor::ZumborInstances, errors::Error};
+use crate::errors::Error;
 
 use super::{
-    effects::Effectable,
-    encounter::{self, Encounter},
-    player::{self, RollResult},
-    ui::{ContinueOption, UI},
+    achievements::{self, AchievementListener},
+    combat::{self, Combat, Enemy},
+    daily,
+    effects::{Effectable, LingeringEffect, LingeringEffectKind, LingeringEffectName},
+    encounter::{self, Encounter, ResultTarget},
+    events::{EventBus, GameEvent, LogListener},
+    party::{Party, Resolution},
+    player::{self, Player, RollResult},
+    replay::RunRecorder,
+    rules::{self, Ruleset},
+    session::{self, SessionKind},
+    shop,
+    ui::{ContinueOption, Destination, UI},
+    zone::WILDS,
 };
 
+/// The most a crowd of spectators can raise a stat for a single roll
+const MAX_MORALE: i16 = 2;
+
 pub async fn start(ctx: &Context, msg: &Message) -> Result<bool, Error> {
     let user: &User = &msg.author;
     let channel_id = msg.channel_id;
-
-    if let Err(err) = add_user_instance(ctx, user.id).await {
-        nice_message(
-            ctx,
-            channel_id,
-            "You Fail!".to_string(),
-            "You already have a running instance of Zumbor you fool!".to_string(),
-        )
-        .await
-        .expect("To send a message");
-        return Err(err);
+    let rules = rules::for_guild(ctx, msg.guild_id).await;
+
+    let session = match session::claim(
+        ctx,
+        user.id,
+        SessionKind::Adventure,
+        channel_id,
+        CancellationToken::new(),
+    )
+    .await
+    {
+        Ok(session) => session,
+        Err(err) => {
+            nice_message(
+                ctx,
+                channel_id,
+                "You Fail!".to_string(),
+                "You already have a running instance of Zumbor you fool!".to_string(),
+            )
+            .await?;
+            return Err(err);
+        }
     };
 
-    let mut player = if let Ok(player) = player::storage::load_save(ctx, &user.tag()).await {
-        player
-    } else {
-        player::create(ctx, user.tag().into(), channel_id).await?
+    // Only a missing save makes a new character, anything else would overwrite one that exists
+    let player = match player::storage::load_save(ctx, &user.tag()).await {
+        Ok(mut player) => {
+            if player.resume_encounter() {
+                nice_message(
+                    ctx,
+                    channel_id,
+                    "Welcome back".to_string(),
+                    format!("{} picks up where they left off", player.name),
+                )
+                .await?;
+            }
+            player
+        }
+        Err(err) if err.is_not_found() => {
+            session
+                .until_killed(player::create(ctx, user.tag().into(), channel_id, &rules))
+                .await?
+        }
+        Err(err) => {
+            nice_message(
+                ctx,
+                channel_id,
+                "Ruh Roh Wraggy...".to_string(),
+                "Your save couldn't be loaded, it has been left as it is".to_string(),
+            )
+            .await?;
+            return Err(err);
+        }
     };
 
-    let mut ui = UI::builder().context(ctx).channel(channel_id).build();
+    run(
+        ctx,
+        channel_id,
+        Party::solo(session, player),
+        RunMode::Adventure,
+        rules,
+    )
+    .await
+}
 
-    loop {
-        let mut encounter: Encounter = encounter::get_random(ctx).await?;
+/// What kind of run is being played, deciding where its randomness comes from and what happens to its characters
+pub enum RunMode {
+    /// Characters come from and go back to their saves
+    Adventure,
+    /// A single attempt at the guild's challenge for the day with a character that is never saved
+    Daily { guild_id: GuildId, day: u64 },
+}
 
-        let (player_choice, current_message) = ui.encounter_details(&encounter, &player).await?;
+impl RunMode {
+    fn rng(&self) -> StdRng {
+        match self {
+            RunMode::Adventure => StdRng::from_entropy(),
+            RunMode::Daily { guild_id, day } => daily::rng(*guild_id, *day),
+        }
+    }
 
-        let encounter_option = encounter
-            .get_option(&player_choice)
-            .expect("Player choice should be limited to encounter option keys");
+    /// Picks the encounter at the given point in the run, kept apart from the dice
+    fn selection_rng(&self, index: u64) -> StdRng {
+        match self {
+            RunMode::Adventure => StdRng::from_entropy(),
+            RunMode::Daily { guild_id, day } => daily::selection_rng(*guild_id, *day, index),
+        }
+    }
 
-        let player_roll = player.roll_stat(&encounter_option.stat);
+    /// The score encounters are picked for, challengers all go by how far into the run they are
+    fn selection_score(&self, player: &Player, index: u64) -> u16 {
+        match self {
+            RunMode::Adventure => player.score,
+            RunMode::Daily { .. } => u16::try_from(index).unwrap_or(u16::MAX),
+        }
+    }
 
-        let encounter_result = encounter_option.test(&player_roll);
+    fn events(&self) -> EventBus {
+        match self {
+            RunMode::Adventure => EventBus::with_defaults(),
+            // Challenge characters don't belong on the all time scoreboard
+            RunMode::Daily { .. } => {
+                let mut bus = EventBus::new();
+                bus.subscribe(Box::new(LogListener));
+                bus
+            }
+        }
+    }
 
-        // Handle base effect of the result
-        if let Some(effect) = &mut encounter_result.base_effect {
-            match player_roll {
-                RollResult::CriticalFail | RollResult::CriticalSuccess => {
-                    effect.set_potency(effect.get_potency() * 2);
-                }
-                _ => (),
-            };
-            player.affect(effect)
+    /**
+     * Settles a character that has left the run, either through death or by resting
+     */
+    async fn finish(&self, ctx: &Context, player: &Player, died: bool) -> Result<(), Error> {
+        match self {
+            RunMode::Adventure if died => player.delete_save(ctx).await,
+            RunMode::Adventure => player.save(ctx).await,
+            RunMode::Daily { guild_id, day } => {
+                daily::record(ctx, *guild_id, *day, player, true).await
+            }
+        }
+    }
+
+    /**
+     * Keeps a record of a character still in the run, so nothing is lost if the run is interrupted
+     */
+    async fn autosave(&self, ctx: &Context, player: &Player) {
+        let res = match self {
+            RunMode::Adventure => player.save(ctx).await,
+            RunMode::Daily { guild_id, day } => {
+                daily::record(ctx, *guild_id, *day, player, false).await
+            }
+        };
+
+        if let Err(err) = res {
+            println!("Unable to autosave {}. {}", player.name, err);
         }
+    }
+}
 
-        // Handle lingering effects of the result
-        if let Some(effect) = &encounter_result.lingering_effect {
-            println!("Added lingering effect: {}", effect.name);
-            let gain_embed: CreateEmbed = effect.into();
+/**
+ * Plays the run until it ends or is killed, every member's session ends with it however it stops
+ */
+pub async fn run(
+    ctx: &Context,
+    channel_id: ChannelId,
+    party: Party,
+    mode: RunMode,
+    rules: Ruleset,
+) -> Result<bool, Error> {
+    // Members share a token so any of their sessions will do
+    let token = party.members[0].session.token();
+
+    session::until_killed(token, play(ctx, channel_id, party, mode, rules)).await
+}
 
-            ui.queue_message(
-                gain_embed.title(format!("Received a {} {}", effect.name, effect.kind)),
-            );
+/**
+ * Plays encounters with the party until everyone has died or the host decides to rest
+ */
+async fn play(
+    ctx: &Context,
+    channel_id: ChannelId,
+    mut party: Party,
+    mode: RunMode,
+    rules: Ruleset,
+) -> Result<bool, Error> {
+    let mut ui = UI::builder()
+        .context(ctx)
+        .channel(channel_id)
+        .rules(rules.clone())
+        .build();
+    let mut rng = mode.rng();
+
+    let mut events = mode.events();
+    events.subscribe(Box::new(AchievementListener::new(events.sender())));
+    let recorder = RunRecorder::new(channel_id, &party);
+    events.subscribe(Box::new(recorder.clone()));
+    for member in party.members.iter_mut() {
+        member.player.events = Some(events.sender());
+        member.player.title = achievements::title_for(ctx, &member.player.tag).await;
+        member.player.rules = rules.clone();
+    }
 
-            player.add_effect(effect.clone())
+    for index in 0.. {
+        let mut selection_rng = mode.selection_rng(index);
+        let score = mode.selection_score(party.actor(), index);
+        let (encounter_path, mut encounter): (String, Encounter) =
+            encounter::get_next(ctx, party.actor_mut(), score, &mut selection_rng).await?;
+        for member in party.members.iter_mut() {
+            member.player.remember_encounter(encounter_path.clone());
+            member.player.current_encounter = Some(encounter_path.clone());
+            mode.autosave(ctx, &member.player).await;
         }
+        encounter.offer_class_option(party.actor(), &mut rng);
+
+        let stunned = party.deciders().is_empty();
+        let (party_choice, current_message) =
+            ui.encounter_details(&encounter, &party, &mut rng).await?;
+
+        // Stuns only last until the choice they took away
+        for member in party.members.iter_mut() {
+            member.player.consume_effect(&LingeringEffectName::Stun);
+        }
+
+        let encounter_title = encounter.title.clone();
+        let encounter_option = encounter
+            .get_option(&party_choice)
+            .expect("Party choice should be limited to encounter option keys");
+
+        let attempter = party.attempter(encounter_option);
+        events.publish(GameEvent::ChoiceMade {
+            tag: party.members[attempter].player.tag.clone(),
+            name: party.members[attempter].player.name.clone(),
+            encounter: encounter_title.clone(),
+            choice: party_choice.clone(),
+            stunned,
+        });
+
+        // Cheering lifts the stat being rolled, just for this roll
+        let spectators = ui.spectators(&current_message, &party).await;
+        if !spectators.is_empty() {
+            let player = &mut party.members[attempter].player;
+            let potency = i16::try_from(spectators.len())
+                .unwrap_or(MAX_MORALE)
+                .min(MAX_MORALE);
+
+            events.publish(GameEvent::Cheered {
+                name: player.name.clone(),
+                spectators,
+            });
+            player.add_effect(LingeringEffect {
+                kind: LingeringEffectKind::Buff,
+                name: LingeringEffectName::Stat(encounter_option.stat.clone()),
+                potency,
+                duration: 1,
+            });
+        }
+
+        let player_roll = party.members[attempter]
+            .player
+            .roll_stat(&encounter_option.stat, &mut rng);
+        events.publish(GameEvent::Rolled {
+            name: party.members[attempter].player.name.clone(),
+            roll: player_roll.clone(),
+        });
+        let resolved = encounter::resolve_result(
+            &mut party.members[attempter].player,
+            encounter_option,
+            &player_roll,
+            &mut selection_rng,
+        );
+        let encounter_result = &resolved.result;
+        let succeeded = resolved.succeeded;
+
+        let shop_id = encounter_result.shop.clone();
+        let enemy_id = encounter_result.enemy.clone();
+        let attempter_tag = party.members[attempter].player.tag.clone();
+
+        let targets: Vec<usize> = match encounter_result.target {
+            ResultTarget::Individual => vec![attempter],
+            ResultTarget::Party => (0..party.members.len()).collect(),
+        };
 
-        for effect in player.get_effects() {
-            if effect.duration == 1 {
-                ui.queue_message(CreateEmbed::new().title(format!(
-                    "A potency {} {} {} has expired",
-                    effect.potency, effect.name, effect.kind
-                )));
+        for &target in &targets {
+            // The attempter's share of the result was landed as it was resolved
+            if target != attempter {
+                encounter_result.land_on(&mut party.members[target].player, &mut selection_rng);
+            }
+
+            if let Some(effect) = &encounter_result.lingering_effect {
+                println!("Added lingering effect: {}", effect.name);
+            }
+        }
+
+        // Only the chain from the attempter's result carries on, whoever acts next follows it
+        let next_encounter = party.members[attempter].player.next_encounter.take();
+        for member in party.members.iter_mut() {
+            member.player.next_encounter = None;
+        }
+
+        for (index, member) in party.members.iter_mut().enumerate() {
+            if index != attempter {
+                member.player.end_turn();
             }
         }
 
-        player.apply_effects();
-        player.add_score(1);
+        events.publish(GameEvent::EncounterResolved {
+            tag: party.members[attempter].player.tag.clone(),
+            name: party.members[attempter].player.name.clone(),
+            encounter: encounter_title,
+            succeeded,
+            critical: !matches!(player_roll, RollResult::Value(_)),
+            survived: party.members[attempter].player.health > 0,
+        });
+        events.dispatch(ctx, &mut ui).await;
+
+        let attempter_name = &party.members[attempter].player.name;
+        let summary = if stunned {
+            CreateEmbed::new().title(format!(
+                "{} is stunned and stumbles into {}",
+                attempter_name, party_choice
+            ))
+        } else if party.is_solo() || party.resolution == Resolution::Actor {
+            CreateEmbed::new().title(format!("{} chose to {}", attempter_name, party_choice))
+        } else {
+            CreateEmbed::new()
+                .title(format!("The party chose to {}", party_choice))
+                .description(format!("{} takes it on", attempter_name))
+        };
 
         if let Err(err) = ui
-            .encounter_result(encounter_result, &player, current_message)
+            .encounter_result(encounter_result, summary, current_message)
             .await
         {
             println!("Unable to display the encounter result. {}", err);
         }
 
-        if player.health <= 0 {
-            player.effects.clear();
+        // Only an attempter left standing by the result has to fight
+        if let (Some(enemy_id), Some(member)) = (
+            &enemy_id,
+            party
+                .members
+                .iter_mut()
+                .find(|member| member.player.tag == attempter_tag && member.player.health > 0),
+        ) {
+            let res = match combat::load(ctx, enemy_id).await {
+                Ok(template) => {
+                    let mut combat = Combat::new(Enemy::new(template));
+                    ui.combat(&mut member.player, &mut combat, &mut rng)
+                        .await
+                        .map(|outcome| (combat, outcome))
+                }
+                Err(err) => Err(err),
+            };
 
-            ui.say(format!("Uh oh {} died", &player.name).as_ref())
-                .await;
+            match res {
+                Ok((combat, outcome)) => {
+                    if let Some(reward) = combat.reward(&mut member.player) {
+                        ui.queue_message(CreateEmbed::new().title(reward));
+                    }
+                    events.publish(GameEvent::Fought {
+                        tag: member.player.tag.clone(),
+                        name: member.player.name.clone(),
+                        enemy: combat.enemy.template.name.clone(),
+                        outcome,
+                        rounds: combat.round,
+                    });
+                }
+                Err(err) => println!("Unable to run the fight with {}. {}", enemy_id, err),
+            }
+        }
 
-            if let Err(err) = player.delete_save(ctx).await {
-                println!("Unable to delete the player save. {}", err);
+        for mut member in party.remove_dead() {
+            member.player.effects.clear();
+
+            events.publish(GameEvent::Death {
+                tag: member.player.tag.clone(),
+                name: member.player.name.clone(),
+                score: member.player.score,
+            });
+
+            if let Err(err) = mode.finish(ctx, &member.player, true).await {
+                println!(
+                    "Unable to settle {} after their death. {}",
+                    member.player.name, err
+                );
             };
+        }
 
-            // match scoreboard.update(player).await {
-            //     Ok(did_win) => match did_win {
-            //         true => ui.say("You win, you winner!"),
-            //         false => ui.say("You lose, loser!"),
-            //     },
-            //     Err(err) => {
-            //         ui.say("Fetching scoreboard failed!");
-            //         println!("{}", err)
-            //     }
-            // };
-
-            remove_user_instance(ctx, user.id).await;
+        events.dispatch(ctx, &mut ui).await;
+        if ui.has_queued_messages() {
+            if let Err(err) = ui.send_messages().await {
+                println!("Unable to send the queued messages. {}", err);
+            }
+        }
+
+        if let Err(err) = recorder.save(ctx).await {
+            println!("Unable to save the run log. {}", err);
+        }
+
+        if party.is_empty() {
+            ui.say(&replay_message(&recorder)).await;
             return Ok(true);
         }
 
-        let resume_playing: ContinueOption = ui.request_continue(&player).await?;
+        let experience = resolved.experience;
+        let target_tags: Vec<String> = targets
+            .iter()
+            .filter_map(|&target| party.members.get(target))
+            .map(|member| member.player.tag.clone())
+            .collect();
+
+        for member in party.members.iter_mut() {
+            if !target_tags.contains(&member.player.tag) {
+                continue;
+            }
+
+            let levels = member.player.gain_experience(experience);
+            // A member who misses the prompt keeps their points for next time, the run goes on
+            if levels > 0 {
+                if let Err(err) =
+                    player::level_up(ctx, channel_id, &mut member.player, levels).await
+                {
+                    println!("{} left their points unspent. {}", member.player.name, err);
+                }
+            }
+        }
+
+        // The attempter may not have survived to do their shopping
+        if let (Some(shop_id), Some(member)) = (
+            &shop_id,
+            party
+                .members
+                .iter_mut()
+                .find(|member| member.player.tag == attempter_tag),
+        ) {
+            let res = match shop::load(ctx, shop_id).await {
+                Ok(shop) => ui.shop(&mut member.player, &shop).await,
+                Err(err) => Err(err),
+            };
+            if let Err(err) = res {
+                println!("Unable to run shop {}. {}", shop_id, err);
+            }
+        }
+
+        party.advance(next_encounter);
+
+        for member in party.members.iter_mut() {
+            member.player.current_encounter = None;
+            mode.autosave(ctx, &member.player).await;
+        }
+
+        let catalogue = encounter::catalogue::get(ctx).await?;
+        let can_travel = !catalogue.zones.is_empty();
+        let (mut resume_playing, mut chosen_by) = ui.request_continue(&party, can_travel).await?;
+
+        loop {
+            match resume_playing {
+                ContinueOption::Inventory => {
+                    if let Some(member) = party
+                        .members
+                        .iter_mut()
+                        .find(|member| member.player.tag == chosen_by)
+                    {
+                        ui.inventory(&mut member.player).await?;
+                    }
+                }
+                ContinueOption::Travel => match ui.travel(&party, &catalogue.zones).await? {
+                    Destination::Stay => (),
+                    destination => {
+                        let id = match destination {
+                            Destination::Zone(id) => Some(id),
+                            _ => None,
+                        };
+                        let zone = id.as_ref().and_then(|id| catalogue.zones.get(id));
+
+                        for member in party.members.iter_mut() {
+                            member.player.travel(id.clone(), zone);
+                            mode.autosave(ctx, &member.player).await;
+                        }
+
+                        ui.queue_message(match zone {
+                            Some(zone) => zone.into(),
+                            None => CreateEmbed::new()
+                                .title(format!("Travelling to {}", WILDS))
+                                .description("Off the beaten track"),
+                        });
+                        events.dispatch(ctx, &mut ui).await;
+                        ui.send_messages().await?;
+                    }
+                },
+                _ => break,
+            }
+
+            (resume_playing, chosen_by) = ui.request_continue(&party, can_travel).await?;
+        }
 
         if let ContinueOption::Continue = resume_playing {
             continue;
         };
 
-        remove_user_instance(ctx, user.id).await;
-
         ui.queue_message(
             CreateEmbed::new()
                 .title("Resting...".to_owned())
-                .description(player.name.clone() + " takes a break"),
+                .description(if party.is_solo() {
+                    party.host().name.clone() + " takes a break"
+                } else {
+                    "The party takes a break".to_string()
+                }),
         );
 
-        match player.save(ctx).await {
-            Ok(_saved) => ui.queue_message(CreateEmbed::new().title("Save Succesful.")),
-            Err(err) => {
-                println!("{}", err);
-                ui.queue_message(
-                    CreateEmbed::new()
-                        .title("Ruh Roh Wraggy...".to_string())
-                        .description(format!(
-                            "Something went wrong while saving... Say goodbye to {}",
-                            player.name
-                        )),
-                )
+        for member in &party.members {
+            let player = &member.player;
+            match mode.finish(ctx, player, false).await {
+                Ok(_saved) => ui.queue_message(CreateEmbed::new().title(match mode {
+                    RunMode::Adventure => format!("Saved {} succesfully.", player.name),
+                    RunMode::Daily { .. } => format!(
+                        "{} finished today's challenge with a score of {}",
+                        player.name, player.score
+                    ),
+                })),
+                Err(err) => {
+                    println!("{}", err);
+                    ui.queue_message(
+                        CreateEmbed::new()
+                            .title("Ruh Roh Wraggy...".to_string())
+                            .description(format!(
+                                "Something went wrong while saving... Say goodbye to {}",
+                                player.name
+                            )),
+                    )
+                }
             }
         }
 
-        ui.send_messages().await.unwrap();
+        events.dispatch(ctx, &mut ui).await;
+        ui.send_messages().await?;
+        ui.say(&replay_message(&recorder)).await;
 
         break;
     }
     Ok(true)
 }
 
-// fn initialise_player_events(player: Player, ui: Display) {
-//     player.on(PlayerEvent::EffectStart, |&player, effect: Effect| {
-//         ui.queue_embed(format!(
-//             "{} has received a {} {}",
-//             player.name, effect.name, effect.typ
-//         ));
-//     });
-//     player.on(PlayerEvent::EffectEnd, |&player, effect: Effect| {
-//         ui.queue_embed(format!(
-//             "{}'s {} {} has ended",
-//             player.name, effect.name, effect.typ
-//         ));
-//     });
-//     player.on(PlayerEvent::EffectApplied, |&player, effect: Effect| {
-//         ui.queue_embed(format!(
-//             "{} has received a {} {}",
-//             player.name, effect.name, effect.typ
-//         ));
-//     });
-// }
+fn replay_message(recorder: &RunRecorder) -> String {
+    format!("Relive this run with `zumbor replay {}`", recorder.id())
+}
 
 // fn request_player<'a>(user: &User) -> Result<Player, Error> {
 //     Ok(Player {
@@ -209,20 +593,3 @@ async fn nice_message(
         .await?;
     Ok(res)
 }
-
-/**
- * Adds the user ID to the running instances saved into th context, returns an error if the user id exists in the intances vec already
- */
-async fn add_user_instance(ctx: &Context, user_id: UserId) -> Result<(), Error> {
-    let mut data = ctx.data.write().await;
-    let zumbor = data.get_mut::<ZumborInstances>().unwrap();
-
-    zumbor.add(user_id)
-}
-
-async fn remove_user_instance(ctx: &Context, user_id: UserId) {
-    let mut data = ctx.data.write().await;
-    let zumbor = data.get_mut::<ZumborInstances>().unwrap();
-
-    zumbor.remove(user_id);
-}
@@ -0,0 +1,133 @@
+use std::fmt::{self, Display};
+
+use serde::{Deserialize, Serialize};
+use serenity::all::{Colour, CreateEmbed};
+
+use super::{
+    attributes::Attribute,
+    effects::{BaseEffect, Effectable, LingeringEffect},
+    player::{stats::Stats, Player},
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Item {
+    /// Identifies the item when an encounter option requires it
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub kind: ItemKind,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum ItemKind {
+    /// Used up to apply its effects
+    Consumable {
+        base_effect: Option<BaseEffect>,
+        lingering_effect: Option<LingeringEffect>,
+    },
+    /// Modifies the holder's stats for as long as it is held
+    Equipment { stats: Stats },
+    /// Unlocks encounter options that require it
+    Key,
+}
+
+impl Display for ItemKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ItemKind::Consumable { .. } => write!(f, "Consumable"),
+            ItemKind::Equipment { .. } => write!(f, "Equipment"),
+            ItemKind::Key => write!(f, "Key item"),
+        }
+    }
+}
+
+impl From<&Item> for CreateEmbed {
+    fn from(item: &Item) -> Self {
+        let embed = CreateEmbed::new()
+            .title(&item.name)
+            .description(&item.description)
+            .colour(match item.kind {
+                ItemKind::Consumable { .. } => Colour::FABLED_PINK,
+                ItemKind::Equipment { .. } => Colour::BLITZ_BLUE,
+                ItemKind::Key => Colour::GOLD,
+            })
+            .field("Type", item.kind.to_string(), true);
+
+        match &item.kind {
+            ItemKind::Consumable {
+                base_effect,
+                lingering_effect,
+            } => {
+                let embed = match base_effect {
+                    Some(BaseEffect::Health(effect)) => {
+                        embed.field("Health", effect.potency.to_string(), true)
+                    }
+                    Some(BaseEffect::Attribute(effect)) => {
+                        embed.field(effect.name.clone(), effect.potency.to_string(), true)
+                    }
+                    None => embed,
+                };
+                match lingering_effect {
+                    Some(effect) => embed.field(
+                        format!("{} {}", effect.name, effect.kind),
+                        format!("{} for {} turns", effect.potency, effect.duration),
+                        true,
+                    ),
+                    None => embed,
+                }
+            }
+            ItemKind::Equipment { stats } => Attribute::VALUES
+                .into_iter()
+                .filter(|attr| stats.get(attr.clone()) != 0)
+                .fold(embed, |embed, attr| {
+                    embed.field(attr.clone(), format!("{:+}", stats.get(attr)), true)
+                }),
+            ItemKind::Key => embed,
+        }
+    }
+}
+
+impl Player {
+    pub fn give_item(&mut self, item: Item) {
+        self.inventory.push(item);
+    }
+
+    pub fn has_item(&self, id: &str) -> bool {
+        self.inventory.iter().any(|item| item.id == id)
+    }
+
+    /**
+     * The total stat modifier from all the equipment the player is holding
+     */
+    pub fn equipment_bonus(&self, stat: Attribute) -> i16 {
+        self.inventory
+            .iter()
+            .filter_map(|item| match &item.kind {
+                ItemKind::Equipment { stats } => Some(stats.get(stat.clone())),
+                _ => None,
+            })
+            .sum()
+    }
+
+    /**
+     * Consumes the item at the given inventory position, returning it if it could be used
+     */
+    pub fn use_item(&mut self, index: usize) -> Option<Item> {
+        let ItemKind::Consumable {
+            base_effect,
+            lingering_effect,
+        } = self.inventory.get(index)?.kind.clone()
+        else {
+            return None;
+        };
+
+        if let Some(effect) = &base_effect {
+            self.affect(effect);
+        }
+        if let Some(effect) = lingering_effect {
+            self.add_effect(effect);
+        }
+
+        Some(self.inventory.remove(index))
+    }
+}
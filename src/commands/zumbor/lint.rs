@@ -0,0 +1,131 @@
+use serenity::{
+    all::{CreateEmbed, CreateMessage},
+    model::prelude::Message,
+    prelude::Context,
+};
+
+use crate::{errors::Error, storage::StorageClient, utilities::permissions};
+
+use super::encounter::{
+    validation::{self, Issue},
+    ENCOUNTER_PREFIX,
+};
+
+pub struct LintReport {
+    pub checked: usize,
+    pub files: Vec<(String, Vec<Issue>)>,
+}
+
+impl LintReport {
+    pub fn new() -> Self {
+        LintReport {
+            checked: 0,
+            files: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, name: String, bytes: &[u8]) {
+        self.checked += 1;
+
+        let issues = validation::lint(bytes);
+        if !issues.is_empty() {
+            self.files.push((name, issues));
+        }
+    }
+
+    pub fn issue_count(&self) -> usize {
+        self.files.iter().map(|(_, issues)| issues.len()).sum()
+    }
+
+    /// Issues that keep an encounter out of play, the rest are only warnings
+    pub fn error_count(&self) -> usize {
+        self.files
+            .iter()
+            .flat_map(|(_, issues)| issues)
+            .filter(|issue| issue.is_error())
+            .count()
+    }
+}
+
+impl Default for LintReport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<&LintReport> for CreateEmbed {
+    fn from(report: &LintReport) -> Self {
+        let embed = CreateEmbed::new()
+            .title("Encounter lint")
+            .description(format!(
+                "Checked {} encounters and found {} errors and {} warnings in {} of them",
+                report.checked,
+                report.error_count(),
+                report.issue_count() - report.error_count(),
+                report.files.len()
+            ))
+            .colour(if report.files.is_empty() {
+                (20, 240, 60)
+            } else if report.error_count() == 0 {
+                (240, 180, 20)
+            } else {
+                (240, 40, 20)
+            });
+
+        // Embeds are limited to 25 fields of 1024 characters
+        report
+            .files
+            .iter()
+            .take(25)
+            .fold(embed, |embed, (name, issues)| {
+                let mut value = String::new();
+                for issue in issues {
+                    let line = format!("{}\n", issue);
+                    if value.len() + line.len() > 1000 {
+                        value.push('…');
+                        break;
+                    }
+                    value.push_str(&line);
+                }
+                embed.field(name, value, false)
+            })
+    }
+}
+
+/**
+ * Checks every encounter in the bucket
+ */
+pub async fn lint_storage(storage_client: &StorageClient) -> Result<LintReport, Error> {
+    let objects = storage_client.get_objects(ENCOUNTER_PREFIX).await?;
+    let mut report = LintReport::new();
+
+    for object in objects {
+        let bytes = storage_client.get(&object.name).await?;
+        report.add(object.name, &bytes);
+    }
+
+    Ok(report)
+}
+
+pub async fn lint(ctx: &Context, msg: &Message) -> Result<(), Error> {
+    if !permissions::is_admin(ctx, msg).await {
+        msg.reply(ctx, "Only admins can lint the encounters you pleb")
+            .await?;
+        return Err(Error::Plain("Lint requested by a non admin"));
+    }
+
+    let report = {
+        let data = ctx.data.read().await;
+        let storage_client = data
+            .get::<StorageClient>()
+            .ok_or(Error::Plain("Storage client not accessible!"))?;
+
+        lint_storage(storage_client).await?
+    };
+
+    msg.channel_id
+        .send_message(ctx, CreateMessage::new().embed((&report).into()))
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,342 @@
+use std::collections::HashSet;
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use serenity::{
+    all::{CreateEmbed, CreateMessage},
+    model::prelude::Message,
+    prelude::Context,
+};
+
+use crate::{errors::Error, storage::StorageClient, utilities::permissions};
+
+use super::{
+    encounter::{Encounter, ENCOUNTER_PREFIX},
+    player::{storage::SAVE_PREFIX, Player},
+};
+
+/// Upgrades a document from the version before it
+pub type Migration = fn(Value) -> Result<Value, Error>;
+
+/**
+ * Something stored as a versioned envelope, `{"version": n, ...}`, that is brought up to date
+ * by running it through every migration since the version it was written in
+ */
+pub trait Versioned: Serialize + DeserializeOwned {
+    /// The migrations in order, the first upgrades version 1 to version 2
+    const MIGRATIONS: &'static [Migration];
+
+    /// Works out the version of documents written before they carried one
+    fn detect_version(value: &Value) -> Result<u8, Error>;
+
+    /// Checks the document before it is migrated, in whichever version it was written in
+    fn validate(_value: &Value) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn current_version() -> u8 {
+        Self::MIGRATIONS.len() as u8 + 1
+    }
+
+    fn version_of(value: &Value) -> Result<u8, Error> {
+        match value.get("version") {
+            Some(version) => version
+                .as_u64()
+                .and_then(|version| u8::try_from(version).ok())
+                .ok_or(Error::Plain("Version is not a valid number")),
+            None => Self::detect_version(value),
+        }
+    }
+
+    /**
+     * Runs the document through the migrations it is missing, returning it along with whether it changed
+     */
+    fn upgrade(value: Value) -> Result<(Value, bool), Error> {
+        let version = Self::version_of(&value)?;
+        if version == 0 || version > Self::current_version() {
+            return Err(Error::Plain("Unsupported document version"));
+        }
+
+        let changed = version < Self::current_version() || value.get("version").is_none();
+
+        let mut value = Self::MIGRATIONS[usize::from(version - 1)..]
+            .iter()
+            .try_fold(value, |value, migration| migration(value))?;
+
+        match &mut value {
+            Value::Object(map) => {
+                map.insert("version".to_string(), Self::current_version().into());
+            }
+            _ => return Err(Error::Plain("Document is not an object")),
+        }
+
+        Ok((value, changed))
+    }
+
+    fn from_versioned(value: Value) -> Result<Self, Error> {
+        Self::validate(&value)?;
+        let (value, _) = Self::upgrade(value)?;
+
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+pub struct MigrationReport {
+    pub checked: usize,
+    pub migrated: Vec<String>,
+    pub failed: Vec<(String, Error)>,
+}
+
+impl MigrationReport {
+    pub fn new() -> Self {
+        MigrationReport {
+            checked: 0,
+            migrated: Vec::new(),
+            failed: Vec::new(),
+        }
+    }
+}
+
+impl Default for MigrationReport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<&MigrationReport> for CreateEmbed {
+    fn from(report: &MigrationReport) -> Self {
+        let embed = CreateEmbed::new()
+            .title("Migration")
+            .description(format!(
+                "Checked {} documents, migrated {} and failed to migrate {}",
+                report.checked,
+                report.migrated.len(),
+                report.failed.len()
+            ))
+            .colour(if report.failed.is_empty() {
+                (20, 240, 60)
+            } else {
+                (240, 40, 20)
+            });
+
+        // Embeds are limited to 25 fields of 1024 characters
+        report
+            .failed
+            .iter()
+            .take(25)
+            .fold(embed, |embed, (name, err)| {
+                let mut value = err.to_string();
+                if value.len() > 1000 {
+                    value.truncate(1000);
+                    value.push('…');
+                }
+                embed.field(name, value, false)
+            })
+    }
+}
+
+/**
+ * Upgrades every document under the prefix that is behind the current version, writing it back
+ * to the path `destination` gives for it and removing the original if that differs
+ */
+async fn migrate_prefix<T: Versioned>(
+    storage_client: &StorageClient,
+    prefix: &str,
+    destination: fn(&str) -> String,
+    report: &mut MigrationReport,
+) -> Result<(), Error> {
+    let objects = storage_client.get_objects(prefix).await?;
+    let names: HashSet<&str> = objects.iter().map(|object| object.name.as_str()).collect();
+
+    for object in &objects {
+        report.checked += 1;
+        let path = destination(&object.name);
+        let moved = path != object.name;
+
+        let res = async {
+            if moved && names.contains(path.as_str()) {
+                return Err(Error::Plain(
+                    "A document already exists where this would be moved to",
+                ));
+            }
+
+            let bytes = storage_client.get(&object.name).await?;
+            let value: Value = serde_json::from_slice(&bytes)?;
+
+            T::validate(&value)?;
+            let (value, changed) = T::upgrade(value)?;
+            if !changed && !moved {
+                return Ok(false);
+            }
+
+            // Round trip through the struct so nothing is written back that can't be read
+            let document: T = serde_json::from_value(value)?;
+            storage_client
+                .create_json(&path, serde_json::to_string(&document)?)
+                .await?;
+
+            if moved {
+                storage_client.delete(&object.name).await?;
+            }
+
+            Ok(true)
+        }
+        .await;
+
+        match res {
+            Ok(true) => report.migrated.push(object.name.clone()),
+            Ok(false) => (),
+            Err(err) => report.failed.push((object.name.clone(), err)),
+        }
+    }
+
+    Ok(())
+}
+
+/**
+ * Brings every encounter and save in the bucket up to the current version
+ */
+pub async fn migrate_storage(storage_client: &StorageClient) -> Result<MigrationReport, Error> {
+    let mut report = MigrationReport::new();
+
+    migrate_prefix::<Encounter>(
+        storage_client,
+        ENCOUNTER_PREFIX,
+        |name| name.to_string(),
+        &mut report,
+    )
+    .await?;
+    // The first saves were written without an extension, where loading never looked for them
+    migrate_prefix::<Player>(
+        storage_client,
+        SAVE_PREFIX,
+        |name| {
+            if name.ends_with(".json") {
+                name.to_string()
+            } else {
+                format!("{}.json", name)
+            }
+        },
+        &mut report,
+    )
+    .await?;
+
+    Ok(report)
+}
+
+pub async fn migrate(ctx: &Context, msg: &Message) -> Result<(), Error> {
+    if !permissions::is_admin(ctx, msg).await {
+        msg.reply(ctx, "Only admins can migrate the bucket you pleb")
+            .await?;
+        return Err(Error::Plain("Migration requested by a non admin"));
+    }
+
+    let report = {
+        let data = ctx.data.read().await;
+        let storage_client = data
+            .get::<StorageClient>()
+            .ok_or(Error::Plain("Storage client not accessible!"))?;
+
+        migrate_storage(storage_client).await?
+    };
+
+    msg.channel_id
+        .send_message(ctx, CreateMessage::new().embed((&report).into()))
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+
+    /// Counts were first written as strings, then under a different name
+    #[derive(Serialize, Deserialize)]
+    struct Counter {
+        version: u8,
+        total: u64,
+    }
+
+    fn migrate_v1(mut value: Value) -> Result<Value, Error> {
+        let count = value["count"]
+            .as_str()
+            .and_then(|count| count.parse::<u64>().ok())
+            .ok_or(Error::Plain("Count is not a number"))?;
+        value["count"] = count.into();
+        Ok(value)
+    }
+
+    fn migrate_v2(mut value: Value) -> Result<Value, Error> {
+        let count = value["count"].take();
+        let map = value.as_object_mut().unwrap();
+        map.remove("count");
+        map.insert("total".to_string(), count);
+        Ok(value)
+    }
+
+    impl Versioned for Counter {
+        const MIGRATIONS: &'static [Migration] = &[migrate_v1, migrate_v2];
+
+        fn detect_version(value: &Value) -> Result<u8, Error> {
+            if value["count"].is_string() {
+                Ok(1)
+            } else {
+                Ok(2)
+            }
+        }
+    }
+
+    #[test]
+    fn runs_every_missing_migration() {
+        let (value, changed) = Counter::upgrade(json!({ "count": "4" })).unwrap();
+
+        assert!(changed);
+        assert_eq!(value, json!({ "version": 3, "total": 4 }));
+    }
+
+    #[test]
+    fn starts_from_the_stored_version() {
+        let (value, changed) = Counter::upgrade(json!({ "version": 2, "count": 4 })).unwrap();
+
+        assert!(changed);
+        assert_eq!(value, json!({ "version": 3, "total": 4 }));
+    }
+
+    #[test]
+    fn current_documents_are_unchanged() {
+        let current = json!({ "version": 3, "total": 4 });
+        let (value, changed) = Counter::upgrade(current.clone()).unwrap();
+
+        assert!(!changed);
+        assert_eq!(value, current);
+    }
+
+    #[test]
+    fn current_documents_without_a_version_are_given_one() {
+        let (value, changed) = Counter::upgrade(json!({ "total": 4 })).unwrap();
+
+        assert!(changed);
+        assert_eq!(value["version"], 3);
+    }
+
+    #[test]
+    fn unsupported_versions_are_rejected() {
+        assert!(Counter::upgrade(json!({ "version": 0, "total": 4 })).is_err());
+        assert!(Counter::upgrade(json!({ "version": 4, "total": 4 })).is_err());
+        assert!(Counter::upgrade(json!({ "version": "3", "total": 4 })).is_err());
+    }
+
+    #[test]
+    fn failed_migrations_are_reported() {
+        assert!(Counter::upgrade(json!({ "count": "many" })).is_err());
+        assert!(Counter::from_versioned(json!({ "count": "many" })).is_err());
+
+        let counter = Counter::from_versioned(json!({ "count": "4" })).unwrap();
+        assert_eq!(counter.version, 3);
+        assert_eq!(counter.total, 4);
+    }
+}
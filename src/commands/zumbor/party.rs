@@ -0,0 +1,409 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+use futures_util::StreamExt;
+use serenity::{
+    all::{
+        ButtonStyle, ComponentInteraction, CreateActionRow, CreateButton, CreateEmbed,
+        CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage, User, UserId,
+    },
+    model::prelude::Message,
+    prelude::Context,
+};
+
+use tokio_util::sync::CancellationToken;
+
+use crate::errors::Error;
+
+use super::{
+    effects::{Effectable, LingeringEffectName},
+    encounter::EncounterOption,
+    initialise::{self, RunMode},
+    player::{self, Player},
+    rules,
+    session::{self, SessionGuard, SessionKind},
+};
+
+pub const MAX_PARTY_SIZE: usize = 4;
+
+/// How long the lobby stays open before the party is abandoned
+const LOBBY_TIMEOUT: Duration = Duration::from_secs(60 * 5);
+
+/// How encounters are decided when there is more than one member
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Resolution {
+    /// Every member votes and the best suited member attempts the winning option
+    Vote,
+    /// Members take turns choosing and attempting the option for the whole party
+    Actor,
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Resolution::Vote => write!(f, "Vote"),
+            Resolution::Actor => write!(f, "Take turns"),
+        }
+    }
+}
+
+pub struct Member {
+    pub user_id: UserId,
+    pub player: Player,
+    /// Ends the member's session when they leave the party, however they leave it
+    pub session: SessionGuard,
+}
+
+pub struct Party {
+    /// The host is always the first member
+    pub members: Vec<Member>,
+    pub resolution: Resolution,
+    actor: usize,
+}
+
+impl Party {
+    pub fn new(members: Vec<Member>, resolution: Resolution) -> Self {
+        Party {
+            members,
+            resolution,
+            actor: 0,
+        }
+    }
+
+    pub fn solo(session: SessionGuard, player: Player) -> Self {
+        Party::new(
+            vec![Member {
+                user_id: session.user_id(),
+                player,
+                session,
+            }],
+            Resolution::Actor,
+        )
+    }
+
+    pub fn is_solo(&self) -> bool {
+        self.members.len() == 1
+    }
+
+    pub fn host(&self) -> &Player {
+        &self.members[0].player
+    }
+
+    /// The member encounters are picked for, and who chooses the option when taking turns
+    pub fn actor(&self) -> &Player {
+        &self.members[self.actor].player
+    }
+
+    pub fn actor_mut(&mut self) -> &mut Player {
+        &mut self.members[self.actor].player
+    }
+
+    /// Shared with interaction filters to only accept members
+    pub fn tags(&self) -> Arc<[String]> {
+        self.members
+            .iter()
+            .map(|member| member.player.tag.clone())
+            .collect()
+    }
+
+    pub fn players(&self) -> Vec<&Player> {
+        self.members.iter().map(|member| &member.player).collect()
+    }
+
+    /// The members with a say in which option is picked, stunned members have none
+    pub fn deciders(&self) -> Vec<&Player> {
+        let deciders = match self.resolution {
+            Resolution::Vote => self.players(),
+            Resolution::Actor => vec![self.actor()],
+        };
+
+        deciders
+            .into_iter()
+            .filter(|player| !player.has_effect(&LingeringEffectName::Stun))
+            .collect()
+    }
+
+    /**
+     * The member who attempts the option, for votes this is whoever has the highest stat for it
+     */
+    pub fn attempter(&self, option: &EncounterOption) -> usize {
+        match self.resolution {
+            Resolution::Actor => self.actor,
+            Resolution::Vote => self
+                .members
+                .iter()
+                .enumerate()
+                .filter(|(_, member)| option.is_available_to(&member.player))
+                .max_by_key(|(_, member)| {
+                    member.player.stats.get(option.stat.clone())
+                        + member.player.equipment_bonus(option.stat.clone())
+                })
+                .map(|(index, _)| index)
+                .unwrap_or(self.actor),
+        }
+    }
+
+    /**
+     * Hands the turn to the next member when taking turns, carrying over any chained encounter
+     */
+    pub fn advance(&mut self, next_encounter: Option<String>) {
+        if self.members.is_empty() {
+            return;
+        }
+
+        if self.resolution == Resolution::Actor {
+            self.actor = (self.actor + 1) % self.members.len();
+        }
+        self.actor_mut().next_encounter = next_encounter;
+    }
+
+    /**
+     * Removes and returns every member who has died
+     */
+    pub fn remove_dead(&mut self) -> Vec<Member> {
+        let (dead, alive) = std::mem::take(&mut self.members)
+            .into_iter()
+            .partition(|member| member.player.health <= 0);
+
+        self.members = alive;
+        if self.actor >= self.members.len() {
+            self.actor = 0;
+        }
+
+        dead
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+}
+
+/**
+ * Opens a lobby for others to join, then starts a run with everyone who joined
+ */
+pub async fn host(ctx: &Context, msg: &Message) -> Result<(), Error> {
+    let channel_id = msg.channel_id;
+    // Everyone in the party shares a token so killing any of their sessions ends the run
+    let token = CancellationToken::new();
+
+    let host_session = match session::claim(
+        ctx,
+        msg.author.id,
+        SessionKind::Party,
+        channel_id,
+        token.clone(),
+    )
+    .await
+    {
+        Ok(session) => session,
+        Err(err) => {
+            msg.reply(
+                ctx,
+                "You already have a running instance of Zumbor you fool!",
+            )
+            .await?;
+            return Err(err);
+        }
+    };
+
+    let mut users = vec![msg.author.clone()];
+    let mut sessions = vec![host_session];
+    let mut resolution = Resolution::Vote;
+
+    let message = channel_id
+        .send_message(
+            ctx,
+            CreateMessage::new()
+                .embed(lobby_embed(&users, resolution))
+                .components(lobby_components(resolution)),
+        )
+        .await?;
+
+    // Killing any session in the lobby closes it, releasing everyone's sessions
+    let lobby = session::until_killed(token.clone(), async {
+        let mut interactions = message
+            .await_component_interactions(ctx)
+            .timeout(LOBBY_TIMEOUT)
+            .stream();
+
+        while let Some(interaction) = interactions.next().await {
+            let is_host = interaction.user.id == msg.author.id;
+
+            match interaction.data.custom_id.as_str() {
+                "join" if users.iter().any(|user| user.id == interaction.user.id) => {
+                    reply_privately(ctx, &interaction, "You are already in the party").await?;
+                    continue;
+                }
+                "join" if users.len() >= MAX_PARTY_SIZE => {
+                    reply_privately(ctx, &interaction, "The party is full").await?;
+                    continue;
+                }
+                "join" => {
+                    let Ok(session) = session::claim(
+                        ctx,
+                        interaction.user.id,
+                        SessionKind::Party,
+                        channel_id,
+                        token.clone(),
+                    )
+                    .await
+                    else {
+                        reply_privately(ctx, &interaction, "You are already playing Zumbor")
+                            .await?;
+                        continue;
+                    };
+                    users.push(interaction.user.clone());
+                    sessions.push(session);
+                }
+                "leave" if is_host => {
+                    reply_privately(ctx, &interaction, "The host can't leave their own party")
+                        .await?;
+                    continue;
+                }
+                "leave" => {
+                    users.retain(|user| user.id != interaction.user.id);
+                    sessions.retain(|session| session.user_id() != interaction.user.id);
+                }
+                "resolution" if is_host => {
+                    resolution = match resolution {
+                        Resolution::Vote => Resolution::Actor,
+                        Resolution::Actor => Resolution::Vote,
+                    };
+                }
+                "start" if is_host => {
+                    interaction
+                        .create_response(ctx, CreateInteractionResponse::Acknowledge)
+                        .await?;
+                    return Ok(true);
+                }
+                _ => {
+                    reply_privately(ctx, &interaction, "Only the host can do that").await?;
+                    continue;
+                }
+            }
+
+            interaction
+                .create_response(
+                    ctx,
+                    CreateInteractionResponse::UpdateMessage(
+                        CreateInteractionResponseMessage::new()
+                            .embed(lobby_embed(&users, resolution))
+                            .components(lobby_components(resolution)),
+                    ),
+                )
+                .await?;
+        }
+
+        Ok(false)
+    })
+    .await;
+
+    if let Err(err) = message.delete(ctx).await {
+        println!("Failed to delete the lobby message. {}", err);
+    }
+
+    if !lobby? {
+        return Err(Error::Plain("The party lobby timed out"));
+    }
+
+    let rules = rules::for_guild(ctx, msg.guild_id).await;
+
+    let mut members = Vec::new();
+    for (user, session) in users.into_iter().zip(sessions) {
+        let player = session
+            .until_killed(async {
+                match player::storage::load_save(ctx, &user.tag()).await {
+                    Ok(mut player) => {
+                        player.resume_encounter();
+                        Ok(player)
+                    }
+                    Err(err) if err.is_not_found() => {
+                        player::create(ctx, user.tag().into(), channel_id, &rules).await
+                    }
+                    Err(err) => Err(err),
+                }
+            })
+            .await;
+
+        match player {
+            Ok(player) => members.push(Member {
+                user_id: user.id,
+                player,
+                session,
+            }),
+            // The whole party goes down with a killed session, not just the member being made
+            Err(err) if token.is_cancelled() => return Err(err),
+            Err(err) => println!("{} could not join the party. {}", user.tag(), err),
+        }
+    }
+
+    // The host has to make it into the party for it to go ahead
+    if members.first().map(|member| member.user_id) != Some(msg.author.id) {
+        return Err(Error::Plain("The host did not make a character"));
+    }
+
+    initialise::run(
+        ctx,
+        channel_id,
+        Party::new(members, resolution),
+        RunMode::Adventure,
+        rules,
+    )
+    .await
+    .map(|_| ())
+}
+
+async fn reply_privately(
+    ctx: &Context,
+    interaction: &ComponentInteraction,
+    content: &str,
+) -> Result<(), Error> {
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+fn lobby_embed(users: &[User], resolution: Resolution) -> CreateEmbed {
+    CreateEmbed::new()
+        .title(format!("{} is gathering a party", users[0].name))
+        .description(
+            users
+                .iter()
+                .map(|user| user.name.clone())
+                .collect::<Vec<String>>()
+                .join("\n"),
+        )
+        .field(
+            "Members",
+            format!("{}/{}", users.len(), MAX_PARTY_SIZE),
+            true,
+        )
+        .field("Decisions", resolution.to_string(), true)
+}
+
+fn lobby_components(resolution: Resolution) -> Vec<CreateActionRow> {
+    let other = match resolution {
+        Resolution::Vote => Resolution::Actor,
+        Resolution::Actor => Resolution::Vote,
+    };
+
+    vec![CreateActionRow::Buttons(vec![
+        CreateButton::new("join").label("Join"),
+        CreateButton::new("leave")
+            .label("Leave")
+            .style(ButtonStyle::Secondary),
+        CreateButton::new("resolution")
+            .label(format!("Switch to {}", other.to_string().to_lowercase()))
+            .style(ButtonStyle::Secondary),
+        CreateButton::new("start")
+            .label("Set off")
+            .style(ButtonStyle::Success),
+    ])]
+}
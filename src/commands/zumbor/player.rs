@@ -1,23 +1,42 @@
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use serenity::{
-    all::CreateEmbedAuthor, builder::CreateEmbed, model::prelude::ChannelId, prelude::Context,
+    all::{ComponentInteractionDataKind, CreateEmbedAuthor, ModalInteraction},
+    builder::CreateEmbed,
+    model::prelude::{ChannelId, Message},
+    prelude::Context,
+};
+use std::{
+    cmp,
+    collections::{BTreeSet, VecDeque},
+    fmt,
+    sync::Arc,
+    time::Duration,
 };
-use std::{cmp, sync::Arc};
 
 mod builder;
+pub mod experience;
 pub mod stats;
 pub mod storage;
 use super::{
     attributes::Attribute,
-    effects::{Effectable, LingeringEffect},
+    classes::{self, Class},
+    effects::{Effectable, LingeringEffect, LingeringEffectName},
+    events::{EventSender, GameEvent},
+    items::Item,
+    rules::Ruleset,
 };
-use crate::{errors::Error, utilities::await_interactions};
-use builder::PlayerDetails;
+use crate::{errors::Error, storage::StorageClient, utilities::await_interactions};
+pub use builder::PlayerDetails;
 use stats::Stats;
 
+/// How many of the player's latest encounters are avoided when picking the next one
+const RECENT_ENCOUNTER_MEMORY: usize = 5;
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Player {
+    #[serde(default = "storage::current_version")]
+    pub version: u8,
     pub tag: String,
     pub description: String,
     pub name: String,
@@ -25,19 +44,78 @@ pub struct Player {
     pub score: u16,
     pub stats: Stats,
     pub effects: Vec<LingeringEffect>,
+    #[serde(default)]
+    pub recent_encounters: VecDeque<String>,
+    #[serde(default)]
+    pub flags: BTreeSet<String>,
+    /// The encounter the player's current chain leads to next
+    #[serde(default)]
+    pub next_encounter: Option<String>,
+    /// The encounter the player was in the middle of when they were last saved
+    #[serde(default)]
+    pub current_encounter: Option<String>,
+    #[serde(default)]
+    pub inventory: Vec<Item>,
+    #[serde(default)]
+    pub gold: u32,
+    /// The id of the zone the player is exploring, they are in the wilds without one
+    #[serde(default)]
+    pub zone: Option<String>,
+    #[serde(default = "experience::starting_level")]
+    pub level: u8,
+    /// Experience earned towards the next level
+    #[serde(default)]
+    pub experience: u32,
+    /// Points from level ups the player hasn't spent yet, offered again at their next level up
+    #[serde(default)]
+    pub unspent_points: i16,
+    #[serde(default)]
+    pub class: Option<Class>,
+    /// Tells the user's characters apart, so a sheet can be traced back to the character it was made from
+    #[serde(default = "storage::new_character_id")]
+    pub character_id: String,
+    /// When the save was last written in seconds since the unix epoch, sheets from before it are out of date
+    #[serde(default)]
+    pub saved_at: u64,
+    /// Cosmetic title from the user's achievements, looked up when a run starts
+    #[serde(skip)]
+    pub title: Option<String>,
+    /// Where the player's effect events are reported while they are playing
+    #[serde(skip)]
+    pub events: Option<EventSender>,
+    /// The rules of the guild the player is playing in
+    #[serde(skip)]
+    pub rules: Ruleset,
 }
 
 impl Player {
     pub fn new(tag: String, details: PlayerDetails, stats: Stats) -> Player {
         let PlayerDetails { name, description } = details;
         Player {
+            version: storage::current_version(),
             tag,
-            health: 20,
+            health: Ruleset::default().starting_health,
             score: 0,
             effects: Vec::new(),
             stats,
             name,
             description,
+            recent_encounters: VecDeque::new(),
+            flags: BTreeSet::new(),
+            next_encounter: None,
+            current_encounter: None,
+            inventory: Vec::new(),
+            gold: 0,
+            zone: None,
+            level: experience::starting_level(),
+            experience: 0,
+            unspent_points: 0,
+            class: None,
+            character_id: storage::new_character_id(),
+            saved_at: 0,
+            title: None,
+            events: None,
+            rules: Ruleset::default(),
         }
     }
 
@@ -45,14 +123,51 @@ impl Player {
         self.score += score
     }
 
-    pub fn roll_stat(&self, stat: &Attribute) -> RollResult {
-        let mut rng = rand::thread_rng();
-        let roll = rng.gen_range(1..20);
+    /// Ticks the player's lingering effects and scores them for getting through the encounter
+    pub fn end_turn(&mut self) {
+        self.apply_effects();
+        self.add_score(1);
+    }
+
+    pub fn remember_encounter(&mut self, path: String) {
+        self.recent_encounters.push_back(path);
+        while self.recent_encounters.len() > RECENT_ENCOUNTER_MEMORY {
+            self.recent_encounters.pop_front();
+        }
+    }
+
+    /**
+     * Sends the player back into the encounter their last run was interrupted in, returning whether there was one
+     */
+    pub fn resume_encounter(&mut self) -> bool {
+        match self.current_encounter.take() {
+            Some(encounter) => {
+                self.next_encounter = Some(encounter);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn roll_stat(&self, stat: &Attribute, rng: &mut impl Rng) -> RollResult {
+        let sides = self.rules.die_sides;
+        let roll = if self.has_effect(&LingeringEffectName::Advantage) {
+            cmp::max(rng.gen_range(1..=sides), rng.gen_range(1..=sides))
+        } else {
+            rng.gen_range(1..=sides)
+        };
+
+        self.roll_result(roll, stat)
+    }
 
+    /// What a natural roll of the die comes to once the player's stat and equipment are added
+    pub fn roll_result(&self, roll: i16, stat: &Attribute) -> RollResult {
         match roll {
             1 => RollResult::CriticalFail,
-            20 => RollResult::CriticalSuccess,
-            num => RollResult::Value(num + self.stats.get(stat.clone())),
+            num if num >= self.rules.die_sides => RollResult::CriticalSuccess,
+            num => RollResult::Value(
+                num + self.stats.get(stat.clone()) + self.equipment_bonus(stat.clone()),
+            ),
         }
     }
 }
@@ -60,31 +175,55 @@ impl Player {
 impl From<&Player> for CreateEmbed {
     fn from(player: &Player) -> Self {
         // Determining color of embed from players health
-        let current_health: u8 = player.health.try_into().unwrap_or(255);
+        let full_health = i32::from(player.rules.starting_health.max(1));
+        let current_health = i32::from(player.health).clamp(0, full_health);
+        let green = (current_health * 255 / full_health) as u8;
 
-        let color: (u8, u8, u8) = (
-            (255u8 - (cmp::min(current_health, 20) / 20 * 255)).clamp(0, 255),
-            ((cmp::min(current_health, 20) / 20) * 255).clamp(0, 255),
-            0,
-        );
+        let color: (u8, u8, u8) = (255 - green, green, 0);
 
-        use Attribute::{Agility, Charisma, Strength, Wisdom};
+        let author = match &player.title {
+            Some(title) => format!("{} {}", player.name, title),
+            None => player.name.clone(),
+        };
 
-        CreateEmbed::default()
-            .author(CreateEmbedAuthor::new(&player.name))
+        let embed = CreateEmbed::default()
+            .author(CreateEmbedAuthor::new(author))
             .title(&player.name)
             .description(&player.description)
             .color(color)
             .field("Score", player.score.to_string(), true)
             .field("Health", player.health.to_string(), true)
-            .field(Charisma, player.stats.charisma.to_string(), true)
-            .field(Strength, player.stats.strength.to_string(), true)
-            .field(Wisdom, player.stats.wisdom.to_string(), true)
-            .field(Agility, player.stats.agility.to_string(), true)
+            .field("Gold", player.gold.to_string(), true)
+            .field(
+                format!("Level {}", player.level),
+                format!(
+                    "{}/{} xp",
+                    player.experience,
+                    experience::experience_to_next(player.level)
+                ),
+                true,
+            );
+
+        let embed = match &player.class {
+            Some(class) => embed.field("Class", &class.name, true),
+            None => embed,
+        };
+
+        Attribute::VALUES.into_iter().fold(embed, |embed, attr| {
+            let stat = player.stats.get(attr.clone());
+            let value = match player.equipment_bonus(attr.clone()) {
+                0 => stat.to_string(),
+                bonus => format!("{} ({:+})", stat, bonus),
+            };
+            embed.field(attr, value, true)
+        })
     }
 }
 
 impl Effectable for Player {
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
     fn get_effects(&self) -> Vec<LingeringEffect> {
         self.effects.clone()
     }
@@ -103,63 +242,93 @@ impl Effectable for Player {
     fn set_stats(&mut self, stats: Stats) {
         self.stats = stats;
     }
+    fn get_events(&self) -> Option<&EventSender> {
+        self.events.as_ref()
+    }
+    fn get_tag(&self) -> Option<String> {
+        Some(self.tag.clone())
+    }
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum RollResult {
     CriticalFail,
     CriticalSuccess,
     Value(i16),
 }
 
+impl fmt::Display for RollResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RollResult::CriticalFail => write!(f, "a critical fail"),
+            RollResult::CriticalSuccess => write!(f, "a critical success"),
+            RollResult::Value(value) => write!(f, "{}", value),
+        }
+    }
+}
+
 pub async fn create(
     context: &Context,
     user_tag: Arc<str>,
     channel: ChannelId,
+    rules: &Ruleset,
 ) -> Result<Player, Error> {
+    let timeout = rules.choice_timeout();
     let message = builder::prompt_character_creation_start(channel, context).await?;
-    let interaction = await_interactions::component(context, &message, user_tag.clone()).await?;
+    let interaction =
+        await_interactions::component_within(context, &message, user_tag.clone(), timeout).await?;
     builder::prompt_with_character_details_modal(interaction, context).await?;
-    let interaction = await_interactions::modal(context, &message, user_tag.clone()).await?;
+    let interaction =
+        await_interactions::modal_within(context, &message, user_tag.clone(), timeout).await?;
 
     let details_data = interaction.data.components.clone();
 
-    builder::prompt_for_player_stats(interaction, context).await?;
-    let interaction = await_interactions::component(context, &message, user_tag.clone()).await?;
-    builder::prompt_with_stats_modal(interaction, context).await?;
-    let interaction = await_interactions::modal(context, &message, user_tag.clone()).await?;
-    let stats_data = interaction.data.components.clone();
-
-    let mut stats: Stats = stats_data.try_into()?;
+    let limits = rules.stat_limits(experience::starting_level());
 
-    println!("Sum is {} and max is {}", stats.sum(), stats.get_max());
+    let classes = {
+        let data = context.data.read().await;
+        let storage_client = data
+            .get::<StorageClient>()
+            .ok_or(Error::Plain("Storage client not accessible!"))?;
 
-    let mut loop_int = interaction.clone();
-    println!("Is invalid?: {}", stats.sum() > 5 || stats.get_max() > 5);
-    while stats.sum() > 5 || stats.get_max() > 5 {
-        println!("Start loop");
-
-        builder::re_prompt_for_player_stats(loop_int, context).await?;
-        println!("Re request stats...");
+        classes::load(storage_client).await.unwrap_or_else(|err| {
+            println!("Unable to load the classes. {}", err);
+            Vec::new()
+        })
+    };
 
+    let class = if classes.is_empty() {
+        builder::prompt_for_player_stats(interaction, context).await?;
+        None
+    } else {
+        builder::prompt_for_class(interaction, context, &classes).await?;
         let interaction =
-            await_interactions::component(context, &message, user_tag.clone()).await?;
-        println!("Awaited button click...");
+            await_interactions::component_within(context, &message, user_tag.clone(), timeout)
+                .await?;
 
-        builder::prompt_with_stats_modal(interaction, context).await?;
-        println!("Sent next modal");
-
-        loop_int = await_interactions::modal(context, &message, user_tag.clone()).await?;
-        println!("Awaited modal interaction");
+        let class = match &interaction.data.kind {
+            ComponentInteractionDataKind::StringSelect { values } => values
+                .first()
+                .and_then(|value| value.parse::<usize>().ok())
+                .and_then(|index| classes.get(index))
+                .cloned(),
+            _ => None,
+        }
+        .ok_or(Error::Plain("Chosen class does not exist"))?;
 
-        let stats_data = loop_int.data.components.clone();
+        builder::prompt_for_class_stats(interaction, context, &class).await?;
+        Some(class)
+    };
 
-        stats = match stats_data.try_into() {
-            Ok(val) => val,
-            Err(_err) => continue,
-        };
-
-        println!("Sum is {} and max is {}", stats.sum(), stats.get_max());
-    }
+    let (stats, _) = allocate_stats(
+        context,
+        &message,
+        user_tag.clone(),
+        limits.budget,
+        timeout,
+        |stats| limits.allows(stats),
+    )
+    .await?;
 
     if let Err(er) = message.delete(context).await {
         println!("Failed to delete previous message!: {}", er);
@@ -167,11 +336,84 @@ pub async fn create(
 
     let details: PlayerDetails = details_data.try_into()?;
 
-    Ok(Player::new(user_tag.to_string(), details, stats))
+    let mut player = Player::new(user_tag.to_string(), details, stats);
+    player.health = rules.starting_health;
+    player.rules = rules.clone();
+    if let Some(class) = class {
+        player.assign_class(class);
+    }
+
+    Ok(player)
 }
 
-// pub enum PlayerEvent {
-//     EffectStart(LingeringEffectName),
-//     EffectEnd(LingeringEffectName),
-//     EffectApplied(LingeringEffect),
-// }
+/**
+ * Lets the player allocate the stat points from the levels they have just gained
+ */
+pub async fn level_up(
+    context: &Context,
+    channel: ChannelId,
+    player: &mut Player,
+    levels: u8,
+) -> Result<(), Error> {
+    // Held on the player until they're spent so a missed prompt doesn't lose them
+    player.unspent_points += experience::POINTS_PER_LEVEL * i16::from(levels);
+    let points = player.unspent_points;
+    let limits = player.rules.stat_limits(player.level);
+    let current = player.stats.clone();
+    let base = player.base_stats();
+
+    let message = builder::prompt_for_level_up(channel, context, player.level, points).await?;
+    let (allocation, interaction) = allocate_stats(
+        context,
+        &message,
+        player.tag.as_str().into(),
+        points,
+        player.rules.choice_timeout(),
+        |allocation| {
+            allocation.get_min() >= 0
+                && allocation.sum() <= points
+                && (current.clone() + allocation.clone() - base.clone()).get_max() <= limits.max
+        },
+    )
+    .await?;
+
+    player.unspent_points = points - allocation.sum();
+    player.stats = current + allocation;
+    player.emit(GameEvent::StatsAllocated {
+        tag: player.tag.clone(),
+        name: player.name.clone(),
+        level: player.level,
+        stats: player.stats.clone() - base,
+        max_stat: limits.max,
+    });
+    builder::confirm_level_up(interaction, context, player.level).await
+}
+
+/**
+ * Prompts with the stats modal until the player submits an allocation that passes the given check
+ */
+async fn allocate_stats(
+    context: &Context,
+    message: &Message,
+    user_tag: Arc<str>,
+    points: i16,
+    timeout: Duration,
+    is_valid: impl Fn(&Stats) -> bool,
+) -> Result<(Stats, ModalInteraction), Error> {
+    loop {
+        let interaction =
+            await_interactions::component_within(context, message, user_tag.clone(), timeout)
+                .await?;
+        builder::prompt_with_stats_modal(interaction, context, points).await?;
+        let interaction =
+            await_interactions::modal_within(context, message, user_tag.clone(), timeout).await?;
+
+        match Stats::try_from(interaction.data.components.clone()) {
+            Ok(stats) if is_valid(&stats) => return Ok((stats, interaction)),
+            Ok(stats) => println!("Sum is {} and max is {}", stats.sum(), stats.get_max()),
+            Err(err) => println!("Failed to read stats. {}", err),
+        }
+
+        builder::re_prompt_for_player_stats(interaction, context).await?;
+    }
+}
@@ -2,14 +2,18 @@ use serenity::{
     all::{
         ActionRow, ActionRowComponent, ComponentInteraction, CreateActionRow, CreateButton,
         CreateEmbed, CreateInputText, CreateInteractionResponseMessage, CreateMessage, CreateModal,
-        InputTextStyle, ModalInteraction,
+        CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption, InputTextStyle,
+        ModalInteraction,
     },
     builder::CreateInteractionResponse,
     model::prelude::{ChannelId, Message},
     prelude::Context,
 };
 
-use crate::{commands::zumbor::attributes::Attribute, errors::Error};
+use crate::{
+    commands::zumbor::{attributes::Attribute, classes::Class},
+    errors::Error,
+};
 
 pub struct PlayerDetails {
     pub name: String,
@@ -108,7 +112,7 @@ pub async fn prompt_for_player_stats(
     interaction
         .create_response(
             context,
-            CreateInteractionResponse::Message(
+            CreateInteractionResponse::UpdateMessage(
                 CreateInteractionResponseMessage::new()
                     .embed(CreateEmbed::new().title("Choose your stats..."))
                     .button(CreateButton::new("stats").label("Stats")),
@@ -121,6 +125,59 @@ pub async fn prompt_for_player_stats(
         })
 }
 
+pub async fn prompt_for_class(
+    interaction: ModalInteraction,
+    context: &Context,
+    classes: &[Class],
+) -> Result<(), Error> {
+    // Select menus are limited to 25 options
+    let options = classes
+        .iter()
+        .take(25)
+        .enumerate()
+        .map(|(index, class)| CreateSelectMenuOption::new(&class.name, index.to_string()))
+        .collect();
+
+    interaction
+        .create_response(
+            context,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .embed(CreateEmbed::new().title("Choose your class..."))
+                    .select_menu(
+                        CreateSelectMenu::new("class", CreateSelectMenuKind::String { options })
+                            .placeholder("Class"),
+                    ),
+            ),
+        )
+        .await
+        .map_err(|err| {
+            println!("{}", err);
+            Error::Plain("Failed to send class selection")
+        })
+}
+
+pub async fn prompt_for_class_stats(
+    interaction: ComponentInteraction,
+    context: &Context,
+    class: &Class,
+) -> Result<(), Error> {
+    interaction
+        .create_response(
+            context,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .embed(class.into())
+                    .button(CreateButton::new("stats").label("Stats")),
+            ),
+        )
+        .await
+        .map_err(|err| {
+            println!("{}", err);
+            Error::Plain("Failed to send class stats prompt")
+        })
+}
+
 pub async fn re_prompt_for_player_stats(
     interaction: ModalInteraction,
     context: &Context,
@@ -128,7 +185,7 @@ pub async fn re_prompt_for_player_stats(
     interaction
         .create_response(
             context,
-            CreateInteractionResponse::Message(
+            CreateInteractionResponse::UpdateMessage(
                 CreateInteractionResponseMessage::new()
                     .embed(
                         CreateEmbed::new()
@@ -141,6 +198,48 @@ pub async fn re_prompt_for_player_stats(
         .map_err(|_e| Error::Plain("Modal failed"))
 }
 
+pub async fn prompt_for_level_up(
+    channel: ChannelId,
+    context: &Context,
+    level: u8,
+    points: i16,
+) -> Result<Message, Error> {
+    channel
+        .send_message(
+            context,
+            CreateMessage::new()
+                .add_embed(
+                    CreateEmbed::new()
+                        .title(format!("You reached level {}!", level))
+                        .description(format!("You have {} new stat points to allocate", points)),
+                )
+                .button(CreateButton::new("stats").label("Allocate")),
+        )
+        .await
+        .map_err(|err| {
+            println!("{}", err);
+            Error::Plain("Failed to send level up message")
+        })
+}
+
+pub async fn confirm_level_up(
+    interaction: ModalInteraction,
+    context: &Context,
+    level: u8,
+) -> Result<(), Error> {
+    interaction
+        .create_response(
+            context,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .embed(CreateEmbed::new().title(format!("Level {} reached", level)))
+                    .components(Vec::new()),
+            ),
+        )
+        .await
+        .map_err(|_e| Error::Plain("Modal failed"))
+}
+
 pub async fn prompt_with_character_details_modal(
     interaction: ComponentInteraction,
     context: &Context,
@@ -157,9 +256,10 @@ pub async fn prompt_with_character_details_modal(
 pub async fn prompt_with_stats_modal(
     interaction: ComponentInteraction,
     context: &Context,
+    points: i16,
 ) -> Result<(), Error> {
     interaction
-        .create_response(context, create_stats_modal())
+        .create_response(context, create_stats_modal(points))
         .await
         .map_err(|err| {
             println!("Stats modal fail... {}", err);
@@ -180,9 +280,9 @@ fn create_character_details_modal() -> CreateInteractionResponse {
     )
 }
 
-fn create_stats_modal() -> CreateInteractionResponse {
+fn create_stats_modal(points: i16) -> CreateInteractionResponse {
     CreateInteractionResponse::Modal(
-        CreateModal::new("stats", "Allocate your 5 stat points").components(
+        CreateModal::new("stats", format!("Allocate your {} stat points", points)).components(
             Attribute::VALUES
                 .map(|attr| -> CreateActionRow {
                     let name: String = attr.into();
@@ -0,0 +1,132 @@
+use crate::commands::zumbor::{effects::Effectable, events::GameEvent, rules::Ruleset};
+
+use super::{stats::Stats, Player, RollResult};
+
+/// Stat points handed out for every level gained
+pub const POINTS_PER_LEVEL: i16 = 2;
+
+/// Thresholds above this reward extra experience
+const HARD_THRESHOLD: u8 = 10;
+
+pub fn starting_level() -> u8 {
+    1
+}
+
+/**
+ * Experience needed to go from the given level to the next, growing with each level
+ */
+pub fn experience_to_next(level: u8) -> u32 {
+    let level = u32::from(level);
+    5 * level * (level + 1)
+}
+
+/**
+ * Experience earned for an encounter result, more for critical successes and harder thresholds
+ */
+pub fn experience_for(roll: &RollResult, threshold: u8, succeeded: bool) -> u32 {
+    let base = 3 + u32::from(threshold.saturating_sub(HARD_THRESHOLD)) / 2;
+
+    match roll {
+        RollResult::CriticalSuccess => base * 2,
+        RollResult::Value(_) if succeeded => base,
+        _ => 1,
+    }
+}
+
+/// How many stat points a character of a given level may have, and how high any one stat can go
+pub struct StatLimits {
+    pub budget: i16,
+    pub max: i16,
+}
+
+impl StatLimits {
+    /// The limits under the standard rules
+    pub fn for_level(level: u8) -> Self {
+        Ruleset::default().stat_limits(level)
+    }
+
+    pub fn allows(&self, stats: &Stats) -> bool {
+        stats.get_min() >= 0 && stats.sum() <= self.budget && stats.get_max() <= self.max
+    }
+}
+
+impl Player {
+    /**
+     * Adds experience to the player, returning how many levels it gained them
+     */
+    pub fn gain_experience(&mut self, experience: u32) -> u8 {
+        self.experience += experience;
+
+        let mut levels = 0;
+        while self.experience >= experience_to_next(self.level) {
+            self.experience -= experience_to_next(self.level);
+            self.level += 1;
+            levels += 1;
+
+            self.emit(GameEvent::LevelUp {
+                tag: self.tag.clone(),
+                name: self.name.clone(),
+                level: self.level,
+            });
+        }
+
+        levels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::PlayerDetails;
+    use super::*;
+
+    #[test]
+    fn each_level_takes_more_experience() {
+        assert_eq!(experience_to_next(1), 10);
+        assert_eq!(experience_to_next(2), 30);
+        assert_eq!(experience_to_next(3), 60);
+    }
+
+    #[test]
+    fn harder_rolls_earn_more_experience() {
+        assert_eq!(experience_for(&RollResult::Value(12), 10, true), 3);
+        assert_eq!(experience_for(&RollResult::Value(16), 16, true), 6);
+        assert_eq!(experience_for(&RollResult::CriticalSuccess, 16, true), 12);
+        assert_eq!(experience_for(&RollResult::Value(8), 16, false), 1);
+        assert_eq!(experience_for(&RollResult::CriticalFail, 16, false), 1);
+    }
+
+    #[test]
+    fn experience_carries_over_levels() {
+        let mut player = Player::new(
+            "tester".to_string(),
+            PlayerDetails {
+                name: "Tester".to_string(),
+                description: "Learning".to_string(),
+            },
+            Stats::default(),
+        );
+
+        assert_eq!(player.gain_experience(9), 0);
+        assert_eq!(player.level, 1);
+
+        assert_eq!(player.gain_experience(35), 2);
+        assert_eq!(player.level, 3);
+        assert_eq!(player.experience, 4);
+    }
+
+    #[test]
+    fn stat_limits_grow_with_level() {
+        let limits = StatLimits::for_level(3);
+        assert_eq!(limits.budget, 9);
+        assert_eq!(limits.max, 6);
+
+        let stats = Stats {
+            charisma: 6,
+            strength: 3,
+            wisdom: 0,
+            agility: 0,
+        };
+        assert!(limits.allows(&stats));
+        assert!(!StatLimits::for_level(1).allows(&stats));
+    }
+}
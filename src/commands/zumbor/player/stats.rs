@@ -1,3 +1,5 @@
+use std::ops::{Add, Sub};
+
 use serde::{Deserialize, Serialize};
 use serenity::all::{ActionRow, ActionRowComponent};
 
@@ -44,6 +46,39 @@ impl Stats {
             .reduce(|acc, nu| if nu > acc { nu } else { acc })
             .expect("Array is not empty?")
     }
+
+    pub fn get_min(&self) -> i16 {
+        [self.agility, self.charisma, self.strength, self.wisdom]
+            .into_iter()
+            .reduce(|acc, nu| if nu < acc { nu } else { acc })
+            .expect("Array is not empty?")
+    }
+}
+
+impl Add for Stats {
+    type Output = Stats;
+
+    fn add(self, other: Stats) -> Stats {
+        Stats {
+            charisma: self.charisma + other.charisma,
+            strength: self.strength + other.strength,
+            wisdom: self.wisdom + other.wisdom,
+            agility: self.agility + other.agility,
+        }
+    }
+}
+
+impl Sub for Stats {
+    type Output = Stats;
+
+    fn sub(self, other: Stats) -> Stats {
+        Stats {
+            charisma: self.charisma - other.charisma,
+            strength: self.strength - other.strength,
+            wisdom: self.wisdom - other.wisdom,
+            agility: self.agility - other.agility,
+        }
+    }
 }
 
 impl TryFrom<Vec<ActionRow>> for Stats {
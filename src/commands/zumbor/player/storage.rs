@@ -1,123 +1,195 @@
-use serde_json::Value;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
 use serenity::client::Context;
 
 use crate::{
-    commands::zumbor::{effects::LingeringEffect, player::stats::Stats},
+    commands::zumbor::{
+        effects::LingeringEffect,
+        migration::{Migration, Versioned},
+    },
     errors::Error,
     storage::StorageClient,
 };
 
 use super::Player;
 
-// Fetches the player's save if it exists
+pub const SAVE_PREFIX: &str = "zumbor/saves/";
+
+/// Every user's dead characters are kept as `zumbor/graves/{tag}.json` so their sheets can't bring them back
+const GRAVE_PREFIX: &str = "zumbor/graves/";
+
+pub fn current_version() -> u8 {
+    Player::current_version()
+}
+
+pub fn new_character_id() -> String {
+    format!("{:016x}", rand::random::<u64>())
+}
+
+/// The ids of the user's characters that have died
+pub async fn load_graves(
+    storage_client: &StorageClient,
+    user_tag: &str,
+) -> Result<Vec<String>, Error> {
+    match storage_client
+        .get_json(&(GRAVE_PREFIX.to_string() + user_tag))
+        .await
+    {
+        Ok(graves) => Ok(graves),
+        Err(err) if err.is_not_found() => Ok(Vec::new()),
+        Err(err) => Err(err),
+    }
+}
+
+// Fetches the player's save if it exists, bringing it up to date if it was written by an older version
 pub async fn load_save(ctx: &Context, user_tag: &str) -> Result<Player, Error> {
     let data = ctx.data.read().await;
 
-    let storage_client = data.get::<StorageClient>().unwrap();
-    let path = "zumbor/saves/".to_string() + user_tag + ".json";
+    let storage_client = data
+        .get::<StorageClient>()
+        .ok_or(Error::Plain("Storage client not accessible!"))?;
 
-    let bytes = storage_client.get(&path).await?;
+    load_save_from(storage_client, user_tag).await
+}
 
-    let maybe_player: Result<Player, Error> = serde_json::from_slice(&bytes).map_err(Error::Json);
+pub async fn load_save_from(
+    storage_client: &StorageClient,
+    user_tag: &str,
+) -> Result<Player, Error> {
+    let value: Value = storage_client
+        .get_json(&(SAVE_PREFIX.to_string() + user_tag))
+        .await?;
 
-    // V2 players should be serializable straight to a struct
-    if let Ok(player) = maybe_player {
-        return Ok(player);
+    Player::from_versioned(value)
+}
+
+impl Versioned for Player {
+    const MIGRATIONS: &'static [Migration] = &[migrate_v1, migrate_v2];
+
+    fn detect_version(value: &Value) -> Result<u8, Error> {
+        // The first saves kept the user's tag under "user"
+        if value.get("user").is_some() && value.get("tag").is_none() {
+            Ok(1)
+        } else {
+            Ok(2)
+        }
     }
+}
 
-    println!("Failed deserialise of object as struct");
-
-    // Handles first versions of the Player object
-    let maybe_player_map: Value = serde_json::from_slice(&bytes).unwrap();
-
-    let name: String = maybe_player_map
-        .get("name")
-        .ok_or(Error::Plain("name field not present in data"))?
-        .as_str()
-        .expect("Name is a string")
-        .into();
-    let tag: String = maybe_player_map
-        .get("user")
-        .ok_or(Error::Plain("name field not present in data"))?
-        .as_str()
-        .expect("User is a string")
-        .into();
-    let description: String = maybe_player_map
-        .get("description")
-        .ok_or(Error::Plain("description field not present in data"))?
-        .as_str()
-        .expect("Description is a string")
-        .into();
-    let health: i16 = maybe_player_map
-        .get("health")
-        .ok_or(Error::Plain("health field not present in data"))?
-        .as_u64()
-        .expect("Health is a number")
-        .try_into()
-        .unwrap();
-    let score: u16 = maybe_player_map
-        .get("score")
-        .ok_or(Error::Plain("score field not present in data"))?
-        .as_u64()
-        .expect("Score to be a number")
-        .try_into()
-        .unwrap();
-
-    let stats = if let Value::Object(maybe_stats) = &maybe_player_map["stats"] {
-        Ok(Stats {
-            charisma: maybe_stats["Charisma"]
-                .as_u64()
-                .expect("Stat to be a number")
-                .try_into()
-                .expect("Good number"),
-            strength: maybe_stats["Strength"]
-                .as_u64()
-                .expect("Stat to be a number")
-                .try_into()
-                .expect("Good number"),
-            wisdom: maybe_stats["Wisdom"]
-                .as_u64()
-                .expect("Stat to be a number")
-                .try_into()
-                .expect("Good number"),
-            agility: maybe_stats["Agility"]
-                .as_u64()
-                .expect("Stat to be a number")
-                .try_into()
-                .expect("Good number"),
-        })
-    } else {
-        Err(Error::Plain("Stats should be an object / hash map"))
-    }?;
-
-    println!("Starting desrialise of effects..");
-    let effects: Vec<LingeringEffect> =
-        serde_json::from_value(maybe_player_map["effects"].clone()).unwrap_or(Vec::new());
-
-    let player = Player {
-        tag,
-        name,
-        description,
-        health,
-        score,
-        stats,
-        effects,
+/**
+ * Handles the first version of the Player object, which had capitalised stat names
+ */
+fn migrate_v1(value: Value) -> Result<Value, Error> {
+    let field = |key: &str| {
+        value
+            .get(key)
+            .cloned()
+            .ok_or(Error::Plain("Field missing from the version 1 save"))
+    };
+
+    let stats = value
+        .get("stats")
+        .and_then(Value::as_object)
+        .ok_or(Error::Plain("Stats should be an object / hash map"))?;
+    let stat = |name: &str| {
+        stats
+            .get(name)
+            .and_then(Value::as_i64)
+            .ok_or(Error::Plain("Stat missing from the version 1 save"))
     };
 
-    Ok(player)
+    // Effects the current version can't read are dropped rather than losing the whole save
+    let effects = value
+        .get("effects")
+        .cloned()
+        .filter(|effects| serde_json::from_value::<Vec<LingeringEffect>>(effects.clone()).is_ok())
+        .unwrap_or(json!([]));
+
+    Ok(json!({
+        "tag": field("user")?,
+        "name": field("name")?,
+        "description": field("description")?,
+        "health": field("health")?,
+        "score": field("score")?,
+        "stats": {
+            "charisma": stat("Charisma")?,
+            "strength": stat("Strength")?,
+            "wisdom": stat("Wisdom")?,
+            "agility": stat("Agility")?,
+        },
+        "effects": effects,
+    }))
+}
+
+/**
+ * Saves from before versioning applied stat debuffs as buffs when they started, while expiring
+ * them as debuffs. Their stats are brought in line with debuffs that lowered them to begin with,
+ * so expiring the debuff returns the stat to where it was
+ */
+fn migrate_v2(mut value: Value) -> Result<Value, Error> {
+    // Saves carrying a version were written after the fix
+    if value.get("version").is_some() {
+        return Ok(value);
+    }
+
+    let debuffs: Vec<(String, i64)> = value
+        .get("effects")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|effect| effect.get("kind").and_then(Value::as_str) == Some("Debuff"))
+        .filter_map(|effect| {
+            let stat = effect.get("name")?.get("Stat")?.as_str()?.to_lowercase();
+            let potency = effect.get("potency")?.as_i64()?;
+            Some((stat, potency))
+        })
+        .collect();
+
+    let stats = value
+        .get_mut("stats")
+        .and_then(Value::as_object_mut)
+        .ok_or(Error::Plain("Stats should be an object / hash map"))?;
+
+    for (stat, potency) in debuffs {
+        let current = stats
+            .get(&stat)
+            .and_then(Value::as_i64)
+            .ok_or(Error::Plain(
+                "Debuffed stat missing from the version 2 save",
+            ))?;
+        stats.insert(stat, (current - 2 * potency).into());
+    }
+
+    Ok(value)
 }
 
 impl Player {
-    pub async fn delete_save(self, ctx: &Context) -> Result<(), Error> {
+    pub async fn delete_save(&self, ctx: &Context) -> Result<(), Error> {
         let data = ctx.data.read().await;
 
         let storage_client = data
             .get::<StorageClient>()
             .ok_or(Error::Plain("Storage client not accessible!"))?;
 
+        self.delete_save_from(storage_client).await
+    }
+
+    /// Deletes the save of a character that has died, burying it first
+    pub async fn delete_save_from(&self, storage_client: &StorageClient) -> Result<(), Error> {
         dbg!(&self.tag);
+
+        let mut graves = load_graves(storage_client, &self.tag).await?;
+        graves.push(self.character_id.clone());
+        storage_client
+            .create_json(
+                &format!("{}{}.json", GRAVE_PREFIX, self.tag),
+                serde_json::to_string(&graves)?,
+            )
+            .await?;
+
         storage_client
-            .delete_json(("zumbor/saves/".to_string() + &self.tag).as_str())
+            .delete_json((SAVE_PREFIX.to_string() + &self.tag).as_str())
             .await
     }
 
@@ -128,9 +200,96 @@ impl Player {
             .get::<StorageClient>()
             .ok_or(Error::Plain("Storage client not accessible!"))?;
 
-        let player_json: String = serde_json::to_string(&self).map_err(Error::Json)?;
-        let save_name = "zumbor/saves/".to_string() + &self.tag;
+        self.save_to(storage_client).await
+    }
+
+    pub async fn save_to(&self, storage_client: &StorageClient) -> Result<(), Error> {
+        let mut player = serde_json::to_value(self).map_err(Error::Json)?;
+        player["saved_at"] = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default()
+            .into();
+
+        let player_json: String = serde_json::to_string(&player).map_err(Error::Json)?;
+        let save_name = SAVE_PREFIX.to_string() + &self.tag + ".json";
 
         storage_client.create_json(&save_name, player_json).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v1_save() -> Value {
+        json!({
+            "user": "tester",
+            "name": "Tester",
+            "description": "From the first version",
+            "health": 15,
+            "score": 4,
+            "stats": { "Charisma": 1, "Strength": 2, "Wisdom": 0, "Agility": 2 },
+            "effects": [
+                { "kind": "Debuff", "name": { "Stat": "Strength" }, "potency": 1, "duration": 2 },
+            ],
+        })
+    }
+
+    #[test]
+    fn detects_saves_by_their_tag_key() {
+        assert_eq!(Player::detect_version(&v1_save()).unwrap(), 1);
+        assert_eq!(
+            Player::detect_version(&json!({ "tag": "tester" })).unwrap(),
+            2
+        );
+    }
+
+    #[test]
+    fn v1_saves_get_lowercase_stats_and_a_tag() {
+        let value = migrate_v1(v1_save()).unwrap();
+
+        assert_eq!(value["tag"], "tester");
+        assert_eq!(value["stats"]["strength"], 2);
+        assert_eq!(value["stats"]["agility"], 2);
+        assert_eq!(value["effects"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn v1_saves_drop_effects_they_cant_read() {
+        let mut save = v1_save();
+        save["effects"] = json!([{ "kind": "Curse" }]);
+
+        let value = migrate_v1(save).unwrap();
+        assert_eq!(value["effects"], json!([]));
+    }
+
+    #[test]
+    fn v1_saves_need_every_field() {
+        let mut save = v1_save();
+        save.as_object_mut().unwrap().remove("score");
+
+        assert!(migrate_v1(save).is_err());
+    }
+
+    #[test]
+    fn v2_saves_have_debuffed_stats_lowered() {
+        let value = migrate_v2(migrate_v1(v1_save()).unwrap()).unwrap();
+        assert_eq!(value["stats"]["strength"], 0);
+
+        let versioned = json!({ "version": 2, "stats": { "strength": 2 }, "effects": [
+            { "kind": "Debuff", "name": { "Stat": "Strength" }, "potency": 1, "duration": 2 },
+        ] });
+        assert_eq!(migrate_v2(versioned.clone()).unwrap(), versioned);
+    }
+
+    #[test]
+    fn v1_saves_load_as_current_players() {
+        let player = Player::from_versioned(v1_save()).unwrap();
+
+        assert_eq!(player.version, current_version());
+        assert_eq!(player.tag, "tester");
+        assert_eq!(player.stats.strength, 0);
+        assert_eq!(player.health, 15);
+    }
+}
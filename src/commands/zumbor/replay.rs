@@ -0,0 +1,233 @@
+use std::{
+    mem,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    all::{ChannelId, CreateEmbed, CreateMessage},
+    framework::standard::Args,
+    model::prelude::Message,
+    prelude::Context,
+};
+
+use crate::{errors::Error, storage::StorageClient};
+
+use super::{
+    events::{EventListener, GameEvent},
+    party::Party,
+};
+
+const RUN_PREFIX: &str = "zumbor/runs/";
+
+/// Discord's limit on the length of an embed field's value
+const FIELD_LENGTH: usize = 1024;
+
+/// Discord's limits on the total length of an embed's text and on how many fields it has
+const EMBED_LENGTH: usize = 6000;
+const EMBED_FIELDS: usize = 25;
+
+/// Everything that happened during a run, in the order it happened
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RunLog {
+    pub id: String,
+    pub channel_id: ChannelId,
+    /// Seconds since the unix epoch
+    pub started: u64,
+    pub players: Vec<String>,
+    pub events: Vec<GameEvent>,
+}
+
+impl RunLog {
+    /**
+     * Splits the run into one entry per encounter, starting from each choice made.
+     * Anything from before the first choice is left out
+     */
+    fn encounters(&self) -> Vec<(String, Vec<String>)> {
+        let mut encounters: Vec<(String, Vec<String>)> = Vec::new();
+
+        for event in &self.events {
+            if let GameEvent::ChoiceMade { encounter, .. } = event {
+                encounters.push((encounter.clone(), Vec::new()));
+            }
+
+            if let (Some((_, lines)), Some(line)) = (encounters.last_mut(), event.describe()) {
+                lines.push(line);
+            }
+        }
+
+        encounters
+    }
+
+    /**
+     * The run as a timeline, an embed per page of encounters.
+     * Pages are filled until the next encounter would take them over discord's limits
+     */
+    pub fn timeline(&self) -> Vec<CreateEmbed> {
+        let encounters = self.encounters();
+        let (title, description) = self.header();
+        if encounters.is_empty() {
+            return vec![CreateEmbed::new()
+                .title(title)
+                .description("Nothing happened")];
+        }
+
+        let mut pages = Vec::new();
+        let mut length = title.len() + description.len();
+        let mut page = CreateEmbed::new().title(title).description(description);
+        let mut fields = 0;
+
+        for (index, (encounter, lines)) in encounters.iter().enumerate() {
+            let name = format!("{}. {}", index + 1, encounter);
+            let mut value = lines.join("\n");
+            if value.len() > FIELD_LENGTH {
+                let mut end = FIELD_LENGTH - '…'.len_utf8();
+                while !value.is_char_boundary(end) {
+                    end -= 1;
+                }
+                value.truncate(end);
+                value.push('…');
+            }
+
+            let field_length = name.len() + value.len();
+            if fields > 0 && (fields == EMBED_FIELDS || length + field_length > EMBED_LENGTH) {
+                let title = format!("Run {} continued", self.id);
+                length = title.len();
+                pages.push(mem::replace(&mut page, CreateEmbed::new().title(title)));
+                fields = 0;
+            }
+
+            page = page.field(name, value, false);
+            length += field_length;
+            fields += 1;
+        }
+        pages.push(page);
+
+        pages
+    }
+
+    /// The title and description the first page of the timeline opens with
+    fn header(&self) -> (String, String) {
+        (
+            format!("Run {}", self.id),
+            format!("{} set out <t:{}:R>", self.players.join(", "), self.started),
+        )
+    }
+}
+
+/**
+ * Keeps a log of every event in the run, shared with the game loop so it can be saved as the run goes
+ */
+#[derive(Clone)]
+pub struct RunRecorder {
+    log: Arc<Mutex<RunLog>>,
+}
+
+impl RunRecorder {
+    pub fn new(channel_id: ChannelId, party: &Party) -> Self {
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+
+        RunRecorder {
+            log: Arc::new(Mutex::new(RunLog {
+                id: format!("{}-{}", channel_id, started),
+                channel_id,
+                started,
+                players: party
+                    .players()
+                    .iter()
+                    .map(|player| player.name.clone())
+                    .collect(),
+                events: Vec::new(),
+            })),
+        }
+    }
+
+    fn snapshot(&self) -> RunLog {
+        // A panic while recording only loses the event it was recording
+        match self.log.lock() {
+            Ok(log) => log.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    pub fn id(&self) -> String {
+        self.snapshot().id
+    }
+
+    pub async fn save(&self, ctx: &Context) -> Result<(), Error> {
+        let log = self.snapshot();
+
+        let data = ctx.data.read().await;
+        let storage_client = data
+            .get::<StorageClient>()
+            .ok_or(Error::Plain("Storage client not accessible!"))?;
+
+        storage_client
+            .create_json(
+                &format!("{}{}.json", RUN_PREFIX, log.id),
+                serde_json::to_string(&log)?,
+            )
+            .await
+    }
+}
+
+impl EventListener for RunRecorder {
+    fn handle<'a>(&'a mut self, _ctx: &'a Context, event: &'a GameEvent) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            let mut log = match self.log.lock() {
+                Ok(log) => log,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            log.events.push(event.clone());
+        })
+    }
+}
+
+/**
+ * Re-renders a finished run as a timeline with `replay <run>`
+ */
+pub async fn replay(ctx: &Context, msg: &Message, mut args: Args) -> Result<(), Error> {
+    let Ok(id) = args.single::<String>() else {
+        msg.reply(ctx, "Which run? Every run ends with its id")
+            .await?;
+        return Err(Error::Plain("No run given to replay"));
+    };
+
+    // Ids are only ever made of digits and dashes, anything else can't be a run
+    if !id.chars().all(|c| c.is_ascii_digit() || c == '-') {
+        msg.reply(ctx, "That isn't a run id").await?;
+        return Err(Error::Plain("Invalid run id"));
+    }
+
+    let log: Result<RunLog, Error> = {
+        let data = ctx.data.read().await;
+        let storage_client = data
+            .get::<StorageClient>()
+            .ok_or(Error::Plain("Storage client not accessible!"))?;
+
+        storage_client
+            .get_json(&format!("{}{}", RUN_PREFIX, id))
+            .await
+    };
+
+    let log = match log {
+        Ok(log) => log,
+        Err(err) => {
+            msg.reply(ctx, format!("Couldn't find run {}", id)).await?;
+            return Err(err);
+        }
+    };
+
+    for embed in log.timeline() {
+        msg.channel_id
+            .send_message(ctx, CreateMessage::new().embed(embed))
+            .await?;
+    }
+
+    Ok(())
+}
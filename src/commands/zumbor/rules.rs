@@ -0,0 +1,331 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serenity::{
+    all::{CreateEmbed, CreateMessage, GuildId},
+    framework::standard::Args,
+    model::prelude::Message,
+    prelude::Context,
+};
+
+use crate::{errors::Error, storage::StorageClient, utilities::permissions};
+
+use super::player::experience::{StatLimits, POINTS_PER_LEVEL};
+
+/// Each guild's rules are stored as `zumbor/rules/{guild id}.json`
+const RULES_PREFIX: &str = "zumbor/rules/";
+
+/**
+ * The numbers a guild's games are played by, anything left out of a stored ruleset is standard
+ */
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct Ruleset {
+    pub starting_health: i16,
+    /// Stat points a new character has to spend
+    pub stat_budget: i16,
+    /// How high a new character can take any one stat
+    pub max_stat: i16,
+    pub die_sides: i16,
+    /// What base effects are multiplied by on a critical roll
+    pub critical_multiplier: i16,
+    /// Seconds players have to make a choice
+    pub choice_timeout: u64,
+    /// Seconds the host has to decide whether to carry on
+    pub continue_timeout: u64,
+}
+
+impl Default for Ruleset {
+    fn default() -> Self {
+        Ruleset {
+            starting_health: 20,
+            stat_budget: 5,
+            max_stat: 5,
+            die_sides: 20,
+            critical_multiplier: 2,
+            choice_timeout: 240,
+            continue_timeout: 120,
+        }
+    }
+}
+
+impl Ruleset {
+    pub const PRESETS: [&'static str; 3] = ["standard", "casual", "hardcore"];
+
+    pub fn preset(name: &str) -> Option<Ruleset> {
+        match name {
+            "standard" => Some(Ruleset::default()),
+            "casual" => Some(Ruleset {
+                starting_health: 30,
+                stat_budget: 7,
+                max_stat: 6,
+                choice_timeout: 480,
+                continue_timeout: 240,
+                ..Ruleset::default()
+            }),
+            "hardcore" => Some(Ruleset {
+                starting_health: 12,
+                stat_budget: 4,
+                max_stat: 4,
+                critical_multiplier: 3,
+                choice_timeout: 120,
+                continue_timeout: 60,
+                ..Ruleset::default()
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn stat_limits(&self, level: u8) -> StatLimits {
+        let levels_gained = i16::from(level.saturating_sub(1));
+
+        StatLimits {
+            budget: self.stat_budget + POINTS_PER_LEVEL * levels_gained,
+            max: self.max_stat + levels_gained / 2,
+        }
+    }
+
+    pub fn choice_timeout(&self) -> Duration {
+        Duration::from_secs(self.choice_timeout)
+    }
+
+    pub fn continue_timeout(&self) -> Duration {
+        Duration::from_secs(self.continue_timeout)
+    }
+
+    /**
+     * Keeps every rule to something the game can still be played by
+     */
+    pub fn validate(&self) -> Result<(), Error> {
+        let checks = [
+            ("starting_health", i64::from(self.starting_health), 1, 100),
+            ("stat_budget", i64::from(self.stat_budget), 1, 20),
+            ("max_stat", i64::from(self.max_stat), 1, 20),
+            ("die_sides", i64::from(self.die_sides), 4, 100),
+            (
+                "critical_multiplier",
+                i64::from(self.critical_multiplier),
+                1,
+                5,
+            ),
+            ("choice_timeout", self.choice_timeout as i64, 30, 900),
+            ("continue_timeout", self.continue_timeout as i64, 30, 900),
+        ];
+
+        let issues: Vec<String> = checks
+            .into_iter()
+            .filter(|(_, value, min, max)| value < min || value > max)
+            .map(|(name, value, min, max)| {
+                format!(
+                    "{} is {}, it should be from {} to {}",
+                    name, value, min, max
+                )
+            })
+            .collect();
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Validation(issues))
+        }
+    }
+
+    /**
+     * Changes a single rule by its name, leaving the ruleset as it was if the result isn't valid
+     */
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), Error> {
+        let value: i64 = value
+            .parse()
+            .map_err(|_| Error::Plain("Rules can only be set to whole numbers"))?;
+
+        let mut rules = serde_json::to_value(&*self)?;
+        match rules.get_mut(name) {
+            Some(rule) => *rule = Value::from(value),
+            None => return Err(Error::Plain("There is no rule by that name")),
+        }
+
+        let rules: Ruleset = serde_json::from_value(rules)
+            .map_err(|_| Error::Plain("That value is out of range"))?;
+        rules.validate()?;
+
+        *self = rules;
+        Ok(())
+    }
+}
+
+impl From<&Ruleset> for CreateEmbed {
+    fn from(rules: &Ruleset) -> Self {
+        let preset = Ruleset::PRESETS
+            .into_iter()
+            .find(|name| Ruleset::preset(name).as_ref() == Some(rules))
+            .unwrap_or("custom");
+
+        CreateEmbed::new()
+            .title("Zumbor rules")
+            .description(format!("Playing by {} rules", preset))
+            .field("starting_health", rules.starting_health.to_string(), true)
+            .field("stat_budget", rules.stat_budget.to_string(), true)
+            .field("max_stat", rules.max_stat.to_string(), true)
+            .field("die_sides", rules.die_sides.to_string(), true)
+            .field(
+                "critical_multiplier",
+                rules.critical_multiplier.to_string(),
+                true,
+            )
+            .field("choice_timeout", format!("{}s", rules.choice_timeout), true)
+            .field(
+                "continue_timeout",
+                format!("{}s", rules.continue_timeout),
+                true,
+            )
+    }
+}
+
+/**
+ * The guild's rules, or the standard ones outside of a guild or for guilds that never changed them
+ */
+pub async fn for_guild(ctx: &Context, guild_id: Option<GuildId>) -> Ruleset {
+    let Some(guild_id) = guild_id else {
+        return Ruleset::default();
+    };
+
+    let data = ctx.data.read().await;
+    let Some(storage_client) = data.get::<StorageClient>() else {
+        return Ruleset::default();
+    };
+
+    // Rules edited by hand in the bucket could be anything, so they are checked on the way in
+    storage_client
+        .get_json::<Ruleset>(&format!("{}{}", RULES_PREFIX, guild_id))
+        .await
+        .ok()
+        .filter(|rules| rules.validate().is_ok())
+        .unwrap_or_default()
+}
+
+async fn save(ctx: &Context, guild_id: GuildId, rules: &Ruleset) -> Result<(), Error> {
+    let data = ctx.data.read().await;
+    let storage_client = data
+        .get::<StorageClient>()
+        .ok_or(Error::Plain("Storage client not accessible!"))?;
+
+    storage_client
+        .create_json(
+            &format!("{}{}.json", RULES_PREFIX, guild_id),
+            serde_json::to_string(rules)?,
+        )
+        .await
+}
+
+/**
+ * Shows the guild's rules, admins can change them with `rules set <rule> <value>` or
+ * `rules preset <standard|casual|hardcore>`
+ */
+pub async fn rules(ctx: &Context, msg: &Message, mut args: Args) -> Result<(), Error> {
+    let Some(guild_id) = msg.guild_id else {
+        msg.reply(ctx, "Rules only apply in a server").await?;
+        return Err(Error::Plain("Rules requested outside a guild"));
+    };
+
+    let mut rules = for_guild(ctx, Some(guild_id)).await;
+
+    let subcommand = args.single::<String>().ok();
+    if subcommand.is_some() && !permissions::is_admin(ctx, msg).await {
+        msg.reply(ctx, "Only admins can change the rules you pleb")
+            .await?;
+        return Err(Error::Plain("Rules changed by a non admin"));
+    }
+
+    let res = match subcommand.as_deref() {
+        None => Ok(()),
+        Some("set") => match (args.single::<String>(), args.single::<String>()) {
+            (Ok(name), Ok(value)) => rules.set(&name, &value),
+            _ => Err(Error::Plain("Usage: rules set <rule> <value>")),
+        },
+        Some("preset") => match args
+            .single::<String>()
+            .ok()
+            .and_then(|name| Ruleset::preset(&name))
+        {
+            Some(preset) => {
+                rules = preset;
+                Ok(())
+            }
+            None => Err(Error::Plain("Presets are standard, casual and hardcore")),
+        },
+        Some(_) => Err(Error::Plain("Rules can be set or changed to a preset")),
+    };
+
+    if let Err(err) = res {
+        msg.reply(ctx, err.to_string()).await?;
+        return Err(err);
+    }
+
+    if subcommand.is_some() {
+        save(ctx, guild_id, &rules).await?;
+    }
+
+    msg.channel_id
+        .send_message(ctx, CreateMessage::new().embed((&rules).into()))
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_are_valid() {
+        for name in Ruleset::PRESETS {
+            assert!(
+                Ruleset::preset(name).unwrap().validate().is_ok(),
+                "{}",
+                name
+            );
+        }
+        assert!(Ruleset::preset("impossible").is_none());
+    }
+
+    #[test]
+    fn validate_lists_every_rule_out_of_range() {
+        let rules = Ruleset {
+            starting_health: 0,
+            die_sides: 2,
+            choice_timeout: 5,
+            ..Ruleset::default()
+        };
+
+        match rules.validate() {
+            Err(Error::Validation(issues)) => assert_eq!(issues.len(), 3),
+            other => panic!("Expected validation issues, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn set_changes_a_single_rule() {
+        let mut rules = Ruleset::default();
+        rules.set("die_sides", "12").unwrap();
+
+        assert_eq!(
+            rules,
+            Ruleset {
+                die_sides: 12,
+                ..Ruleset::default()
+            }
+        );
+    }
+
+    #[test]
+    fn set_leaves_the_rules_alone_when_it_fails() {
+        let mut rules = Ruleset::default();
+
+        assert!(rules.set("die_sides", "2").is_err());
+        assert!(rules.set("die_sides", "twelve").is_err());
+        assert!(rules.set("starting_health", "-40000").is_err());
+        assert!(rules.set("luck", "7").is_err());
+        assert_eq!(rules, Ruleset::default());
+    }
+}
@@ -0,0 +1,252 @@
+use std::{
+    fmt,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serenity::{
+    all::{ChannelId, CreateEmbed, CreateMessage, UserId},
+    framework::standard::Args,
+    model::prelude::Message,
+    prelude::{Context, RwLock, TypeMap},
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{errors::Error, utilities::permissions};
+
+use super::ZumborInstances;
+
+/// Tells sessions for the same user apart, so a stale guard never ends its replacement
+static NEXT_SESSION_ID: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Clone, Copy, Debug)]
+pub enum SessionKind {
+    Adventure,
+    Party,
+    Daily,
+    Duel,
+    Boss,
+    Import,
+    Export,
+}
+
+impl fmt::Display for SessionKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SessionKind::Adventure => write!(f, "Adventure"),
+            SessionKind::Party => write!(f, "Party"),
+            SessionKind::Daily => write!(f, "Daily challenge"),
+            SessionKind::Duel => write!(f, "Duel"),
+            SessionKind::Boss => write!(f, "Boss fight"),
+            SessionKind::Import => write!(f, "Character import"),
+            SessionKind::Export => write!(f, "Character export"),
+        }
+    }
+}
+
+/// Something a user is busy doing with their character
+#[derive(Clone, Debug)]
+pub struct Session {
+    pub id: u64,
+    pub kind: SessionKind,
+    pub channel_id: ChannelId,
+    /// Seconds since the unix epoch
+    pub started: u64,
+    token: CancellationToken,
+}
+
+impl Session {
+    /// Stops whatever the session is running, along with every session sharing its token
+    pub fn kill(&self) {
+        self.token.cancel();
+    }
+}
+
+/**
+ * Holds the user's session for as long as it lives, ending it however the holder goes out of scope
+ */
+pub struct SessionGuard {
+    id: u64,
+    user_id: UserId,
+    token: CancellationToken,
+    data: Arc<RwLock<TypeMap>>,
+}
+
+impl SessionGuard {
+    pub fn user_id(&self) -> UserId {
+        self.user_id
+    }
+
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /**
+     * Runs the future until it finishes or the session is killed
+     */
+    pub async fn until_killed<T>(
+        &self,
+        future: impl Future<Output = Result<T, Error>>,
+    ) -> Result<T, Error> {
+        until_killed(self.token(), future).await
+    }
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        let (id, user_id) = (self.id, self.user_id);
+
+        // Dropping can't wait on the lock, so it is only handed to a task when it is held elsewhere
+        match self.data.try_write() {
+            Ok(mut data) => end(&mut data, id, user_id),
+            Err(_) => {
+                let data = self.data.clone();
+                tokio::spawn(async move { end(&mut *data.write().await, id, user_id) });
+            }
+        }
+    }
+}
+
+fn end(data: &mut TypeMap, id: u64, user_id: UserId) {
+    if let Some(instances) = data.get_mut::<ZumborInstances>() {
+        instances.remove(user_id, id);
+    }
+}
+
+/**
+ * Starts a session for the user, failing if they are already in one. Sessions that should be
+ * killed together share a token
+ */
+pub async fn claim(
+    ctx: &Context,
+    user_id: UserId,
+    kind: SessionKind,
+    channel_id: ChannelId,
+    token: CancellationToken,
+) -> Result<SessionGuard, Error> {
+    let id = NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed);
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default();
+
+    let mut data = ctx.data.write().await;
+    let instances = data
+        .get_mut::<ZumborInstances>()
+        .ok_or(Error::Plain("Zumbor instances not accessible!"))?;
+
+    instances.add(
+        user_id,
+        Session {
+            id,
+            kind,
+            channel_id,
+            started,
+            token: token.clone(),
+        },
+    )?;
+
+    Ok(SessionGuard {
+        id,
+        user_id,
+        token,
+        data: ctx.data.clone(),
+    })
+}
+
+/**
+ * Runs the future until it finishes or the token is cancelled
+ */
+pub async fn until_killed<T>(
+    token: CancellationToken,
+    future: impl Future<Output = Result<T, Error>>,
+) -> Result<T, Error> {
+    tokio::select! {
+        res = future => res,
+        _ = token.cancelled() => Err(Error::Plain("The session was killed")),
+    }
+}
+
+/**
+ * Lists everyone's sessions, or kills the mentioned user's with `sessions kill @user`
+ */
+pub async fn sessions(ctx: &Context, msg: &Message, mut args: Args) -> Result<(), Error> {
+    if !permissions::is_admin(ctx, msg).await {
+        msg.reply(ctx, "Only admins can manage sessions you pleb")
+            .await?;
+        return Err(Error::Plain("Sessions requested by a non admin"));
+    }
+
+    if let Ok("kill") = args.single::<String>().as_deref() {
+        let Some(user) = msg.mentions.first() else {
+            msg.reply(ctx, "Mention whose session to kill").await?;
+            return Err(Error::Plain("No user mentioned"));
+        };
+
+        let session = ctx
+            .data
+            .read()
+            .await
+            .get::<ZumborInstances>()
+            .and_then(|instances| instances.get(user.id).cloned());
+
+        match session {
+            Some(session) => {
+                session.kill();
+                msg.reply(ctx, format!("Killed {}'s {}", user.name, session.kind))
+                    .await?;
+            }
+            None => {
+                msg.reply(ctx, format!("{} isn't in a session", user.name))
+                    .await?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    let sessions: Vec<(UserId, Session)> = ctx
+        .data
+        .read()
+        .await
+        .get::<ZumborInstances>()
+        .map(|instances| {
+            instances
+                .iter()
+                .map(|(user_id, session)| (*user_id, session.clone()))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let description = if sessions.is_empty() {
+        "Nobody is playing".to_string()
+    } else {
+        sessions
+            .iter()
+            .map(|(user_id, session)| {
+                format!(
+                    "<@{}> {} in <#{}> since <t:{}:R>",
+                    user_id, session.kind, session.channel_id, session.started
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    };
+
+    msg.channel_id
+        .send_message(
+            ctx,
+            CreateMessage::new().embed(
+                CreateEmbed::new()
+                    .title("Zumbor sessions")
+                    .description(description),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,352 @@
+use std::{
+    env,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use openssl::{hash::MessageDigest, memcmp, pkey::PKey, sign::Signer};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use serenity::{
+    all::{CreateAttachment, CreateMessage},
+    model::prelude::Message,
+    prelude::Context,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{errors::Error, storage::StorageClient};
+
+use super::{
+    attributes::Attribute,
+    effects::Effectable,
+    migration::Versioned,
+    player::{self, Player},
+    session::{self, SessionKind},
+};
+
+/// The secret sheets are signed with, every bot instance that should accept a sheet needs the same one
+const SECRET_VAR: &str = "ZUMBOR_SHEET_SECRET";
+
+/// Far larger than any character, anything bigger isn't worth downloading
+const MAX_SHEET_SIZE: u32 = 256 * 1024;
+
+/**
+ * A character that can be carried between buckets, signed so it can't be edited along the way
+ */
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Sheet {
+    pub player: Value,
+    /// When the sheet was made in seconds since the unix epoch, so it can't be imported over a later save
+    pub issued: u64,
+    /// Hex encoded HMAC-SHA256 of the player and when the sheet was issued
+    pub signature: String,
+}
+
+impl Sheet {
+    pub fn new(player: &Player) -> Result<Sheet, Error> {
+        let player = serde_json::to_value(player)?;
+        let issued = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+        let signature = sign(&player, issued)?;
+
+        Ok(Sheet {
+            player,
+            issued,
+            signature,
+        })
+    }
+
+    /**
+     * Checks the signature before bringing the character up to the current version
+     */
+    pub fn verify(self) -> Result<Player, Error> {
+        let expected = sign(&self.player, self.issued)?;
+        if expected.len() != self.signature.len()
+            || !memcmp::eq(expected.as_bytes(), self.signature.as_bytes())
+        {
+            return Err(Error::Plain("The sheet's signature doesn't match"));
+        }
+
+        Player::from_versioned(self.player)
+    }
+}
+
+/**
+ * Signs the player and when the sheet was issued as serde_json writes values, with sorted keys,
+ * so the signature doesn't depend on the order the fields were written in
+ */
+fn sign(player: &Value, issued: u64) -> Result<String, Error> {
+    let secret = env::var(SECRET_VAR).map_err(|_| Error::Plain("No secret to sign sheets with"))?;
+
+    let key = PKey::hmac(secret.as_bytes())?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    let payload = json!({ "player": player, "issued": issued });
+    signer.update(payload.to_string().as_bytes())?;
+
+    Ok(signer
+        .sign_to_vec()?
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// The character written out for reading rather than importing
+fn card(player: &Player) -> String {
+    let mut lines = vec![
+        format!("{} - {}", player.name, player.description),
+        format!(
+            "Level {}{} | Health {} | Score {} | Gold {}",
+            player.level,
+            player
+                .class
+                .as_ref()
+                .map(|class| format!(" {}", class.name))
+                .unwrap_or_default(),
+            player.get_health(),
+            player.score,
+            player.gold
+        ),
+        Attribute::VALUES
+            .into_iter()
+            .map(|attr| format!("{} {}", attr, player.stats.get(attr.clone())))
+            .collect::<Vec<String>>()
+            .join(" | "),
+    ];
+
+    if !player.effects.is_empty() {
+        lines.push(format!(
+            "Effects: {}",
+            player
+                .effects
+                .iter()
+                .map(|effect| format!("{} {} ({})", effect.name, effect.kind, effect.potency))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ));
+    }
+
+    if !player.inventory.is_empty() {
+        lines.push(format!(
+            "Bag: {}",
+            player
+                .inventory
+                .iter()
+                .map(|item| item.name.clone())
+                .collect::<Vec<String>>()
+                .join(", ")
+        ));
+    }
+
+    format!("```\n{}\n```", lines.join("\n"))
+}
+
+/**
+ * Sends the author their character as a card and a signed sheet they can import elsewhere
+ */
+pub async fn export(ctx: &Context, msg: &Message) -> Result<(), Error> {
+    // The character is saved again below, nothing running should have a newer copy of it
+    let _session = match session::claim(
+        ctx,
+        msg.author.id,
+        SessionKind::Export,
+        msg.channel_id,
+        CancellationToken::new(),
+    )
+    .await
+    {
+        Ok(session) => session,
+        Err(err) => {
+            msg.reply(ctx, "Finish what you're playing before exporting")
+                .await?;
+            return Err(err);
+        }
+    };
+
+    let player = match player::storage::load_save(ctx, &msg.author.tag()).await {
+        Ok(player) => player,
+        Err(err) => {
+            msg.reply(ctx, "You don't have a character to export")
+                .await?;
+            return Err(err);
+        }
+    };
+
+    // Saves from before characters had ids are given one when loaded, it has to be kept to match the sheet
+    player.save(ctx).await?;
+    let sheet = Sheet::new(&player)?;
+
+    msg.author
+        .direct_message(
+            ctx,
+            CreateMessage::new()
+                .content(card(&player))
+                .add_file(CreateAttachment::bytes(
+                    serde_json::to_vec_pretty(&sheet)?,
+                    format!("{}.json", player.name),
+                )),
+        )
+        .await?;
+
+    msg.reply(ctx, "Check your DMs").await?;
+
+    Ok(())
+}
+
+/**
+ * Restores the author's character from a sheet attached to the message, replacing their current save
+ */
+pub async fn import(ctx: &Context, msg: &Message) -> Result<(), Error> {
+    let Some(attachment) = msg.attachments.first() else {
+        msg.reply(ctx, "Attach the sheet you were sent by export")
+            .await?;
+        return Err(Error::Plain("No sheet attached"));
+    };
+
+    if attachment.size > MAX_SHEET_SIZE {
+        msg.reply(ctx, "That's far too big to be a character sheet")
+            .await?;
+        return Err(Error::Plain("Sheet is too large"));
+    }
+
+    // Nothing can be running that would save over the imported character
+    let _session = match session::claim(
+        ctx,
+        msg.author.id,
+        SessionKind::Import,
+        msg.channel_id,
+        CancellationToken::new(),
+    )
+    .await
+    {
+        Ok(session) => session,
+        Err(err) => {
+            msg.reply(ctx, "Finish what you're playing before importing")
+                .await?;
+            return Err(err);
+        }
+    };
+
+    let bytes = attachment.download().await?;
+    let sheet = serde_json::from_slice::<Sheet>(&bytes)
+        .map_err(Error::from)
+        .and_then(|sheet| Ok((sheet.issued, sheet.verify()?)));
+
+    let (issued, player) = match sheet {
+        Ok((issued, player)) if player.tag == msg.author.tag() => (issued, player),
+        Ok(_) => {
+            msg.reply(ctx, "That character belongs to someone else")
+                .await?;
+            return Err(Error::Plain("Sheet belongs to another user"));
+        }
+        Err(err) => {
+            msg.reply(ctx, "That sheet isn't valid, it may have been edited")
+                .await?;
+            return Err(err);
+        }
+    };
+
+    // A sheet can only carry a character forward, never back past its save or out of its grave
+    let (current, graves) = {
+        let data = ctx.data.read().await;
+        let storage_client = data
+            .get::<StorageClient>()
+            .ok_or(Error::Plain("Storage client not accessible!"))?;
+
+        (
+            player::storage::load_save_from(storage_client, &player.tag).await,
+            player::storage::load_graves(storage_client, &player.tag).await?,
+        )
+    };
+
+    if graves.contains(&player.character_id) {
+        msg.reply(ctx, format!("{} is dead and buried", player.name))
+            .await?;
+        return Err(Error::Plain("Sheet is for a dead character"));
+    }
+
+    match current {
+        Ok(current) if current.saved_at > issued => {
+            msg.reply(
+                ctx,
+                "Your character has been saved since that sheet was made",
+            )
+            .await?;
+            return Err(Error::Plain("Sheet is older than the current save"));
+        }
+        Err(err) if !err.is_not_found() => return Err(err),
+        _ => (),
+    }
+
+    player.save(ctx).await?;
+
+    msg.reply(ctx, format!("Welcome back {}", player.name))
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::player::{stats::Stats, PlayerDetails};
+    use super::*;
+
+    fn sheet() -> Sheet {
+        env::set_var(SECRET_VAR, "test secret");
+
+        let player = Player::new(
+            "tester".to_string(),
+            PlayerDetails {
+                name: "Tester".to_string(),
+                description: "Travelling".to_string(),
+            },
+            Stats::default(),
+        );
+        Sheet::new(&player).unwrap()
+    }
+
+    #[test]
+    fn untampered_sheets_verify() {
+        let player = sheet().verify().unwrap();
+
+        assert_eq!(player.tag, "tester");
+        assert_eq!(player.name, "Tester");
+    }
+
+    #[test]
+    fn sheets_verify_whatever_order_their_fields_are_in() {
+        let sheet = sheet();
+        let reordered: Value = serde_json::from_str(&sheet.player.to_string()).unwrap();
+
+        assert!(Sheet {
+            player: reordered,
+            ..sheet
+        }
+        .verify()
+        .is_ok());
+    }
+
+    #[test]
+    fn tampered_players_are_rejected() {
+        let mut sheet = sheet();
+        sheet.player["health"] = json!(100);
+
+        assert!(sheet.verify().is_err());
+    }
+
+    #[test]
+    fn tampered_issue_times_are_rejected() {
+        let mut sheet = sheet();
+        sheet.issued += 1;
+
+        assert!(sheet.verify().is_err());
+    }
+
+    #[test]
+    fn tampered_signatures_are_rejected() {
+        let mut sheet = sheet();
+        sheet.signature.truncate(10);
+
+        assert!(sheet.verify().is_err());
+    }
+}
@@ -0,0 +1,121 @@
+use std::fmt::{self, Display};
+
+use serde::{Deserialize, Serialize};
+use serenity::{
+    all::{Colour, CreateEmbed},
+    prelude::Context,
+};
+
+use crate::{errors::Error, storage::StorageClient};
+
+use super::{
+    effects::{BaseHealthEffect, Effectable},
+    items::Item,
+    player::Player,
+};
+
+/// Shops are stored as `zumbor/shops/{id}.json` and referred to by id from encounter results
+pub const SHOP_PREFIX: &str = "zumbor/shops/";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Shop {
+    pub name: String,
+    pub description: String,
+    pub stock: Vec<Offer>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Offer {
+    pub price: u32,
+    pub ware: Ware,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum Ware {
+    Item(Item),
+    /// Restores the given amount of health
+    Heal(i16),
+    /// Clears every lingering effect, good or bad
+    Cleanse,
+}
+
+impl Display for Ware {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ware::Item(item) => write!(f, "{}", item.name),
+            Ware::Heal(amount) => write!(f, "Heal {} health", amount),
+            Ware::Cleanse => write!(f, "Cleanse all effects"),
+        }
+    }
+}
+
+impl Ware {
+    pub fn kind(&self) -> String {
+        match self {
+            Ware::Item(item) => item.kind.to_string(),
+            Ware::Heal(_) => "Healing".to_string(),
+            Ware::Cleanse => "Cleansing".to_string(),
+        }
+    }
+
+    fn deliver(&self, player: &mut Player) {
+        match self {
+            Ware::Item(item) => player.give_item(item.clone()),
+            Ware::Heal(amount) => player.affect_health(&BaseHealthEffect { potency: *amount }),
+            Ware::Cleanse => player.clear_effects(),
+        }
+    }
+}
+
+impl From<&Offer> for CreateEmbed {
+    fn from(offer: &Offer) -> Self {
+        let embed = match &offer.ware {
+            Ware::Item(item) => CreateEmbed::from(item),
+            Ware::Heal(_) => CreateEmbed::new()
+                .title(offer.ware.to_string())
+                .description("A restorative tonic")
+                .colour(Colour::FABLED_PINK),
+            Ware::Cleanse => CreateEmbed::new()
+                .title(offer.ware.to_string())
+                .description("Washes away every lingering effect, the good along with the bad")
+                .colour(Colour::LIGHT_GREY),
+        };
+
+        embed.field("Price", format!("{} gold", offer.price), true)
+    }
+}
+
+impl Shop {
+    /**
+     * Takes the price of the offer at the given position from the player and hands them the ware
+     */
+    pub fn buy(&self, player: &mut Player, index: usize) -> Result<&Offer, Error> {
+        let offer = self
+            .stock
+            .get(index)
+            .ok_or(Error::Plain("The shop has no such offer"))?;
+
+        player.gold = player
+            .gold
+            .checked_sub(offer.price)
+            .ok_or(Error::Plain("Not enough gold"))?;
+        offer.ware.deliver(player);
+
+        Ok(offer)
+    }
+}
+
+pub async fn load(ctx: &Context, id: &str) -> Result<Shop, Error> {
+    let data = ctx.data.read().await;
+    let storage_client = data
+        .get::<StorageClient>()
+        .ok_or(Error::Plain("Storage client not accessible!"))?;
+
+    load_from(storage_client, id).await
+}
+
+pub async fn load_from(storage_client: &StorageClient, id: &str) -> Result<Shop, Error> {
+    storage_client
+        .get_json(&format!("{}{}", SHOP_PREFIX, id))
+        .await
+}
@@ -0,0 +1,434 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::{self, Display},
+};
+
+use rand::{seq::SliceRandom, Rng};
+
+use super::{
+    attributes::Attribute,
+    combat::{Combat, CombatAction, Enemy, EnemyTemplate},
+    effects::{BaseEffect, Effectable, LingeringEffectName},
+    encounter::{
+        self, catalogue::EncounterCatalogue, EncounterOption, EncounterResult, EncounterResultKind,
+        ResolvedResult,
+    },
+    player::{
+        experience::{self, StatLimits},
+        stats::Stats,
+        Player, PlayerDetails,
+    },
+    rules::Ruleset,
+};
+
+/// Runs that survive this many encounters are counted as survivors and stopped
+pub const MAX_ENCOUNTERS: usize = 100;
+
+/// Encounter counts the survival curve is reported at
+pub const CHECKPOINTS: [usize; 5] = [5, 10, 25, 50, MAX_ENCOUNTERS];
+
+/// How a simulated player picks between the options of an encounter
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Strategy {
+    /// The option testing the player's best stat
+    HighestStat,
+    Random,
+    /// The option with the best expected change to the player's health
+    GreedyHealth,
+}
+
+impl Strategy {
+    pub const VALUES: [Strategy; 3] = [
+        Strategy::HighestStat,
+        Strategy::Random,
+        Strategy::GreedyHealth,
+    ];
+
+    fn choose<'a>(
+        &self,
+        player: &Player,
+        options: &[(&'a String, &'a EncounterOption)],
+        rng: &mut impl Rng,
+    ) -> Option<&'a String> {
+        let (key, _) = match self {
+            Strategy::HighestStat => options.iter().max_by_key(|(_, option)| {
+                player.stats.get(option.stat.clone()) + player.equipment_bonus(option.stat.clone())
+            }),
+            Strategy::Random => options.choose(rng),
+            Strategy::GreedyHealth => options.iter().max_by(|(_, a), (_, b)| {
+                expected_health(player, a).total_cmp(&expected_health(player, b))
+            }),
+        }?;
+
+        Some(*key)
+    }
+
+    fn act(&self, player: &Player, combat: &Combat, rng: &mut impl Rng) -> CombatAction {
+        match self {
+            Strategy::HighestStat => CombatAction::Attack,
+            Strategy::Random => *CombatAction::VALUES
+                .choose(rng)
+                .expect("There are combat actions to choose from"),
+            // Gets out while another strike can still be taken
+            Strategy::GreedyHealth => {
+                if player.health <= combat.enemy.template.damage {
+                    CombatAction::Flee
+                } else {
+                    CombatAction::Attack
+                }
+            }
+        }
+    }
+}
+
+impl Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Strategy::HighestStat => write!(f, "Highest stat"),
+            Strategy::Random => write!(f, "Random"),
+            Strategy::GreedyHealth => write!(f, "Greedy health"),
+        }
+    }
+}
+
+/// A typical way of spending the starting stat points
+pub struct Build {
+    pub name: String,
+    pub stats: Stats,
+}
+
+impl Build {
+    /**
+     * An even spread, followed by every stat taken as high as a new character can have it
+     */
+    pub fn typical() -> Vec<Build> {
+        let limits = StatLimits::for_level(experience::starting_level());
+
+        let mut balanced = Stats::default();
+        for point in 0..limits.budget {
+            let attribute = &Attribute::VALUES[point as usize % Attribute::VALUES.len()];
+            *balanced.get_mut(attribute.clone()) += 1;
+        }
+
+        let mut builds = vec![Build {
+            name: "Balanced".to_string(),
+            stats: balanced,
+        }];
+
+        for attribute in Attribute::VALUES {
+            let mut stats = Stats::default();
+            *stats.get_mut(attribute.clone()) = limits.max.min(limits.budget);
+            builds.push(Build {
+                name: format!("{} focused", attribute),
+                stats,
+            });
+        }
+
+        builds
+    }
+
+    fn player(&self) -> Player {
+        Player::new(
+            "simulated".to_string(),
+            PlayerDetails {
+                name: self.name.clone(),
+                description: "A simulated adventurer".to_string(),
+            },
+            self.stats.clone(),
+        )
+    }
+}
+
+/**
+ * How likely the player is to succeed at the option, going through every side of the die
+ */
+pub fn success_chance(player: &Player, option: &EncounterOption) -> f64 {
+    let mut option = option.clone();
+
+    let sides = player.rules.die_sides;
+    let successes = (1..=sides)
+        .filter(|&roll| {
+            let result = option.test(&player.roll_result(roll, &option.stat));
+            matches!(result.kind, EncounterResultKind::Success(_))
+        })
+        .count();
+
+    successes as f64 / f64::from(sides)
+}
+
+fn health_change(result: &EncounterResult) -> f64 {
+    let base = match &result.base_effect {
+        Some(BaseEffect::Health(effect)) => effect.potency,
+        _ => 0,
+    };
+
+    let lingering = match &result.lingering_effect {
+        Some(effect) => match effect.name {
+            LingeringEffectName::Poison => -effect.potency * effect.duration.max(1),
+            LingeringEffectName::Regenerate => effect.potency * effect.duration.max(1),
+            _ => 0,
+        },
+        None => 0,
+    };
+
+    f64::from(base + lingering)
+}
+
+fn expected_health(player: &Player, option: &EncounterOption) -> f64 {
+    let chance = success_chance(player, option);
+
+    chance * health_change(&option.success) + (1.0 - chance) * health_change(&option.fail)
+}
+
+pub struct RunOutcome {
+    pub encounters: usize,
+    pub score: u16,
+    pub died: bool,
+}
+
+/**
+ * Plays a run the way the game loop does, with the strategy making every choice. Results fight the
+ * enemies found in `enemies`, the rest play out without a fight
+ */
+pub fn simulate_run(
+    catalogue: &EncounterCatalogue,
+    enemies: &HashMap<String, EnemyTemplate>,
+    strategy: Strategy,
+    build: &Build,
+    rng: &mut impl Rng,
+) -> RunOutcome {
+    let mut player = build.player();
+    let mut encounters = 0;
+
+    while encounters < MAX_ENCOUNTERS {
+        let entry = match player.next_encounter.take() {
+            Some(reference) => catalogue
+                .find(&reference)
+                .or_else(|| catalogue.choose(&player, rng)),
+            None => catalogue.choose(&player, rng),
+        };
+        let Some(entry) = entry else {
+            break;
+        };
+
+        player.remember_encounter(entry.path.clone());
+        let mut encounter = entry.encounter.clone();
+
+        let options: Vec<(&String, &EncounterOption)> = entry
+            .encounter
+            .options
+            .iter()
+            .filter(|(_, option)| option.is_available_to(&player))
+            .collect();
+
+        // Stunned players don't get to choose, the same as in the game
+        let strategy = if player.has_effect(&LingeringEffectName::Stun) {
+            Strategy::Random
+        } else {
+            strategy
+        };
+        let Some(choice) = strategy.choose(&player, &options, rng) else {
+            break;
+        };
+        player.consume_effect(&LingeringEffectName::Stun);
+
+        let option = encounter
+            .get_option(choice)
+            .expect("Choices are limited to encounter option keys");
+        let roll = player.roll_stat(&option.stat, rng);
+
+        let ResolvedResult {
+            result, experience, ..
+        } = encounter::resolve_result(&mut player, option, &roll, rng);
+        encounters += 1;
+
+        let template = result.enemy.as_ref().and_then(|id| enemies.get(id));
+        if let (Some(template), true) = (template, player.health > 0) {
+            let mut combat = Combat::new(Enemy::new(template.clone()));
+            while combat.outcome.is_none() {
+                let action = strategy.act(&player, &combat, rng);
+                combat.round(&mut player, action, rng);
+            }
+            combat.reward(&mut player);
+        }
+
+        if player.health <= 0 {
+            return RunOutcome {
+                encounters,
+                score: player.score,
+                died: true,
+            };
+        }
+
+        let levels = player.gain_experience(experience);
+        if levels > 0 {
+            level_up(
+                &mut player,
+                experience::POINTS_PER_LEVEL * i16::from(levels),
+            );
+        }
+    }
+
+    RunOutcome {
+        encounters,
+        score: player.score,
+        died: false,
+    }
+}
+
+/**
+ * Spends new stat points on the player's best stats, as far as their level allows
+ */
+fn level_up(player: &mut Player, points: i16) {
+    let limits = StatLimits::for_level(player.level);
+
+    let mut attributes = Attribute::VALUES.to_vec();
+    attributes.sort_by_key(|attribute| std::cmp::Reverse(player.stats.get(attribute.clone())));
+
+    let mut remaining = points;
+    for attribute in attributes {
+        while remaining > 0 && player.stats.get(attribute.clone()) < limits.max {
+            *player.stats.get_mut(attribute.clone()) += 1;
+            remaining -= 1;
+        }
+    }
+}
+
+pub struct SimulationReport {
+    pub strategy: Strategy,
+    pub build: String,
+    pub runs: usize,
+    /// How many runs were still going at each checkpoint
+    pub survivors: [usize; CHECKPOINTS.len()],
+    pub total_score: u64,
+    pub total_encounters: usize,
+}
+
+impl SimulationReport {
+    pub fn average_score(&self) -> f64 {
+        self.total_score as f64 / self.runs.max(1) as f64
+    }
+
+    pub fn average_encounters(&self) -> f64 {
+        self.total_encounters as f64 / self.runs.max(1) as f64
+    }
+
+    /// The survival curve, as the percentage of runs alive at each checkpoint
+    pub fn survival(&self) -> Vec<f64> {
+        self.survivors
+            .iter()
+            .map(|&survivors| 100.0 * survivors as f64 / self.runs.max(1) as f64)
+            .collect()
+    }
+}
+
+/**
+ * Plays the given number of runs with every strategy and typical build
+ */
+pub fn simulate(
+    catalogue: &EncounterCatalogue,
+    enemies: &HashMap<String, EnemyTemplate>,
+    runs: usize,
+    rng: &mut impl Rng,
+) -> Vec<SimulationReport> {
+    let mut reports = Vec::new();
+
+    for strategy in Strategy::VALUES {
+        for build in Build::typical() {
+            let mut report = SimulationReport {
+                strategy,
+                build: build.name.clone(),
+                runs,
+                survivors: [0; CHECKPOINTS.len()],
+                total_score: 0,
+                total_encounters: 0,
+            };
+
+            for _ in 0..runs {
+                let outcome = simulate_run(catalogue, enemies, strategy, &build, rng);
+
+                report.total_score += u64::from(outcome.score);
+                report.total_encounters += outcome.encounters;
+                for (survivors, checkpoint) in report.survivors.iter_mut().zip(CHECKPOINTS) {
+                    if !outcome.died || outcome.encounters > checkpoint {
+                        *survivors += 1;
+                    }
+                }
+            }
+
+            reports.push(report);
+        }
+    }
+
+    reports
+}
+
+/// The ids of every enemy the catalogue's results lead into a fight with
+pub fn enemy_ids(catalogue: &EncounterCatalogue) -> BTreeSet<String> {
+    catalogue
+        .entries
+        .iter()
+        .flat_map(|entry| entry.encounter.options.values())
+        .flat_map(|option| [&option.success, &option.fail])
+        .filter_map(|result| result.enemy.clone())
+        .collect()
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ThresholdIssue {
+    /// Only a natural twenty passes, whatever the build
+    NeverMet,
+    /// Only a natural one fails, whatever the build
+    AlwaysMet,
+}
+
+impl Display for ThresholdIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ThresholdIssue::NeverMet => write!(f, "never met without a critical"),
+            ThresholdIssue::AlwaysMet => write!(f, "always met unless critically failed"),
+        }
+    }
+}
+
+pub struct ThresholdFlag {
+    pub path: String,
+    pub option: String,
+    pub threshold: u8,
+    pub issue: ThresholdIssue,
+}
+
+/**
+ * Finds the options whose thresholds are out of reach, or a formality, for every typical build
+ */
+pub fn check_thresholds(catalogue: &EncounterCatalogue) -> Vec<ThresholdFlag> {
+    let players: Vec<Player> = Build::typical().iter().map(Build::player).collect();
+    let crit = 1.0 / f64::from(Ruleset::default().die_sides);
+
+    let mut flags = Vec::new();
+    for entry in &catalogue.entries {
+        for (key, option) in &entry.encounter.options {
+            let chances: Vec<f64> = players
+                .iter()
+                .map(|player| success_chance(player, option))
+                .collect();
+
+            let issue = if chances.iter().all(|&chance| chance <= crit) {
+                ThresholdIssue::NeverMet
+            } else if chances.iter().all(|&chance| chance >= 1.0 - crit) {
+                ThresholdIssue::AlwaysMet
+            } else {
+                continue;
+            };
+
+            flags.push(ThresholdFlag {
+                path: entry.path.clone(),
+                option: key.clone(),
+                threshold: option.threshold,
+                issue,
+            });
+        }
+    }
+
+    flags
+}
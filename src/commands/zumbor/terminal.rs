@@ -0,0 +1,453 @@
+use std::io::{self, BufRead, Write};
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+use crate::{errors::Error, storage::StorageClient};
+
+use super::{
+    attributes::Attribute,
+    classes::{self, Class},
+    combat::{self, Combat, CombatAction, CombatOutcome, Enemy},
+    effects::{Effectable, LingeringEffectName},
+    encounter::{self, catalogue::EncounterCatalogue, EncounterOption, ResolvedResult},
+    events::GameEvent,
+    player::{
+        self,
+        experience::{self, POINTS_PER_LEVEL},
+        stats::Stats,
+        Player, PlayerDetails,
+    },
+    rules::Ruleset,
+    shop::{self, Shop},
+    zone::WILDS,
+};
+
+/// Who plays in the terminal when no tag is given, their save sits alongside the real ones
+pub const LOCAL_TAG: &str = "local";
+
+/**
+ * Plays the game in the terminal against whatever the storage client holds, reading every choice
+ * from the input. Returns once the player dies or rests
+ */
+pub async fn play(
+    storage_client: &StorageClient,
+    tag: &str,
+    input: &mut impl BufRead,
+) -> Result<(), Error> {
+    let catalogue = EncounterCatalogue::load(storage_client).await?;
+    if catalogue.entries.is_empty() {
+        return Err(Error::Plain("There are no encounters to play"));
+    }
+
+    let mut player = match player::storage::load_save_from(storage_client, tag).await {
+        Ok(mut player) => {
+            if player.resume_encounter() {
+                println!("{} picks up where they left off", player.name);
+            } else {
+                println!("Welcome back {}", player.name);
+            }
+            player
+        }
+        Err(err) if err.is_not_found() => create(storage_client, tag, input).await?,
+        Err(err) => return Err(err),
+    };
+
+    // Events are printed as they happen rather than going through the bus, which needs discord
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    player.events = Some(sender);
+    let mut rng = StdRng::from_entropy();
+
+    loop {
+        let entry = match player.next_encounter.take() {
+            Some(reference) => catalogue
+                .find(&reference)
+                .or_else(|| catalogue.choose(&player, &mut rng)),
+            None => catalogue.choose(&player, &mut rng),
+        }
+        .ok_or(Error::Plain("No encounter could be found for the player"))?;
+
+        player.remember_encounter(entry.path.clone());
+        player.current_encounter = Some(entry.path.clone());
+        player.save_to(storage_client).await?;
+
+        let mut encounter = entry.encounter.clone();
+        encounter.offer_class_option(&player, &mut rng);
+
+        println!("\n== {} ==\n{}\n", encounter.title, encounter.text);
+
+        let mut options: Vec<(&String, &EncounterOption)> = encounter
+            .options
+            .iter()
+            .filter(|(_, option)| option.is_available_to(&player))
+            .collect();
+        options.sort_by_key(|(key, _)| *key);
+        if options.is_empty() {
+            return Err(Error::Plain("The encounter has no options for the player"));
+        }
+
+        for (index, (key, option)) in options.iter().enumerate() {
+            println!(
+                "  {}. {} ({} {}+)",
+                index + 1,
+                key,
+                option.stat,
+                option.threshold
+            );
+        }
+
+        let choice = if player.has_effect(&LingeringEffectName::Stun) {
+            let (key, _) = options
+                .choose(&mut rng)
+                .expect("Options were checked to not be empty");
+            println!("{} is stunned and stumbles into {}", player.name, key);
+            (*key).clone()
+        } else {
+            choose(input, "Choose an option", &options)?
+        };
+        player.consume_effect(&LingeringEffectName::Stun);
+
+        let option = encounter
+            .get_option(&choice)
+            .expect("Choices are limited to encounter option keys");
+        let roll = player.roll_stat(&option.stat, &mut rng);
+        println!("{} rolled {}", player.name, roll);
+
+        let ResolvedResult {
+            result, experience, ..
+        } = encounter::resolve_result(&mut player, option, &roll, &mut rng);
+
+        println!("\n-- {} --\n{}", result.title, result.text);
+        if result.gold != 0 {
+            println!("Gold {:+}", result.gold);
+        }
+        let shop_id = result.shop.clone();
+        let enemy_id = result.enemy.clone();
+        print_events(&mut receiver);
+
+        if let (Some(enemy_id), true) = (enemy_id, player.health > 0) {
+            match combat::load_from(storage_client, &enemy_id).await {
+                Ok(template) => fight(input, &mut player, Enemy::new(template), &mut rng)?,
+                Err(err) => println!("Unable to run the fight with {}. {}", enemy_id, err),
+            }
+            print_events(&mut receiver);
+        }
+
+        if player.health <= 0 {
+            println!(
+                "\nUh oh {} died, they finished with a score of {}",
+                player.name, player.score
+            );
+            player.delete_save_from(storage_client).await?;
+            return Ok(());
+        }
+
+        let levels = player.gain_experience(experience);
+        print_events(&mut receiver);
+        if levels > 0 {
+            level_up(input, &mut player, levels)?;
+        }
+
+        if let Some(shop_id) = shop_id {
+            match shop::load_from(storage_client, &shop_id).await {
+                Ok(shop) => browse(input, &mut player, &shop)?,
+                Err(err) => println!("Unable to open shop {}. {}", shop_id, err),
+            }
+            print_events(&mut receiver);
+        }
+
+        player.current_encounter = None;
+        player.save_to(storage_client).await?;
+        println!("{}", status(&player));
+
+        loop {
+            match prompt(input, "[c]ontinue, [t]ravel or [r]est?")?.as_str() {
+                "" | "c" | "continue" => break,
+                "t" | "travel" => {
+                    travel(input, &mut player, &catalogue)?;
+                    print_events(&mut receiver);
+                    player.save_to(storage_client).await?;
+                }
+                "r" | "rest" => {
+                    println!("{} rests, their progress is saved", player.name);
+                    return Ok(());
+                }
+                _ => println!("That isn't one of the choices"),
+            }
+        }
+    }
+}
+
+/**
+ * Reads a trimmed line of input after printing the question, failing once the input runs out
+ */
+fn prompt(input: &mut impl BufRead, question: &str) -> Result<String, Error> {
+    print!("{} ", question);
+    io::stdout().flush()?;
+
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Err(Error::Plain("The input ended"));
+    }
+
+    Ok(line.trim().to_string())
+}
+
+/// Asks until one of the options is picked, by its number or its name
+fn choose(
+    input: &mut impl BufRead,
+    question: &str,
+    options: &[(&String, &EncounterOption)],
+) -> Result<String, Error> {
+    loop {
+        let answer = prompt(input, &format!("{} [1-{}]:", question, options.len()))?;
+
+        let chosen = match answer.parse::<usize>() {
+            Ok(number) => options.get(number.wrapping_sub(1)),
+            Err(_) => options
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(&answer)),
+        };
+
+        match chosen {
+            Some((key, _)) => return Ok((*key).clone()),
+            None => println!("That isn't one of the options"),
+        }
+    }
+}
+
+/// Asks until the answer is a number, with an empty answer giving none
+fn number(input: &mut impl BufRead, question: &str) -> Result<Option<usize>, Error> {
+    loop {
+        let answer = prompt(input, question)?;
+        if answer.is_empty() {
+            return Ok(None);
+        }
+
+        match answer.parse() {
+            Ok(number) => return Ok(Some(number)),
+            Err(_) => println!("That isn't a number"),
+        }
+    }
+}
+
+/// Asks for a value for every stat until they fit the check
+fn allocate_stats(
+    input: &mut impl BufRead,
+    points: i16,
+    is_valid: impl Fn(&Stats) -> bool,
+) -> Result<Stats, Error> {
+    loop {
+        println!("Spend {} stat points", points);
+
+        let mut stats = Stats::default();
+        for attribute in Attribute::VALUES {
+            let value = number(input, &format!("{}:", attribute))?.unwrap_or_default();
+            *stats.get_mut(attribute.clone()) = i16::try_from(value).unwrap_or(i16::MAX);
+        }
+
+        if is_valid(&stats) {
+            return Ok(stats);
+        }
+        println!("Those stats aren't allowed, try again");
+    }
+}
+
+async fn create(
+    storage_client: &StorageClient,
+    tag: &str,
+    input: &mut impl BufRead,
+) -> Result<Player, Error> {
+    println!("Creating a new character");
+    let details = PlayerDetails {
+        name: prompt(input, "Name:")?,
+        description: prompt(input, "Description:")?,
+    };
+
+    let classes = classes::load(storage_client).await.unwrap_or_else(|err| {
+        println!("Unable to load the classes. {}", err);
+        Vec::new()
+    });
+    let class = choose_class(input, &classes)?;
+
+    let limits = Ruleset::default().stat_limits(experience::starting_level());
+    let stats = allocate_stats(input, limits.budget, |stats| limits.allows(stats))?;
+
+    let mut player = Player::new(tag.to_string(), details, stats);
+    if let Some(class) = class {
+        player.assign_class(class);
+    }
+
+    player.save_to(storage_client).await?;
+    Ok(player)
+}
+
+fn choose_class(input: &mut impl BufRead, classes: &[Class]) -> Result<Option<Class>, Error> {
+    if classes.is_empty() {
+        return Ok(None);
+    }
+
+    for (index, class) in classes.iter().enumerate() {
+        println!("  {}. {} - {}", index + 1, class.name, class.description);
+    }
+
+    loop {
+        let Some(index) = number(input, "Choose a class, or nothing to go without:")? else {
+            return Ok(None);
+        };
+
+        match classes.get(index.wrapping_sub(1)) {
+            Some(class) => return Ok(Some(class.clone())),
+            None => println!("There is no such class"),
+        }
+    }
+}
+
+fn level_up(input: &mut impl BufRead, player: &mut Player, levels: u8) -> Result<(), Error> {
+    player.unspent_points += POINTS_PER_LEVEL * i16::from(levels);
+    let points = player.unspent_points;
+    let limits = player.rules.stat_limits(player.level);
+    let current = player.stats.clone();
+    let base = player.base_stats();
+
+    let allocation = allocate_stats(input, points, |allocation| {
+        allocation.get_min() >= 0
+            && allocation.sum() <= points
+            && (current.clone() + allocation.clone() - base.clone()).get_max() <= limits.max
+    })?;
+
+    player.unspent_points = points - allocation.sum();
+    player.stats = current + allocation;
+    player.emit(GameEvent::StatsAllocated {
+        tag: player.tag.clone(),
+        name: player.name.clone(),
+        level: player.level,
+        stats: player.stats.clone() - base,
+        max_stat: limits.max,
+    });
+
+    Ok(())
+}
+
+fn fight(
+    input: &mut impl BufRead,
+    player: &mut Player,
+    enemy: Enemy,
+    rng: &mut impl Rng,
+) -> Result<(), Error> {
+    println!(
+        "\n== {} ==\n{}\n{} squares up to {}",
+        enemy.template.name, enemy.template.description, player.name, enemy.template.name
+    );
+    let mut combat = Combat::new(enemy);
+
+    while combat.outcome.is_none() {
+        println!(
+            "{} {}/{} | {} {}",
+            combat.enemy.template.name,
+            combat.enemy.health.max(0),
+            combat.enemy.template.health,
+            player.name,
+            player.get_health()
+        );
+
+        let action = loop {
+            let action = match prompt(input, "[a]ttack, [d]efend or [f]lee?")?.as_str() {
+                "a" => Ok(CombatAction::Attack),
+                "d" => Ok(CombatAction::Defend),
+                "f" => Ok(CombatAction::Flee),
+                answer => CombatAction::try_from(answer),
+            };
+
+            match action {
+                Ok(action) => break action,
+                Err(_) => println!("That isn't one of the choices"),
+            }
+        };
+
+        for line in combat.round(player, action, rng) {
+            println!("  {}", line);
+        }
+    }
+
+    if let Some(reward) = combat.reward(player) {
+        println!("{}", reward);
+    }
+    player.emit(GameEvent::Fought {
+        tag: player.tag.clone(),
+        name: player.name.clone(),
+        enemy: combat.enemy.template.name.clone(),
+        outcome: combat.outcome.unwrap_or(CombatOutcome::Withdrew),
+        rounds: combat.round,
+    });
+
+    Ok(())
+}
+
+fn browse(input: &mut impl BufRead, player: &mut Player, shop: &Shop) -> Result<(), Error> {
+    println!("\n== {} ==\n{}", shop.name, shop.description);
+
+    loop {
+        println!("You have {} gold", player.gold);
+        for (index, offer) in shop.stock.iter().enumerate() {
+            println!("  {}. {} ({} gold)", index + 1, offer.ware, offer.price);
+        }
+
+        let Some(index) = number(input, "Buy which, or nothing to leave?")? else {
+            return Ok(());
+        };
+
+        match shop.buy(player, index.wrapping_sub(1)) {
+            Ok(offer) => println!("Bought {}", offer.ware),
+            Err(err) => println!("{}", err),
+        }
+    }
+}
+
+fn travel(
+    input: &mut impl BufRead,
+    player: &mut Player,
+    catalogue: &EncounterCatalogue,
+) -> Result<(), Error> {
+    println!("  0. {}", WILDS);
+    let zones: Vec<_> = catalogue.zones.iter().collect();
+    for (index, (_, zone)) in zones.iter().enumerate() {
+        println!("  {}. {} - {}", index + 1, zone.name, zone.description);
+    }
+
+    match number(input, "Travel where, or nothing to stay?")? {
+        None => (),
+        Some(0) => {
+            player.travel(None, None);
+            println!("Travelling to {}", WILDS);
+        }
+        Some(index) => match zones.get(index - 1) {
+            Some((id, zone)) => {
+                player.travel(Some((*id).clone()), Some(zone));
+                println!("Travelling to {}", zone.name);
+            }
+            None => println!("There is no such zone"),
+        },
+    }
+
+    Ok(())
+}
+
+fn print_events(receiver: &mut UnboundedReceiver<GameEvent>) {
+    while let Ok(event) = receiver.try_recv() {
+        if let Some(line) = event.describe() {
+            println!("  {}", line);
+        }
+    }
+}
+
+fn status(player: &Player) -> String {
+    format!(
+        "{} | Level {} | Health {} | Score {} | Gold {}",
+        player.name,
+        player.level,
+        player.get_health(),
+        player.score,
+        player.gold
+    )
+}
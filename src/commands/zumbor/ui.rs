@@ -1,8 +1,17 @@
-use std::{collections::VecDeque, fmt, panic, sync::Arc, time::Duration};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt, panic,
+    sync::Arc,
+};
 
+use futures_util::{future::BoxFuture, StreamExt};
+use rand::{seq::SliceRandom, Rng};
 use serenity::{
     all::{
-        ChannelId, CreateActionRow, CreateButton, CreateMessage, EditMessage, Interaction, Message,
+        ButtonStyle, ChannelId, ComponentInteractionDataKind, CreateActionRow, CreateButton,
+        CreateEmbedFooter, CreateInteractionResponse, CreateInteractionResponseMessage,
+        CreateMessage, CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption, EditMessage,
+        Message, User,
     },
     builder::CreateEmbed,
     prelude::Context,
@@ -11,14 +20,24 @@ use serenity::{
 use crate::{errors::Error, utilities::await_interactions};
 
 use super::{
+    combat::{Combat, CombatAction, CombatOutcome},
     encounter::{Encounter, EncounterResult},
+    events::{EventListener, GameEvent},
+    items::ItemKind,
+    party::{Party, Resolution},
     player::Player,
+    rules::Ruleset,
+    shop::Shop,
+    zone::{Zone, WILDS},
 };
 
+/// Discord won't show more than 10 embeds on a single message
+const MAX_EMBEDS: usize = 10;
+
 pub struct UI<'a> {
     context: &'a Context,
     channel: ChannelId,
-    interaction: Option<Arc<Interaction>>,
+    rules: Ruleset,
     messages: VecDeque<CreateEmbed>,
 }
 
@@ -36,37 +55,58 @@ impl UI<'_> {
     pub async fn encounter_details(
         &mut self,
         encounter: &Encounter,
-        player: &Player,
+        party: &Party,
+        rng: &mut impl Rng,
     ) -> Result<(String, Message), crate::errors::Error> {
-        println!("{:?}", player);
-        let user_tag = player.tag.clone();
+        let mut embeds: Vec<CreateEmbed> =
+            party.players().into_iter().map(CreateEmbed::from).collect();
+        embeds.push(CreateEmbed::from(encounter).footer(CreateEmbedFooter::new(
+            "Spectators can react to cheer the party on",
+        )));
+
+        let deciders = party.deciders();
+        let components = if deciders.is_empty() {
+            Vec::new()
+        } else {
+            vec![encounter.action_row(&deciders)]
+        };
 
         let message = self
             .channel
             .send_message(
                 self.context,
-                CreateMessage::new()
-                    .embeds(vec![player.into(), encounter.into()])
-                    .components(vec![encounter.into()]),
+                CreateMessage::new().embeds(embeds).components(components),
             )
             .await;
 
         match message {
             Ok(message) => {
-                self.interaction = Some(Arc::new(Interaction::Component(
-                    await_interactions::component(self.context, &message, Arc::from(user_tag))
-                        .await?,
-                )));
-
-                let choice = self
-                    .interaction
-                    .as_ref()
-                    .expect("Interaction has just been set!")
-                    .as_message_component()
-                    .ok_or(Error::Plain("Message interaction was not collected"))?
-                    .data
-                    .custom_id
-                    .clone();
+                let choice = match party.resolution {
+                    // Nobody is able to choose so the choice is made for them
+                    _ if deciders.is_empty() => encounter
+                        .options_for(&party.players())
+                        .map(|(key, _)| key.clone())
+                        .collect::<Vec<String>>()
+                        .choose(rng)
+                        .cloned()
+                        .ok_or(Error::Plain("No options are available"))?,
+                    Resolution::Actor => {
+                        let user_tag = party.actor().tag.clone();
+                        await_interactions::component_within(
+                            self.context,
+                            &message,
+                            Arc::from(user_tag),
+                            self.rules.choice_timeout(),
+                        )
+                        .await?
+                        .data
+                        .custom_id
+                    }
+                    Resolution::Vote => {
+                        let voters = deciders.iter().map(|player| player.tag.clone()).collect();
+                        self.collect_votes(&message, voters).await?
+                    }
+                };
 
                 Ok((choice, message))
             }
@@ -79,40 +119,136 @@ impl UI<'_> {
         }
     }
 
+    /**
+     * Everyone outside the party who has reacted to the message
+     */
+    pub async fn spectators(&self, message: &Message, party: &Party) -> Vec<String> {
+        // The message that was sent doesn't keep up with its reactions so it is fetched again
+        let message = match self.channel.message(self.context, message.id).await {
+            Ok(message) => message,
+            Err(err) => {
+                println!("Unable to fetch the encounter's reactions. {}", err);
+                return Vec::new();
+            }
+        };
+
+        let mut spectators: Vec<User> = Vec::new();
+        for reaction in &message.reactions {
+            let users = match message
+                .reaction_users(self.context, reaction.reaction_type.clone(), None, None)
+                .await
+            {
+                Ok(users) => users,
+                Err(err) => {
+                    println!("Unable to fetch who reacted. {}", err);
+                    continue;
+                }
+            };
+
+            for user in users {
+                let in_party = party.members.iter().any(|member| member.user_id == user.id);
+                let counted = spectators.iter().any(|spectator| spectator.id == user.id);
+
+                if !user.bot && !in_party && !counted {
+                    spectators.push(user);
+                }
+            }
+        }
+
+        spectators.into_iter().map(|user| user.name).collect()
+    }
+
+    /**
+     * Takes votes from the party until everyone has voted or time runs out, returning the most
+     * popular option. Ties go to whichever option was voted for first
+     */
+    async fn collect_votes(&self, message: &Message, voters: Vec<String>) -> Result<String, Error> {
+        let voter_count = voters.len();
+        let mut votes: Vec<(String, String)> = Vec::new();
+
+        let mut interactions = message
+            .await_component_interactions(self.context)
+            .filter(move |interaction| voters.contains(&interaction.user.tag()))
+            .timeout(self.rules.choice_timeout())
+            .stream();
+
+        while let Some(interaction) = interactions.next().await {
+            let voter = interaction.user.tag();
+            let choice = interaction.data.custom_id.clone();
+
+            match votes.iter_mut().find(|(tag, _)| *tag == voter) {
+                Some(vote) => vote.1 = choice,
+                None => votes.push((voter, choice)),
+            }
+
+            interaction
+                .create_response(self.context, CreateInteractionResponse::Acknowledge)
+                .await?;
+
+            if votes.len() == voter_count {
+                break;
+            }
+        }
+
+        let mut tally: Vec<(&String, usize)> = Vec::new();
+        for (_, choice) in &votes {
+            match tally.iter_mut().find(|(option, _)| *option == choice) {
+                Some(count) => count.1 += 1,
+                None => tally.push((choice, 1)),
+            }
+        }
+
+        // Reversed so the earliest option wins ties, max_by_key keeps the last maximum
+        tally
+            .into_iter()
+            .rev()
+            .max_by_key(|(_, count)| *count)
+            .map(|(choice, _)| choice.clone())
+            .ok_or(Error::Plain("Nobody in the party voted"))
+    }
+
     pub async fn encounter_result(
         &mut self,
         result: &EncounterResult,
-        player: &Player,
+        summary: CreateEmbed,
         mut message: Message,
     ) -> Result<Message, Error> {
-        message.embeds.remove(0);
+        // Only the encounter is kept, the player embeds above it are out of date by now
+        let mut embeds: Vec<CreateEmbed> = message
+            .embeds
+            .pop()
+            .map(CreateEmbed::from)
+            .into_iter()
+            .collect();
+        embeds.push(summary);
+        embeds.push(result.into());
+
+        let room = MAX_EMBEDS
+            .saturating_sub(embeds.len())
+            .min(self.messages.len());
+        embeds.extend(self.messages.drain(..room));
 
         message
-            .edit(
-                self.context,
-                EditMessage::new()
-                    .add_embeds(vec![
-                        CreateEmbed::new().title(format!(
-                            "{} chose to {}",
-                            player.name,
-                            self.interaction
-                                .as_ref()
-                                .expect("Exists!")
-                                .as_message_component()
-                                .expect("It can only be a message component")
-                                .data
-                                .custom_id
-                        )),
-                        result.into(),
-                    ])
-                    .add_embeds(self.get_queued_messages().into()),
-            )
+            .edit(self.context, EditMessage::new().embeds(embeds))
             .await?;
 
+        // Whatever didn't fit follows straight after so nothing is shown out of order
+        if self.has_queued_messages() {
+            self.send_messages().await?;
+        }
+
         Ok(message)
     }
 
-    pub async fn request_continue(&self, player: &Player) -> Result<ContinueOption, Error> {
+    /**
+     * Asks the host whether to carry on, any member can check their bag in the meantime.
+     * Returns the choice along with the tag of whoever made it
+     */
+    pub async fn request_continue(
+        &self,
+        party: &Party,
+        can_travel: bool,
+    ) -> Result<(ContinueOption, String), Error> {
         println!("Requesting continue!");
         let message = self
             .channel
@@ -122,19 +258,38 @@ impl UI<'_> {
                     CreateButton::new(ContinueOption::Continue.to_string())
                         .label("Continue your journey"),
                     CreateButton::new(ContinueOption::Rest.to_string()).label("Take a break"),
+                    CreateButton::new(ContinueOption::Travel.to_string())
+                        .label("Travel")
+                        .style(ButtonStyle::Secondary)
+                        .disabled(!can_travel),
+                    CreateButton::new(ContinueOption::Inventory.to_string())
+                        .label("Check your bag")
+                        .style(ButtonStyle::Secondary)
+                        .disabled(
+                            party
+                                .players()
+                                .iter()
+                                .all(|player| player.inventory.is_empty()),
+                        ),
                 ])]),
             )
             .await;
 
         match message {
             Ok(message) => {
-                let user_tag = player.tag.clone();
+                let host_tag = party.host().tag.clone();
+                let tags = party.tags();
                 let context = self.context.clone();
 
                 let interaction = message
                     .await_component_interaction(self.context)
-                    .filter(move |interaction| interaction.user.tag() == user_tag)
-                    .timeout(Duration::new(120, 0))
+                    .filter(move |interaction| {
+                        let user_tag = interaction.user.tag();
+                        user_tag == host_tag
+                            || (interaction.data.custom_id == "inventory"
+                                && tags.contains(&user_tag))
+                    })
+                    .timeout(self.rules.continue_timeout())
                     .await
                     .ok_or(Error::Plain("Message interaction was not collected"))?;
 
@@ -147,7 +302,7 @@ impl UI<'_> {
 
                 let choice = interaction.data.custom_id.clone();
 
-                Ok(ContinueOption::from(choice))
+                Ok((ContinueOption::from(choice), interaction.user.tag()))
             }
             Err(err) => {
                 println!("{}", err);
@@ -158,6 +313,327 @@ impl UI<'_> {
         }
     }
 
+    /**
+     * Lets the player inspect and use the items in their inventory until they close it
+     */
+    pub async fn inventory(&self, player: &mut Player) -> Result<(), Error> {
+        let user_tag: Arc<str> = Arc::from(player.tag.as_str());
+
+        let message = self
+            .channel
+            .send_message(
+                self.context,
+                CreateMessage::new()
+                    .embeds(vec![(&*player).into(), inventory_embed(player, None)])
+                    .components(inventory_components(player)),
+            )
+            .await?;
+
+        loop {
+            let interaction = await_interactions::component_within(
+                self.context,
+                &message,
+                user_tag.clone(),
+                self.rules.choice_timeout(),
+            )
+            .await?;
+
+            let (embed, components) = match &interaction.data.kind {
+                ComponentInteractionDataKind::StringSelect { values } => {
+                    let index: Option<usize> = values.first().and_then(|value| value.parse().ok());
+                    match index.and_then(|index| player.inventory.get(index).map(|i| (index, i))) {
+                        Some((index, item)) => {
+                            let usable = matches!(item.kind, ItemKind::Consumable { .. });
+                            (
+                                CreateEmbed::from(item),
+                                vec![CreateActionRow::Buttons(vec![
+                                    CreateButton::new(format!("use:{}", index))
+                                        .label("Use")
+                                        .disabled(!usable),
+                                    CreateButton::new("back").label("Back"),
+                                    CreateButton::new("close")
+                                        .label("Close")
+                                        .style(ButtonStyle::Secondary),
+                                ])],
+                            )
+                        }
+                        None => (inventory_embed(player, None), inventory_components(player)),
+                    }
+                }
+                _ => match interaction.data.custom_id.as_str() {
+                    "close" => {
+                        interaction
+                            .create_response(self.context, CreateInteractionResponse::Acknowledge)
+                            .await?;
+                        break;
+                    }
+                    id => {
+                        let used = id
+                            .strip_prefix("use:")
+                            .and_then(|index| index.parse().ok())
+                            .and_then(|index| player.use_item(index))
+                            .map(|item| format!("{} used {}", player.name, item.name));
+                        (inventory_embed(player, used), inventory_components(player))
+                    }
+                },
+            };
+
+            interaction
+                .create_response(
+                    self.context,
+                    CreateInteractionResponse::UpdateMessage(
+                        CreateInteractionResponseMessage::new()
+                            .embeds(vec![(&*player).into(), embed])
+                            .components(components),
+                    ),
+                )
+                .await?;
+        }
+
+        if let Err(err) = message.delete(self.context).await {
+            println!("{}", err);
+        }
+
+        Ok(())
+    }
+
+    /**
+     * Asks the host where the party should head next
+     */
+    pub async fn travel(
+        &self,
+        party: &Party,
+        zones: &BTreeMap<String, Zone>,
+    ) -> Result<Destination, Error> {
+        let current = party.host().zone.clone();
+
+        // Select menus can hold at most 25 options, one of which is the wilds
+        let options = zones
+            .iter()
+            .take(24)
+            .map(|(id, zone)| {
+                CreateSelectMenuOption::new(&zone.name, id)
+                    .default_selection(current.as_ref() == Some(id))
+            })
+            .chain([CreateSelectMenuOption::new(WILDS, WILDS_VALUE)
+                .default_selection(current.is_none())])
+            .collect();
+
+        let message = self
+            .channel
+            .send_message(
+                self.context,
+                CreateMessage::new().components(vec![
+                    CreateActionRow::SelectMenu(
+                        CreateSelectMenu::new("zone", CreateSelectMenuKind::String { options })
+                            .placeholder("Pick a destination"),
+                    ),
+                    CreateActionRow::Buttons(vec![CreateButton::new("stay")
+                        .label("Stay")
+                        .style(ButtonStyle::Secondary)]),
+                ]),
+            )
+            .await?;
+
+        let interaction = await_interactions::component_within(
+            self.context,
+            &message,
+            Arc::from(party.host().tag.as_str()),
+            self.rules.choice_timeout(),
+        )
+        .await;
+
+        if let Err(err) = message.delete(self.context).await {
+            println!("{}", err);
+        }
+
+        let destination = match &interaction?.data.kind {
+            ComponentInteractionDataKind::StringSelect { values } => match values.first() {
+                Some(value) if value == WILDS_VALUE => Destination::Wilds,
+                Some(value) if zones.contains_key(value) => Destination::Zone(value.clone()),
+                _ => Destination::Stay,
+            },
+            _ => Destination::Stay,
+        };
+
+        Ok(match destination {
+            Destination::Wilds if current.is_none() => Destination::Stay,
+            Destination::Zone(id) if current.as_ref() == Some(&id) => Destination::Stay,
+            destination => destination,
+        })
+    }
+
+    /**
+     * Lets the player look over the shop's stock and buy what they can afford until they leave
+     */
+    pub async fn shop(&self, player: &mut Player, shop: &Shop) -> Result<(), Error> {
+        let user_tag: Arc<str> = Arc::from(player.tag.as_str());
+
+        let message = self
+            .channel
+            .send_message(
+                self.context,
+                CreateMessage::new()
+                    .embeds(vec![(&*player).into(), shop_embed(shop, None)])
+                    .components(shop_components(shop)),
+            )
+            .await?;
+
+        loop {
+            let interaction = await_interactions::component_within(
+                self.context,
+                &message,
+                user_tag.clone(),
+                self.rules.choice_timeout(),
+            )
+            .await?;
+
+            let (embed, components) = match &interaction.data.kind {
+                ComponentInteractionDataKind::StringSelect { values } => {
+                    let index: Option<usize> = values.first().and_then(|value| value.parse().ok());
+                    match index.and_then(|index| shop.stock.get(index).map(|o| (index, o))) {
+                        Some((index, offer)) => (
+                            CreateEmbed::from(offer),
+                            vec![CreateActionRow::Buttons(vec![
+                                CreateButton::new(format!("buy:{}", index))
+                                    .label("Buy")
+                                    .disabled(player.gold < offer.price),
+                                CreateButton::new("back").label("Back"),
+                                CreateButton::new("leave")
+                                    .label("Leave")
+                                    .style(ButtonStyle::Secondary),
+                            ])],
+                        ),
+                        None => (shop_embed(shop, None), shop_components(shop)),
+                    }
+                }
+                _ => match interaction.data.custom_id.as_str() {
+                    "leave" => {
+                        interaction
+                            .create_response(self.context, CreateInteractionResponse::Acknowledge)
+                            .await?;
+                        break;
+                    }
+                    id => {
+                        let notice = id
+                            .strip_prefix("buy:")
+                            .and_then(|index| index.parse().ok())
+                            .map(|index| match shop.buy(player, index) {
+                                Ok(offer) => format!(
+                                    "{} bought {} for {} gold",
+                                    player.name, offer.ware, offer.price
+                                ),
+                                Err(err) => err.to_string(),
+                            });
+                        (shop_embed(shop, notice), shop_components(shop))
+                    }
+                },
+            };
+
+            interaction
+                .create_response(
+                    self.context,
+                    CreateInteractionResponse::UpdateMessage(
+                        CreateInteractionResponseMessage::new()
+                            .embeds(vec![(&*player).into(), embed])
+                            .components(components),
+                    ),
+                )
+                .await?;
+        }
+
+        if let Err(err) = message.delete(self.context).await {
+            println!("{}", err);
+        }
+
+        Ok(())
+    }
+
+    /**
+     * Fights the combat out with the player a round at a time, editing the same message as it goes.
+     * Players who don't act in time hold their ground and defend
+     */
+    pub async fn combat(
+        &self,
+        player: &mut Player,
+        combat: &mut Combat,
+        rng: &mut impl Rng,
+    ) -> Result<CombatOutcome, Error> {
+        let user_tag: Arc<str> = Arc::from(player.tag.as_str());
+
+        let mut message = self
+            .channel
+            .send_message(
+                self.context,
+                CreateMessage::new()
+                    .embeds(vec![
+                        (&combat.enemy).into(),
+                        (&*player).into(),
+                        CreateEmbed::new().title(format!(
+                            "{} squares up to {}",
+                            player.name, combat.enemy.template.name
+                        )),
+                    ])
+                    .components(combat_components()),
+            )
+            .await?;
+
+        loop {
+            let interaction = await_interactions::component_within(
+                self.context,
+                &message,
+                user_tag.clone(),
+                self.rules.choice_timeout(),
+            )
+            .await
+            .ok();
+
+            let action = interaction
+                .as_ref()
+                .and_then(|interaction| {
+                    CombatAction::try_from(interaction.data.custom_id.as_str()).ok()
+                })
+                .unwrap_or(CombatAction::Defend);
+
+            let lines = combat.round(player, action, rng);
+            let round = CreateEmbed::new()
+                .title(format!("Round {}", combat.round))
+                .description(lines.join("\n"));
+            let embeds = vec![(&combat.enemy).into(), (&*player).into(), round];
+            let components = match combat.outcome {
+                Some(_) => Vec::new(),
+                None => combat_components(),
+            };
+
+            match interaction {
+                Some(interaction) => {
+                    interaction
+                        .create_response(
+                            self.context,
+                            CreateInteractionResponse::UpdateMessage(
+                                CreateInteractionResponseMessage::new()
+                                    .embeds(embeds)
+                                    .components(components),
+                            ),
+                        )
+                        .await?
+                }
+                None => {
+                    message
+                        .edit(
+                            self.context,
+                            EditMessage::new().embeds(embeds).components(components),
+                        )
+                        .await?
+                }
+            }
+
+            if let Some(outcome) = combat.outcome {
+                return Ok(outcome);
+            }
+        }
+    }
+
     pub fn queue_message(&mut self, message: CreateEmbed) {
         self.messages.push_back(message);
     }
@@ -169,22 +645,176 @@ impl UI<'_> {
         std::mem::take(&mut self.messages)
     }
 
+    pub fn has_queued_messages(&self) -> bool {
+        !self.messages.is_empty()
+    }
+
+    /**
+     * Sends the queued messages, split over as many messages as they need. Returns the last one sent
+     */
     pub async fn send_messages(&mut self) -> Result<Message, Error> {
-        let messages = self.get_queued_messages();
-        self.channel
-            .send_message(self.context, CreateMessage::new().embeds(messages.into()))
-            .await
-            .map_err(|err| {
-                dbg!(&err);
-                Error::Serenity(err)
-            })
+        let mut messages = self.get_queued_messages();
+        let mut sent = None;
+
+        while !messages.is_empty() {
+            let embeds: Vec<CreateEmbed> =
+                messages.drain(..MAX_EMBEDS.min(messages.len())).collect();
+
+            let message = self
+                .channel
+                .send_message(self.context, CreateMessage::new().embeds(embeds))
+                .await
+                .map_err(|err| {
+                    dbg!(&err);
+                    Error::Serenity(err)
+                })?;
+            sent = Some(message);
+        }
+
+        sent.ok_or(Error::Plain("There were no messages to send"))
+    }
+}
+
+impl EventListener for UI<'_> {
+    fn handle<'a>(&'a mut self, _ctx: &'a Context, event: &'a GameEvent) -> BoxFuture<'a, ()> {
+        if let Some(embed) = event.embed() {
+            self.queue_message(embed);
+        }
+        Box::pin(async {})
+    }
+}
+
+fn inventory_embed(player: &Player, notice: Option<String>) -> CreateEmbed {
+    let contents = player
+        .inventory
+        .iter()
+        .map(|item| format!("**{}** - {}", item.name, item.kind))
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    let embed = CreateEmbed::new()
+        .title(format!("{}'s bag", player.name))
+        .description(if contents.is_empty() {
+            "Empty...".to_string()
+        } else {
+            contents
+        });
+
+    match notice {
+        Some(notice) => embed.field("\u{200b}", notice, false),
+        None => embed,
+    }
+}
+
+fn inventory_components(player: &Player) -> Vec<CreateActionRow> {
+    let close = CreateActionRow::Buttons(vec![CreateButton::new("close")
+        .label("Close")
+        .style(ButtonStyle::Secondary)]);
+
+    if player.inventory.is_empty() {
+        return vec![close];
+    }
+
+    // Select menus can hold at most 25 options
+    let options = player
+        .inventory
+        .iter()
+        .enumerate()
+        .take(25)
+        .map(|(index, item)| {
+            CreateSelectMenuOption::new(&item.name, index.to_string())
+                .description(item.kind.to_string())
+        })
+        .collect();
+
+    vec![
+        CreateActionRow::SelectMenu(
+            CreateSelectMenu::new("item", CreateSelectMenuKind::String { options })
+                .placeholder("Pick an item"),
+        ),
+        close,
+    ]
+}
+
+fn shop_embed(shop: &Shop, notice: Option<String>) -> CreateEmbed {
+    let stock = shop
+        .stock
+        .iter()
+        .map(|offer| format!("**{}** - {} gold", offer.ware, offer.price))
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    let embed = CreateEmbed::new()
+        .title(&shop.name)
+        .description(&shop.description)
+        .field(
+            "Stock",
+            if stock.is_empty() {
+                "Sold out...".to_string()
+            } else {
+                stock
+            },
+            false,
+        );
+
+    match notice {
+        Some(notice) => embed.field("\u{200b}", notice, false),
+        None => embed,
     }
 }
 
+fn shop_components(shop: &Shop) -> Vec<CreateActionRow> {
+    let leave = CreateActionRow::Buttons(vec![CreateButton::new("leave")
+        .label("Leave")
+        .style(ButtonStyle::Secondary)]);
+
+    if shop.stock.is_empty() {
+        return vec![leave];
+    }
+
+    // Select menus can hold at most 25 options
+    let options = shop
+        .stock
+        .iter()
+        .enumerate()
+        .take(25)
+        .map(|(index, offer)| {
+            CreateSelectMenuOption::new(offer.ware.to_string(), index.to_string())
+                .description(format!("{} - {} gold", offer.ware.kind(), offer.price))
+        })
+        .collect();
+
+    vec![
+        CreateActionRow::SelectMenu(
+            CreateSelectMenu::new("offer", CreateSelectMenuKind::String { options })
+                .placeholder("Browse the wares"),
+        ),
+        leave,
+    ]
+}
+
+fn combat_components() -> Vec<CreateActionRow> {
+    vec![CreateActionRow::Buttons(
+        CombatAction::VALUES
+            .into_iter()
+            .map(|action| {
+                CreateButton::new(action.to_string())
+                    .label(action.to_string())
+                    .style(match action {
+                        CombatAction::Attack => ButtonStyle::Danger,
+                        CombatAction::Defend => ButtonStyle::Primary,
+                        CombatAction::Flee => ButtonStyle::Secondary,
+                    })
+            })
+            .collect(),
+    )]
+}
+
 #[derive(Default)]
 pub struct UIBuilder<'a> {
     context: Option<&'a Context>,
     channel: Option<ChannelId>,
+    rules: Option<Ruleset>,
 }
 
 impl<'a> UIBuilder<'a> {
@@ -198,6 +828,11 @@ impl<'a> UIBuilder<'a> {
         self
     }
 
+    pub fn rules(mut self, rules: Ruleset) -> Self {
+        self.rules = Some(rules);
+        self
+    }
+
     pub fn build(self) -> UI<'a> {
         UI {
             context: self
@@ -206,15 +841,26 @@ impl<'a> UIBuilder<'a> {
             channel: self
                 .channel
                 .expect("Channel should be added to the builder before building"),
-            interaction: None,
+            rules: self.rules.unwrap_or_default(),
             messages: VecDeque::new(),
         }
     }
 }
 
+/// Stands in for the wilds in the travel menu, where every other value is a zone id
+const WILDS_VALUE: &str = "~wilds";
+
+pub enum Destination {
+    Stay,
+    Wilds,
+    Zone(String),
+}
+
 pub enum ContinueOption {
     Continue,
     Rest,
+    Inventory,
+    Travel,
 }
 
 impl fmt::Display for ContinueOption {
@@ -222,6 +868,8 @@ impl fmt::Display for ContinueOption {
         match *self {
             ContinueOption::Continue => write!(f, "continue"),
             ContinueOption::Rest => write!(f, "rest"),
+            ContinueOption::Inventory => write!(f, "inventory"),
+            ContinueOption::Travel => write!(f, "travel"),
         }
     }
 }
@@ -231,6 +879,8 @@ impl From<String> for ContinueOption {
         match choice.as_str() {
             "continue" => ContinueOption::Continue,
             "rest" => ContinueOption::Rest,
+            "inventory" => ContinueOption::Inventory,
+            "travel" => ContinueOption::Travel,
             _ => panic!("Don't call me on strings that aren't correct"),
         }
     }
@@ -241,6 +891,8 @@ impl From<ContinueOption> for &str {
         match value {
             ContinueOption::Continue => "continue",
             ContinueOption::Rest => "rest",
+            ContinueOption::Inventory => "inventory",
+            ContinueOption::Travel => "travel",
         }
     }
 }
@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+use serenity::all::{Colour, CreateEmbed};
+
+use super::{
+    effects::{Effectable, LingeringEffect},
+    encounter::schema,
+    player::Player,
+};
+
+/// Zones are stored as `zumbor/zones/{id}.json`, encounters and saves refer to them by id
+pub const ZONE_PREFIX: &str = "zumbor/zones/";
+
+/// Where players without a zone are, finding the encounters that don't belong to one
+pub const WILDS: &str = "The Wilds";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Zone {
+    pub name: String,
+    pub description: String,
+    /// Encounters without a colour of their own take one of these
+    #[serde(default, deserialize_with = "schema::deserialize_palette")]
+    pub palette: Vec<Colour>,
+    /// Given to everyone who travels into the zone
+    #[serde(default)]
+    pub environment: Option<LingeringEffect>,
+}
+
+impl Zone {
+    /// The zone's id from the path of the file it was loaded from
+    pub fn id_from_path(path: &str) -> String {
+        path.trim_start_matches(ZONE_PREFIX)
+            .trim_end_matches(".json")
+            .to_string()
+    }
+}
+
+impl From<&Zone> for CreateEmbed {
+    fn from(zone: &Zone) -> Self {
+        let embed = CreateEmbed::new()
+            .title(format!("Travelling to {}", zone.name))
+            .description(&zone.description)
+            .colour(zone.palette.first().copied().unwrap_or_default());
+
+        match &zone.environment {
+            Some(effect) => embed.field(
+                format!("{} {}", effect.name, effect.kind),
+                format!("{} for {} turns", effect.potency, effect.duration),
+                true,
+            ),
+            None => embed,
+        }
+    }
+}
+
+impl Player {
+    /**
+     * Moves the player into the zone, or back into the wilds, exposing them to its environment
+     */
+    pub fn travel(&mut self, id: Option<String>, zone: Option<&Zone>) {
+        self.zone = id;
+
+        if let Some(effect) = zone.and_then(|zone| zone.environment.clone()) {
+            self.add_effect(effect);
+        }
+    }
+}
@@ -3,24 +3,46 @@ use serenity::framework::standard::CommandError;
 #[derive(Debug)]
 pub enum Error {
     Plain(&'static str),
+    Validation(Vec<String>),
     Serenity(serenity::Error),
     Json(serde_json::Error),
     Cloud(cloud_storage::Error),
     Io(std::io::Error),
     Reqwest(reqwest::Error),
     Songbird(songbird::error::JoinError),
+    Openssl(openssl::error::ErrorStack),
+}
+
+impl Error {
+    /// Whether the error came from asking storage for something that isn't there
+    pub fn is_not_found(&self) -> bool {
+        match self {
+            Error::Io(err) => err.kind() == std::io::ErrorKind::NotFound,
+            Error::Cloud(cloud_storage::Error::Google(response)) => response.error.code == 404,
+            Error::Cloud(cloud_storage::Error::Reqwest(err)) => {
+                err.status().map(|status| status.as_u16()) == Some(404)
+            }
+            // Downloads of missing objects come back with the body of the 404
+            Error::Cloud(cloud_storage::Error::Other(message)) => {
+                message.contains("No such object")
+            }
+            _ => false,
+        }
+    }
 }
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Error::Plain(str) => write!(f, "{}", str),
+            Error::Validation(issues) => write!(f, "{}", issues.join("\n")),
             Error::Serenity(err) => write!(f, "{:?}", err),
             Error::Json(err) => write!(f, "{:?}", err),
             Error::Cloud(err) => write!(f, "{:?}", err),
             Error::Io(err) => write!(f, "{:?}", err),
             Error::Reqwest(err) => write!(f, "{:?}", err),
             Error::Songbird(err) => write!(f, "{:?}", err),
+            Error::Openssl(err) => write!(f, "{:?}", err),
         }
     }
 }
@@ -58,6 +80,12 @@ impl From<songbird::error::JoinError> for Error {
     }
 }
 
+impl From<openssl::error::ErrorStack> for Error {
+    fn from(value: openssl::error::ErrorStack) -> Self {
+        Error::Openssl(value)
+    }
+}
+
 impl From<Error> for CommandError {
     fn from(value: Error) -> Self {
         CommandError::from(value.to_string())
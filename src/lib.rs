@@ -0,0 +1,5 @@
+pub mod commands;
+pub mod errors;
+pub mod storage;
+pub mod utilities;
+pub mod voice;
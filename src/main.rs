@@ -1,11 +1,6 @@
-mod commands;
-mod errors;
-mod storage;
-mod utilities;
-mod voice;
-
-use commands::zumbor::ZumborInstances;
 use dotenv::dotenv;
+use rusty_ziplod::commands::zumbor::ZumborInstances;
+use rusty_ziplod::storage::StorageClient;
 use serenity::all::standard::Configuration;
 use serenity::client::{Client, EventHandler};
 use serenity::framework::standard::macros::group;
@@ -13,15 +8,14 @@ use serenity::framework::StandardFramework;
 use serenity::prelude::GatewayIntents;
 use songbird::serenity::SerenityInit;
 use std::env;
-use storage::StorageClient;
 
-use commands::{
+use rusty_ziplod::commands::{
     add::ADD_COMMAND, list::LIST_COMMAND, ping::PING_COMMAND, play::PLAY_COMMAND,
     themes::THEME_COMMAND, zumbor::ZUMBOR_COMMAND,
 };
 
 #[cfg(feature = "chat")]
-use commands::chat::{ChatBot, CHAT_COMMAND};
+use rusty_ziplod::commands::chat::{ChatBot, CHAT_COMMAND};
 
 #[group]
 #[commands(ping, zumbor, play, add, list, theme)]
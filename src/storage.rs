@@ -1,16 +1,36 @@
 use crate::errors::Error;
 
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
 use bytes::Bytes;
-use cloud_storage::{Client, ListRequest, Object};
-use futures_util::{Stream, StreamExt, TryStreamExt};
+use cloud_storage::{Client, ListRequest};
+use futures_util::{future::Either, stream, Stream, TryStreamExt};
 use rand::seq::SliceRandom;
 use serde::Deserialize;
 use serenity::prelude::TypeMapKey;
 
 #[derive(Debug)]
 pub struct StorageClient {
-    pub client: cloud_storage::Client,
-    pub bucket_name: String,
+    backend: Backend,
+}
+
+#[derive(Debug)]
+enum Backend {
+    Bucket {
+        client: cloud_storage::Client,
+        bucket_name: String,
+    },
+    /// Files under a local directory, named by their path from it, for tools run away from the bucket
+    Directory(PathBuf),
+}
+
+/// A stored file, named by its full path
+#[derive(Debug, Clone)]
+pub struct StoredObject {
+    pub name: String,
 }
 
 impl StorageClient {
@@ -18,42 +38,78 @@ impl StorageClient {
         let client = Client::new();
 
         StorageClient {
-            client,
-            bucket_name,
+            backend: Backend::Bucket {
+                client,
+                bucket_name,
+            },
+        }
+    }
+
+    /**
+     * Reads and writes files under the directory instead of the bucket, laid out the same way
+     */
+    pub fn local(directory: impl Into<PathBuf>) -> Self {
+        StorageClient {
+            backend: Backend::Directory(directory.into()),
         }
     }
 
     pub async fn delete(&self, path: &str) -> Result<(), Error> {
-        self.client
-            .object()
-            .delete(&self.bucket_name, path)
-            .await
-            .map_err(|err| {
-                println!("{}", err);
-                Error::Plain("Failed to remove file")
-            })
+        match &self.backend {
+            Backend::Bucket {
+                client,
+                bucket_name,
+            } => client
+                .object()
+                .delete(bucket_name, path)
+                .await
+                .map_err(|err| {
+                    println!("{}", err);
+                    Error::Plain("Failed to remove file")
+                }),
+            Backend::Directory(root) => fs::remove_file(root.join(path)).map_err(Error::from),
+        }
     }
 
     pub async fn get_stream(
         &self,
         path: &str,
     ) -> Result<impl Stream<Item = Result<u8, Error>>, Error> {
-        let object = self.client.object();
-        let stream = object
-            .download_streamed(&self.bucket_name, path)
-            .await
-            .map_err(|err| -> Error { err.into() })?
-            .map_err(|err| -> Error { err.into() });
-
-        Ok(stream)
+        match &self.backend {
+            Backend::Bucket {
+                client,
+                bucket_name,
+            } => {
+                let object = client.object();
+                let stream = object
+                    .download_streamed(bucket_name, path)
+                    .await
+                    .map_err(|err| -> Error { err.into() })?
+                    .map_err(|err| -> Error { err.into() });
+
+                Ok(Either::Left(stream))
+            }
+            Backend::Directory(root) => {
+                let bytes = fs::read(root.join(path))?;
+                Ok(Either::Right(stream::iter(bytes.into_iter().map(Ok))))
+            }
+        }
     }
 
     pub async fn get(&self, path: &str) -> Result<Vec<u8>, Error> {
-        let object = self.client.object();
-        object
-            .download(&self.bucket_name, path)
-            .await
-            .map_err(|o| o.into())
+        match &self.backend {
+            Backend::Bucket {
+                client,
+                bucket_name,
+            } => {
+                let object = client.object();
+                object
+                    .download(bucket_name, path)
+                    .await
+                    .map_err(|o| o.into())
+            }
+            Backend::Directory(root) => fs::read(root.join(path)).map_err(Error::from),
+        }
     }
 
     pub async fn delete_json(&self, path: &str) -> Result<(), Error> {
@@ -72,10 +128,18 @@ impl StorageClient {
         path: &str,
         mime_type: &str,
     ) -> Result<(), Error> {
-        self.client
-            .object()
-            .create(&self.bucket_name, content.into(), path, mime_type)
-            .await?;
+        match &self.backend {
+            Backend::Bucket {
+                client,
+                bucket_name,
+            } => {
+                client
+                    .object()
+                    .create(bucket_name, content.into(), path, mime_type)
+                    .await?;
+            }
+            Backend::Directory(root) => write_file(&root.join(path), content.into())?,
+        }
         Ok(())
     }
 
@@ -89,10 +153,25 @@ impl StorageClient {
         length: impl Into<Option<u64>>,
         mime_type: &str,
     ) -> Result<(), Error> {
-        let res = self
-            .client
+        let (client, bucket_name) = match &self.backend {
+            Backend::Bucket {
+                client,
+                bucket_name,
+            } => (client, bucket_name),
+            Backend::Directory(root) => {
+                let content: Vec<u8> = stream
+                    .try_fold(Vec::new(), |mut content, chunk| async move {
+                        content.extend_from_slice(&chunk);
+                        Ok(content)
+                    })
+                    .await?;
+                return write_file(&root.join(path), content);
+            }
+        };
+
+        let res = client
             .object()
-            .create_streamed(&self.bucket_name, stream, length, path, mime_type)
+            .create_streamed(bucket_name, stream, length, path, mime_type)
             .await;
 
         if let Err(err) = res {
@@ -123,12 +202,29 @@ impl StorageClient {
         Ok(objs.len())
     }
 
-    pub async fn get_objects(&self, prefix: &str) -> Result<Vec<Object>, Error> {
-        let list = self
-            .client
+    pub async fn get_objects(&self, prefix: &str) -> Result<Vec<StoredObject>, Error> {
+        let (client, bucket_name) = match &self.backend {
+            Backend::Bucket {
+                client,
+                bucket_name,
+            } => (client, bucket_name),
+            Backend::Directory(root) => {
+                let mut names = Vec::new();
+                list_directory(root, root, &mut names)?;
+                names.retain(|name| name.starts_with(prefix));
+                names.sort();
+
+                return Ok(names
+                    .into_iter()
+                    .map(|name| StoredObject { name })
+                    .collect());
+            }
+        };
+
+        let list = client
             .object()
             .list(
-                &self.bucket_name,
+                bucket_name,
                 ListRequest {
                     prefix: Some(prefix.to_owned()),
                     max_results: Some(1000),
@@ -137,13 +233,50 @@ impl StorageClient {
             )
             .await?;
 
-        let items = match Box::pin(list).next().await {
-            Some(list) => list?.items,
-            None => Vec::new(),
-        };
+        // Each item of the stream is one page of up to 1000 objects, the next is fetched as it is polled
+        let items = Box::pin(list)
+            .map_ok(|page| page.items)
+            .try_concat()
+            .await?;
+
+        Ok(items
+            .into_iter()
+            .map(|object| StoredObject { name: object.name })
+            .collect())
+    }
+}
+
+fn write_file(path: &Path, content: Vec<u8>) -> Result<(), Error> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::write(path, content).map_err(Error::from)
+}
 
-        Ok(items)
+/**
+ * Collects the name of every file under the directory, as a path from the root with forward slashes
+ */
+fn list_directory(root: &Path, directory: &Path, names: &mut Vec<String>) -> Result<(), Error> {
+    if !directory.is_dir() {
+        return Ok(());
     }
+
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            list_directory(root, &path, names)?;
+        } else if let Ok(name) = path.strip_prefix(root) {
+            let parts: Vec<String> = name
+                .components()
+                .map(|part| part.as_os_str().to_string_lossy().into_owned())
+                .collect();
+            names.push(parts.join("/"));
+        }
+    }
+
+    Ok(())
 }
 
 impl TypeMapKey for StorageClient {
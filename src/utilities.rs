@@ -1,3 +1,4 @@
 pub mod await_interactions;
 pub mod message;
+pub mod permissions;
 pub mod random;
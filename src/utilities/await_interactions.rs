@@ -8,15 +8,30 @@ use serenity::{
 
 use crate::errors::Error;
 
+/// How long a user has to interact with a component when nothing says otherwise
+pub(crate) const COMPONENT_TIMEOUT: Duration = Duration::from_secs(240);
+
+/// How long a user has to fill in and submit a modal when nothing says otherwise
+pub(crate) const MODAL_TIMEOUT: Duration = Duration::from_secs(120);
+
 pub(crate) async fn component(
     context: &Context,
     message: &Message,
     user_tag: Arc<str>,
+) -> Result<ComponentInteraction, Error> {
+    component_within(context, message, user_tag, COMPONENT_TIMEOUT).await
+}
+
+pub(crate) async fn component_within(
+    context: &Context,
+    message: &Message,
+    user_tag: Arc<str>,
+    timeout: Duration,
 ) -> Result<ComponentInteraction, Error> {
     message
         .await_component_interaction(context)
         .filter(move |interaction| interaction.user.tag() == user_tag.as_ref())
-        .timeout(Duration::new(240, 0))
+        .timeout(timeout)
         .await
         .ok_or(Error::Plain(
             "Message Component interaction was not collected",
@@ -26,11 +41,42 @@ pub(crate) async fn modal(
     context: &Context,
     message: &Message,
     user_tag: Arc<str>,
+) -> Result<ModalInteraction, Error> {
+    modal_within(context, message, user_tag, MODAL_TIMEOUT).await
+}
+
+pub(crate) async fn modal_within(
+    context: &Context,
+    message: &Message,
+    user_tag: Arc<str>,
+    timeout: Duration,
 ) -> Result<ModalInteraction, Error> {
     message
         .await_modal_interaction(context)
         .filter(move |interaction| interaction.user.tag() == user_tag.as_ref())
-        .timeout(Duration::new(120, 0))
+        .timeout(timeout)
         .await
         .ok_or(Error::Plain("Modal interaction was not collected"))
 }
+
+/**
+ * Waits for a component interaction from anyone allowed to manage messages in the guild
+ */
+pub(crate) async fn moderator_component(
+    context: &Context,
+    message: &Message,
+    timeout: Duration,
+) -> Result<ComponentInteraction, Error> {
+    message
+        .await_component_interaction(context)
+        .filter(|interaction| {
+            interaction
+                .member
+                .as_ref()
+                .and_then(|member| member.permissions)
+                .is_some_and(|permissions| permissions.manage_messages())
+        })
+        .timeout(timeout)
+        .await
+        .ok_or(Error::Plain("No moderator responded in time"))
+}
@@ -0,0 +1,34 @@
+use serenity::{
+    model::{prelude::Message, Permissions},
+    prelude::Context,
+};
+
+/**
+ * Whether the author of the message is an administrator of the guild it was sent in
+ */
+pub async fn is_admin(ctx: &Context, msg: &Message) -> bool {
+    author_permissions(ctx, msg)
+        .await
+        .is_some_and(|permissions| permissions.administrator())
+}
+
+/**
+ * Whether the author of the message can manage messages in the guild it was sent in, as moderators can
+ */
+pub async fn is_moderator(ctx: &Context, msg: &Message) -> bool {
+    author_permissions(ctx, msg)
+        .await
+        .is_some_and(|permissions| permissions.manage_messages())
+}
+
+async fn author_permissions(ctx: &Context, msg: &Message) -> Option<Permissions> {
+    let guild_id = msg.guild_id?;
+
+    match guild_id.member(ctx, msg.author.id).await {
+        Ok(member) => member.permissions(ctx).ok(),
+        Err(err) => {
+            println!("Unable to fetch member permissions. {}", err);
+            None
+        }
+    }
+}